@@ -0,0 +1,69 @@
+pub const MAX_AD_ID_LENGTH: usize = 32;
+pub const MAX_AD_URL_LENGTH: usize = 200;
+pub const MAX_AD_CONTENT_LENGTH: usize = 500;
+pub const MAX_CONTENT_URI_LENGTH: usize = 200;
+pub const MAX_MIME_TYPE_LENGTH: usize = 64;
+pub const DEFAULT_FEE_PER_AD: u64 = 5_000; // 0.005 SOL
+pub const MIN_AD_REWARD: u64 = 1_000; // 0.001 SOL
+pub const TRANSACTION_TIMEOUT: i64 = 300; // 5 minutes
+pub const MAX_SINGLE_DEPOSIT: u64 = 10_000_000_000; // 10 SOL
+pub const BASE_TRANSACTION_FEE: u64 = 5_000; // Base fee in lamports (0.005 SOL)
+pub const MIN_AD_VIEW_TIME: i64 = 5; // Minimum 5 seconds to view ad
+pub const DEFAULT_RELAYER_FEE: u64 = 10_000; // Covers the relayer's initiate + complete tx fees
+pub const MAX_BATCH_RECIPIENTS: usize = 10;
+pub const MIN_SUBSCRIPTION_INTERVAL: i64 = 3_600; // 1 hour
+pub const MAX_SUBSCRIPTION_PAYMENTS: u32 = 120;
+pub const MAX_CRANK_FEE: u64 = 10_000; // Covers the executor's transaction fee
+
+// Account layout versions, bumped whenever a deployed layout changes.
+// Accounts from before versioning read back as version 0.
+pub const STATE_VERSION: u8 = 1;
+pub const AD_VERSION: u8 = 1;
+pub const REQUEST_VERSION: u8 = 1;
+pub const PROGRAM_STATE_SPACE: usize = 8 + 235;
+pub const ADVERTISEMENT_SPACE: usize = 8 + 1409;
+
+// Reference-mode ads leave the inline url and content empty, so only their
+// length prefixes are allocated
+pub const REFERENCE_AD_SPACE: usize = 8 // discriminator
+    + (4 + MAX_AD_ID_LENGTH) // id
+    + 4 // url
+    + 4 // content
+    + 8 + 8 + 1 + 8 + 8 + 1 + 1 // reward_amount ..= version
+    + 32 + 8 + 8 + 8 // advertiser, bid_per_view, budget, reserved_budget
+    + 2 + 8 // max_impressions_per_user, frequency_window
+    + 4 + (4 + MAX_AD_LOCALES * 2) + 8 + (4 + MAX_AD_TARGET_RECIPIENTS * 32) // targeting
+    + 1 + (4 + MAX_CONTENT_URI_LENGTH) + 32 + (4 + MAX_MIME_TYPE_LENGTH) // creative
+    + 1 + 8 + 4 // rejection_reason, ends_at, report_count
+    + 8 + 1 // click_count, billing_model
+    + 32 + 8 + 8 // conversion_authority, attribution_window, conversions
+    + (1 + 32); // campaign
+const _: () = assert!(
+    ADVERTISEMENT_SPACE == REFERENCE_AD_SPACE + MAX_AD_URL_LENGTH + MAX_AD_CONTENT_LENGTH
+);
+pub const TRANSACTION_REQUEST_SPACE: usize = 8 + 321;
+pub const LEGACY_TRANSACTION_REQUEST_SPACE: usize = 8 + 162;
+
+// Pausable operations, as bits of `ProgramState::paused_operations`
+pub const PAUSE_INITIATE: u8 = 1 << 0;
+pub const PAUSE_COMPLETE: u8 = 1 << 1;
+pub const PAUSE_DEPOSIT: u8 = 1 << 2;
+pub const PAUSE_WITHDRAW: u8 = 1 << 3;
+pub const PAUSE_AD_MANAGEMENT: u8 = 1 << 4;
+pub const PAUSE_ALL: u8 = PAUSE_INITIATE | PAUSE_COMPLETE | PAUSE_DEPOSIT | PAUSE_WITHDRAW | PAUSE_AD_MANAGEMENT;
+pub const DEFAULT_MIN_LIQUID_BUFFER: u64 = 1_000_000_000; // 1 SOL kept liquid for sponsorship
+pub const DEFAULT_OUTFLOW_WINDOW_SLOTS: u64 = 9_000; // ~1 hour at 400ms slots
+pub const MAX_POOL_ID_LENGTH: usize = 32;
+pub const MAX_POOL_RECIPIENTS: usize = 5;
+pub const MAX_POOL_ADS: usize = 5;
+pub const MAX_FEE_BPS: u16 = 10_000;
+pub const MAX_TRACKED_ADS: usize = 16; // Capped ads tracked per user
+pub const MAX_AUCTION_ADS: usize = 8; // Ads registered to bid in placement auctions
+pub const MAX_CAMPAIGN_ID_LENGTH: usize = 32;
+pub const MAX_CAMPAIGN_VARIANTS: usize = 5;
+pub const MAX_AD_LOCALES: usize = 4;
+pub const MAX_AD_TARGET_RECIPIENTS: usize = 4;
+pub const DEFAULT_REPORT_THRESHOLD: u32 = 10;
+pub const CLICK_WINDOW: i64 = 3_600; // Clicks count for an hour after the view
+pub const DEFAULT_PUBLISHER_SHARE_BPS: u16 = 1_000; // 10% of each ad charge
+pub const SPONSORSHIP_PRECISION: u128 = 1_000_000_000_000_000_000; // Scale of sponsorship attribution
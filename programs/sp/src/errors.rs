@@ -0,0 +1,175 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum FeePaymentError {
+    #[msg("Unauthorized access")]
+    Unauthorized,
+    #[msg("Invalid amount")]
+    InvalidAmount,
+    #[msg("Invalid recipient address")]
+    InvalidRecipient,
+    #[msg("Invalid fee amount")]
+    InvalidFee,
+    #[msg("Program has insufficient funds to sponsor gas fees")]
+    InsufficientProgramFunds,
+    #[msg("Invalid ad ID")]
+    InvalidAdId,
+    #[msg("Invalid ad URL - must be HTTPS")]
+    InvalidAdUrl,
+    #[msg("Invalid ad content")]
+    InvalidAdContent,
+    #[msg("Invalid display time")]
+    InvalidDisplayTime,
+    #[msg("Reward amount too low")]
+    RewardTooLow,
+    #[msg("Program is paused")]
+    ProgramPaused,
+    #[msg("Request has expired")]
+    RequestExpired,
+    #[msg("Invalid request status")]
+    InvalidStatus,
+    #[msg("Advertisement not active")]
+    AdNotActive,
+    #[msg("Recipient address mismatch")]
+    RecipientMismatch,
+    #[msg("Ad ID mismatch")]
+    AdMismatch,
+    #[msg("Ad display not started")]
+    AdNotStarted,
+    #[msg("Insufficient ad viewing time")]
+    InsufficientViewTime,
+    #[msg("Math overflow")]
+    MathOverflow,
+    #[msg("Math underflow")]
+    MathUnderflow,
+    #[msg("Relayer account required for relayed request")]
+    RelayerRequired,
+    #[msg("Relayer not active")]
+    RelayerNotActive,
+    #[msg("Relayer account passed for a request that was not relayed")]
+    UnexpectedRelayer,
+    #[msg("Insufficient user profile balance")]
+    InsufficientProfileBalance,
+    #[msg("Missing Ed25519 signature instruction")]
+    MissingSignatureInstruction,
+    #[msg("Invalid Ed25519 signature instruction")]
+    InvalidSignatureInstruction,
+    #[msg("Signed intent signer mismatch")]
+    SignerMismatch,
+    #[msg("Signed intent does not match request")]
+    IntentMismatch,
+    #[msg("Signed intent nonce is not the user's next nonce")]
+    InvalidNonce,
+    #[msg("Invalid number of batch recipients")]
+    InvalidBatchSize,
+    #[msg("Invalid subscription interval")]
+    InvalidInterval,
+    #[msg("Invalid number of subscription payments")]
+    InvalidPaymentCount,
+    #[msg("Invalid sponsorship mode for this action")]
+    InvalidSponsorship,
+    #[msg("Subscription payment not due yet")]
+    PaymentNotDue,
+    #[msg("Ad for this cycle not viewed")]
+    AdNotViewed,
+    #[msg("Escrow account required for escrowed request")]
+    EscrowRequired,
+    #[msg("Escrow account mismatch")]
+    EscrowMismatch,
+    #[msg("Refund deadline must be after the request timeout")]
+    InvalidRefundDeadline,
+    #[msg("Request has not expired yet")]
+    RequestNotExpired,
+    #[msg("Staking would leave the treasury below its liquid buffer")]
+    LiquidBufferTooLow,
+    #[msg("Invalid sponsor pool ID")]
+    InvalidPoolId,
+    #[msg("Too many sponsor pool eligibility entries")]
+    TooManyPoolRules,
+    #[msg("Sponsor pool not active")]
+    PoolNotActive,
+    #[msg("Request not eligible for this sponsor pool")]
+    NotEligibleForPool,
+    #[msg("Sponsor pool has insufficient funds")]
+    InsufficientPoolFunds,
+    #[msg("Sponsor pool account required for pool-sponsored request")]
+    PoolRequired,
+    #[msg("Sponsor pool account mismatch")]
+    PoolMismatch,
+    #[msg("Merchant sponsor account required")]
+    MerchantSponsorRequired,
+    #[msg("Merchant sponsor has insufficient budget")]
+    InsufficientMerchantBudget,
+    #[msg("Address is blocked")]
+    AddressBlocked,
+    #[msg("Address is not on the allowlist")]
+    AddressNotAllowed,
+    #[msg("Invalid blocklist or allowlist account")]
+    InvalidAccessEntry,
+    #[msg("Ad account required to release its reserved budget")]
+    AdRequired,
+    #[msg("Insufficient unreserved ad budget")]
+    InsufficientAdBudget,
+    #[msg("Invalid frequency cap")]
+    InvalidFrequencyCap,
+    #[msg("Ad frequency cap reached for this user")]
+    FrequencyCapReached,
+    #[msg("Invalid ad targeting")]
+    InvalidTargeting,
+    #[msg("Ad targeting does not match this send")]
+    AdNotTargeted,
+    #[msg("Auction registry is full")]
+    AdRegistryFull,
+    #[msg("Ad is not registered for auctions")]
+    AdNotRegistered,
+    #[msg("Ad can still win auctions")]
+    AdStillEligible,
+    #[msg("Auction candidates must be every registered ad, in order")]
+    AuctionCandidatesMismatch,
+    #[msg("Content URI must use ar://, ipfs:// or https://")]
+    InvalidContentUri,
+    #[msg("Invalid creative hash or MIME type")]
+    InvalidCreative,
+    #[msg("Action not allowed in the ad's current status")]
+    InvalidAdStatus,
+    #[msg("Invalid ad end time")]
+    InvalidAdSchedule,
+    #[msg("Report threshold must be at least one")]
+    InvalidReportThreshold,
+    #[msg("Only users who were shown this ad can report it")]
+    NoViewReceipt,
+    #[msg("No recent view of this ad to click through")]
+    ClickWindowClosed,
+    #[msg("This view was already clicked")]
+    AlreadyClicked,
+    #[msg("Invalid attribution window")]
+    InvalidAttributionWindow,
+    #[msg("No view of this ad within the attribution window")]
+    AttributionWindowClosed,
+    #[msg("This view already has a conversion")]
+    AlreadyConverted,
+    #[msg("Invalid campaign ID")]
+    InvalidCampaignId,
+    #[msg("Campaign is not active or has no weighted variants")]
+    CampaignNotActive,
+    #[msg("Ad is not a variant of this campaign")]
+    InvalidCampaignVariant,
+    #[msg("Campaign account is required for a campaign's ads")]
+    CampaignRequired,
+    #[msg("Too many campaign variants")]
+    TooManyVariants,
+    #[msg("Selected ad is not the user's assigned campaign variant")]
+    VariantMismatch,
+    #[msg("Invalid pause flags")]
+    InvalidPauseFlags,
+    #[msg("Publisher not active")]
+    PublisherNotActive,
+    #[msg("Publisher account required for this request")]
+    PublisherRequired,
+    #[msg("Publisher account does not match the request")]
+    PublisherMismatch,
+    #[msg("Outflow window must be at least one slot")]
+    InvalidOutflowWindow,
+    #[msg("Account is already at the current version")]
+    AlreadyMigrated,
+}
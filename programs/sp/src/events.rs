@@ -0,0 +1,655 @@
+use anchor_lang::prelude::*;
+
+use crate::state::*;
+
+#[event]
+pub struct ProgramInitialized {
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FundsDeposited {
+    pub admin: Pubkey,
+    pub amount: u64,
+    pub total_funds: u64,
+}
+
+#[event]
+pub struct SponsorPoolCreated {
+    pub pool: Pubkey,
+    pub pool_id: String,
+    pub authority: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct SponsorPoolRulesUpdated {
+    pub pool: Pubkey,
+    pub base_fee: u64,
+    pub fee_bps: u16,
+    pub max_amount: u64,
+}
+
+#[event]
+pub struct SponsorPoolToggled {
+    pub pool: Pubkey,
+    pub is_active: bool,
+}
+
+#[event]
+pub struct SponsorPoolFunded {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct SponsorPoolWithdrawn {
+    pub pool: Pubkey,
+    pub amount: u64,
+    pub remaining: u64,
+}
+
+#[event]
+pub struct PoolSponsorship {
+    pub pool: Pubkey,
+    pub pool_id: String,
+    pub user: Pubkey,
+    pub gas_fee: u64,
+    pub remaining_balance: u64,
+}
+
+#[event]
+pub struct MerchantSponsorRegistered {
+    pub merchant: Pubkey,
+    pub skip_ad: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MerchantSponsorUpdated {
+    pub merchant: Pubkey,
+    pub skip_ad: bool,
+    pub is_active: bool,
+}
+
+#[event]
+pub struct MerchantSponsorFunded {
+    pub merchant: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub budget: u64,
+}
+
+#[event]
+pub struct MerchantSponsorWithdrawn {
+    pub merchant: Pubkey,
+    pub amount: u64,
+    pub remaining: u64,
+}
+
+#[event]
+pub struct MerchantSponsoredPayment {
+    pub merchant: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub gas_fee: u64,
+    pub ad_skipped: bool,
+    pub remaining_budget: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SponsorDonation {
+    pub donor: Pubkey,
+    pub amount: u64,
+    pub total_contributed: u64,
+    pub total_funds: u64,
+}
+
+#[event]
+pub struct SponsorshipAttributed {
+    pub donor: Pubkey,
+    pub total_contributed: u64,
+    pub sponsored_count: u64,
+}
+
+#[event]
+pub struct AdCreated {
+    pub ad_id: String,
+    pub reward_amount: u64,
+    pub display_duration: i64,
+    pub creator: Pubkey,
+    pub status: AdStatus,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdToggled {
+    pub ad_id: String,
+    pub status: AdStatus,
+}
+
+#[event]
+pub struct CampaignCreated {
+    pub campaign_id: String,
+    pub advertiser: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CampaignVariantUpdated {
+    pub campaign_id: String,
+    pub ad_id: String,
+    pub weight: u16,
+}
+
+#[event]
+pub struct CampaignToggled {
+    pub campaign_id: String,
+    pub is_active: bool,
+}
+
+#[event]
+pub struct AdClicked {
+    pub ad_id: String,
+    pub user: Pubkey,
+    pub price: u64,
+    pub click_count: u64,
+}
+
+#[event]
+pub struct ConversionAuthorityUpdated {
+    pub ad_id: String,
+    pub conversion_authority: Pubkey,
+    pub attribution_window: i64,
+}
+
+#[event]
+pub struct AdConversion {
+    pub ad_id: String,
+    pub user: Pubkey,
+    pub authority: Pubkey,
+    pub viewed_at: i64,
+    pub conversions: u64,
+}
+
+#[event]
+pub struct AdBillingModelUpdated {
+    pub ad_id: String,
+    pub billing_model: BillingModel,
+}
+
+#[event]
+pub struct AdReported {
+    pub ad_id: String,
+    pub user: Pubkey,
+    pub reason: u8,
+    pub report_count: u32,
+}
+
+#[event]
+pub struct AdSuspended {
+    pub ad_id: String,
+    pub report_count: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReportThresholdUpdated {
+    pub old_threshold: u32,
+    pub new_threshold: u32,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct AdApproved {
+    pub ad_id: String,
+    pub moderator: Pubkey,
+}
+
+#[event]
+pub struct AdRejected {
+    pub ad_id: String,
+    pub reason_code: u8,
+    pub moderator: Pubkey,
+}
+
+#[event]
+pub struct AdEndTimeUpdated {
+    pub ad_id: String,
+    pub ends_at: i64,
+}
+
+#[event]
+pub struct AdExpired {
+    pub ad_id: String,
+    pub ends_at: i64,
+}
+
+#[event]
+pub struct AdExhausted {
+    pub ad_id: String,
+    pub remaining_budget: u64,
+}
+
+#[event]
+pub struct ModeratorUpdated {
+    pub old_moderator: Pubkey,
+    pub new_moderator: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct AdAdvertiserUpdated {
+    pub ad_id: String,
+    pub advertiser: Pubkey,
+}
+
+#[event]
+pub struct AdBidUpdated {
+    pub ad_id: String,
+    pub advertiser: Pubkey,
+    pub bid_per_view: u64,
+}
+
+#[event]
+pub struct AdRegistryUpdated {
+    pub ad: Pubkey,
+    pub registered: bool,
+    pub registered_ads: u8,
+}
+
+#[event]
+pub struct AdTargetingUpdated {
+    pub ad_id: String,
+    pub categories: u32,
+    pub locale_count: u8,
+    pub min_transfer_amount: u64,
+    pub recipient_count: u8,
+}
+
+#[event]
+pub struct UserPreferencesUpdated {
+    pub user: Pubkey,
+    pub categories: u32,
+    pub language: [u8; 2],
+}
+
+#[event]
+pub struct AdFrequencyCapUpdated {
+    pub ad_id: String,
+    pub max_impressions_per_user: u16,
+    pub frequency_window: i64,
+}
+
+#[event]
+pub struct AdBudgetFunded {
+    pub ad_id: String,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub budget: u64,
+}
+
+#[event]
+pub struct AdBudgetWithdrawn {
+    pub ad_id: String,
+    pub amount: u64,
+    pub remaining: u64,
+}
+
+#[event]
+pub struct AdCharged {
+    pub ad_id: String,
+    pub advertiser: Pubkey,
+    pub price: u64,
+    pub remaining_budget: u64,
+}
+
+#[event]
+pub struct TransactionInitiated {
+    pub user: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub calculated_fee: u64,
+    pub funds_locked: bool,
+    pub ad_id: String,
+    pub ad_content: String,
+    pub ad_url: String,
+    pub display_duration: i64,
+    pub creative_mode: CreativeMode,
+    pub content_uri: String,
+    pub content_hash: [u8; 32],
+    pub mime_type: String,
+    pub ad_price: u64,
+    pub request_id: Pubkey,
+}
+
+#[event]
+pub struct TransactionCompleted {
+    pub user: Pubkey,
+    pub recipient: Pubkey,
+    pub amount_sent: u64,
+    pub amount_received: u64,
+    pub gas_fee_sponsored: u64,
+    pub ad_id: String,
+    pub view_duration: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EscrowCreated {
+    pub escrow: Pubkey,
+    pub user: Pubkey,
+    pub recipient: Pubkey,
+    pub arbiter: Pubkey,
+    pub amount: u64,
+    pub refund_after: i64,
+}
+
+#[event]
+pub struct EscrowHeld {
+    pub escrow: Pubkey,
+    pub user: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct EscrowReleased {
+    pub escrow: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub released_by: Pubkey,
+}
+
+#[event]
+pub struct EscrowRefunded {
+    pub escrow: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub refunded_by: Pubkey,
+}
+
+#[event]
+pub struct BatchInitiated {
+    pub user: Pubkey,
+    pub recipient_count: u8,
+    pub total_amount: u64,
+    pub calculated_fee: u64,
+    pub ad_id: String,
+    pub ad_content: String,
+    pub ad_url: String,
+    pub display_duration: i64,
+    pub ad_price: u64,
+    pub request_id: Pubkey,
+}
+
+#[event]
+pub struct BatchPayoutSent {
+    pub user: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub index: u8,
+    pub request_id: Pubkey,
+}
+
+#[event]
+pub struct BatchCompleted {
+    pub user: Pubkey,
+    pub recipient_count: u8,
+    pub total_amount: u64,
+    pub gas_fee_sponsored: u64,
+    pub ad_id: String,
+    pub view_duration: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SubscriptionCreated {
+    pub subscription: Pubkey,
+    pub user: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub interval: i64,
+    pub payment_count: u32,
+    pub sponsorship: SubscriptionSponsorship,
+}
+
+#[event]
+pub struct SubscriptionAdViewed {
+    pub subscription: Pubkey,
+    pub user: Pubkey,
+    pub ad_id: String,
+    pub view_duration: i64,
+}
+
+#[event]
+pub struct SubscriptionPaymentExecuted {
+    pub subscription: Pubkey,
+    pub user: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub gas_fee_sponsored: u64,
+    pub remaining_payments: u32,
+    pub executor: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SubscriptionCancelled {
+    pub subscription: Pubkey,
+    pub user: Pubkey,
+    pub remaining_payments: u32,
+}
+
+#[event]
+pub struct AdRetrieved {
+    pub ad_id: String,
+    pub ad_content: String,
+    pub ad_url: String,
+    pub display_duration: i64,
+    pub reward_amount: u64,
+}
+
+#[event]
+pub struct RequestCancelled {
+    pub user: Pubkey,
+}
+
+#[event]
+pub struct ExpiredRequestClosed {
+    pub user: Pubkey,
+    pub funds_returned: bool,
+    pub closed_by: Pubkey,
+}
+
+#[event]
+pub struct AddressBlockUpdated {
+    pub address: Pubkey,
+    pub blocked: bool,
+    pub reason: u8,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct AddressAllowUpdated {
+    pub address: Pubkey,
+    pub allowed: bool,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct AllowlistModeUpdated {
+    pub enabled: bool,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct BaseFeeUpdated {
+    pub old_fee: u64,
+    pub new_fee: u64,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct GuardianUpdated {
+    pub old_guardian: Pubkey,
+    pub new_guardian: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct PauseFlagsUpdated {
+    pub paused_operations: u8,
+    pub changed: u8,
+    pub paused: bool,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct FundsWithdrawn {
+    pub amount: u64,
+    pub admin: Pubkey,
+    pub remaining: u64,
+}
+
+#[event]
+pub struct RelayerRegistered {
+    pub relayer: Pubkey,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RelayerToggled {
+    pub relayer: Pubkey,
+    pub is_active: bool,
+}
+
+#[event]
+pub struct PublisherRegistered {
+    pub publisher: Pubkey,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PublisherToggled {
+    pub publisher: Pubkey,
+    pub is_active: bool,
+}
+
+#[event]
+pub struct PublisherPaid {
+    pub publisher: Pubkey,
+    pub ad_id: String,
+    pub amount: u64,
+    pub claimable: u64,
+}
+
+#[event]
+pub struct PublisherRevenueClaimed {
+    pub publisher: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct PublisherShareUpdated {
+    pub old_share_bps: u16,
+    pub new_share_bps: u16,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct RelayerFeeUpdated {
+    pub old_fee: u64,
+    pub new_fee: u64,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct UserProfileCreated {
+    pub user: Pubkey,
+    pub payer: Pubkey,
+}
+
+#[event]
+pub struct UserProfileFunded {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct UserProfileWithdrawn {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub balance: u64,
+}
+
+#[event]
+pub struct RelayerReimbursed {
+    pub relayer: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct AccountMigrated {
+    pub account: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+#[event]
+pub struct CircuitBreakerUpdated {
+    pub threshold: u64,
+    pub window_slots: u64,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct CircuitBreakerTripped {
+    pub outflow: u64,
+    pub threshold: u64,
+    pub window_start: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct LiquidBufferUpdated {
+    pub old_buffer: u64,
+    pub new_buffer: u64,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct TreasuryStakeCreated {
+    pub stake_account: Pubkey,
+    pub amount: u64,
+    pub remaining_liquid: u64,
+}
+
+#[event]
+pub struct TreasuryStakeDelegated {
+    pub stake_account: Pubkey,
+    pub vote_account: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct TreasuryStakeDeactivated {
+    pub stake_account: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct TreasuryStakeWithdrawn {
+    pub stake_account: Pubkey,
+    pub amount: u64,
+    pub rewards: u64,
+    pub total_funds: u64,
+}
@@ -0,0 +1,720 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::system_program::{transfer, Transfer};
+use solana_instructions_sysvar::{load_current_index_checked, load_instruction_at_checked};
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::state::*;
+
+/// Calculate gas fee for transaction
+pub(crate) fn calculate_gas_fee(amount: u64, state: &ProgramState) -> u64 {
+    let percentage_fee = amount / 1000; // 0.1% of amount
+    state.base_transaction_fee + percentage_fee
+}
+
+pub(crate) fn require_not_paused(state: &ProgramState, operation: u8) -> Result<()> {
+    require!(state.paused_operations & operation == 0, FeePaymentError::ProgramPaused);
+    Ok(())
+}
+
+/// Ad chosen for a request and the price it pays per view
+pub(crate) struct AdPlacement {
+    pub(crate) key: Pubkey,
+    pub(crate) ad: Advertisement,
+    pub(crate) price: u64,
+}
+
+/// Set up a new advertisement with an empty inline creative and no bid,
+/// budget, cap or targeting
+pub(crate) fn init_advertisement(
+    ad: &mut Advertisement,
+    ad_id: String,
+    reward_amount: u64,
+    display_duration: i64,
+    advertiser: Pubkey,
+    bump: u8,
+    now: i64,
+) {
+    ad.id = ad_id;
+    ad.url = String::new();
+    ad.content = String::new();
+    ad.reward_amount = reward_amount;
+    ad.display_duration = display_duration;
+    ad.status = AdStatus::Approved;
+    ad.view_count = 0;
+    ad.created_at = now;
+    ad.bump = bump;
+    ad.version = AD_VERSION;
+    ad.advertiser = advertiser;
+    ad.bid_per_view = 0;
+    ad.budget = 0;
+    ad.reserved_budget = 0;
+    ad.max_impressions_per_user = 0;
+    ad.frequency_window = 0;
+    ad.categories = 0;
+    ad.locales = Vec::new();
+    ad.min_transfer_amount = 0;
+    ad.recipient_programs = Vec::new();
+    ad.creative_mode = CreativeMode::Inline;
+    ad.content_uri = String::new();
+    ad.content_hash = [0; 32];
+    ad.mime_type = String::new();
+    ad.rejection_reason = 0;
+    ad.ends_at = 0;
+    ad.report_count = 0;
+    ad.click_count = 0;
+    ad.billing_model = BillingModel::PerView;
+    ad.conversion_authority = Pubkey::default();
+    ad.attribution_window = 0;
+    ad.conversions = 0;
+    ad.campaign = None;
+}
+
+/// Whether an ad may be shown: approved and not past its end time
+pub(crate) fn is_servable(ad: &Advertisement, now: i64) -> bool {
+    ad.status == AdStatus::Approved && (ad.ends_at == 0 || now <= ad.ends_at)
+}
+
+/// The variant a user is assigned to, split by weight. Assignment is a hash
+/// of campaign and user, so it's sticky per user and clients can compute
+/// which ad to pass.
+pub(crate) fn assigned_variant(campaign: &Campaign, user: &Pubkey) -> Result<Pubkey> {
+    require!(campaign.is_active, FeePaymentError::CampaignNotActive);
+
+    let total_weight: u64 = campaign.variants
+        .iter()
+        .map(|variant| variant.weight as u64)
+        .sum();
+    require!(total_weight > 0, FeePaymentError::CampaignNotActive);
+
+    let hash = hashv(&[campaign.campaign_id.as_bytes(), user.as_ref()]);
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash.to_bytes()[..8]);
+    let mut point = u64::from_le_bytes(bytes) % total_weight;
+
+    for variant in &campaign.variants {
+        if point < variant.weight as u64 {
+            return Ok(variant.ad);
+        }
+        point -= variant.weight as u64;
+    }
+    err!(FeePaymentError::CampaignNotActive)
+}
+
+/// A campaign's ads must come with their campaign, so its traffic split and
+/// per-variant counters can't be bypassed by leaving it out
+pub(crate) fn check_ad_campaign(ad: &Advertisement, campaign: Option<&Account<Campaign>>) -> Result<()> {
+    match (ad.campaign, campaign) {
+        (Some(campaign_key), Some(campaign)) => {
+            require!(campaign.key() == campaign_key, FeePaymentError::InvalidCampaignVariant);
+        }
+        (Some(_), None) => return err!(FeePaymentError::CampaignRequired),
+        (None, Some(_)) => return err!(FeePaymentError::InvalidCampaignVariant),
+        (None, None) => {}
+    }
+    Ok(())
+}
+
+/// A campaign's entry for one of its ads
+pub(crate) fn campaign_variant<'a>(campaign: &'a mut Campaign, ad: &Pubkey) -> Result<&'a mut CampaignVariant> {
+    campaign.variants
+        .iter_mut()
+        .find(|variant| variant.ad == *ad)
+        .ok_or(error!(FeePaymentError::InvalidCampaignVariant))
+}
+
+/// The send an ad would be shown for, matched against ad targeting. Batches
+/// list every recipient and their total amount.
+pub(crate) struct AdAudience<'a> {
+    pub(crate) user: Pubkey,
+    pub(crate) preferences: Option<&'a UserPreferences>,
+    pub(crate) recipients: &'a [Pubkey],
+    pub(crate) amount: u64,
+    /// Ads the sponsor pool paying for the send admits; empty admits any
+    pub(crate) allowed_ads: &'a [Pubkey],
+    pub(crate) impressions: &'a UserImpressions,
+}
+
+/// Whether an ad's targeting admits this send. Category and locale targeting
+/// only match users who declared matching preferences, and recipient
+/// targeting only sends whose recipients are all targeted.
+pub(crate) fn matches_targeting(ad: &Advertisement, audience: &AdAudience) -> bool {
+    let categories_match = ad.categories == 0
+        || audience.preferences
+            .is_some_and(|preferences| preferences.categories & ad.categories != 0);
+    let locale_matches = ad.locales.is_empty()
+        || audience.preferences
+            .is_some_and(|preferences| ad.locales.contains(&preferences.language));
+    let recipient_matches = ad.recipient_programs.is_empty()
+        || audience.recipients
+            .iter()
+            .all(|recipient| ad.recipient_programs.contains(recipient));
+
+    categories_match
+        && locale_matches
+        && recipient_matches
+        && audience.amount >= ad.min_transfer_amount
+}
+
+/// Second-price auction between the selected ad and every ad in the registry.
+/// `candidates` must hold each registered ad, writable and in registry order,
+/// each followed by its campaign if it belongs to one, so clients can't leave
+/// higher bidders out. Ads bid at least `fee_per_ad` per view out of
+/// unreserved budget; the winner pays the runner-up's bid, or `fee_per_ad` if
+/// unopposed, held in reserve until the view completes. With no eligible
+/// bidder the selected ad is served without charge. Only ads whose targeting
+/// matches the audience take part, a campaign's ads only for the users
+/// assigned to them, and only ads the sponsor pool admits. Ads the user has
+/// seen up to their frequency cap sit the auction out; a capped selected ad
+/// is only rejected when no rival can take its place.
+pub(crate) fn run_ad_auction<'info>(
+    state: &ProgramState,
+    registry: &AdRegistry,
+    selected_ad: &mut Account<'info, Advertisement>,
+    candidates: &'info [AccountInfo<'info>],
+    audience: &AdAudience,
+) -> Result<AdPlacement> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(is_servable(selected_ad, now), FeePaymentError::AdNotActive);
+    require!(
+        matches_targeting(selected_ad, audience),
+        FeePaymentError::AdNotTargeted
+    );
+
+    let selected_capped = frequency_capped(audience.impressions, &selected_ad.key(), selected_ad, now);
+
+    let reserve_price = state.fee_per_ad;
+    let is_eligible = |ad: &Advertisement| {
+        is_servable(ad, now)
+            && ad.bid_per_view >= reserve_price
+            && ad.budget.saturating_sub(ad.reserved_budget) >= ad.bid_per_view
+    };
+
+    let mut candidates = candidates.iter();
+    let mut rivals = Vec::with_capacity(registry.ads.len());
+    for key in &registry.ads {
+        let info = candidates.next().ok_or(FeePaymentError::AuctionCandidatesMismatch)?;
+        require!(
+            info.key() == *key && info.is_writable,
+            FeePaymentError::AuctionCandidatesMismatch
+        );
+        let ad = Account::<Advertisement>::try_from(info)?;
+
+        let assigned = match ad.campaign {
+            Some(campaign_key) => {
+                let info = candidates.next().ok_or(FeePaymentError::AuctionCandidatesMismatch)?;
+                require!(info.key() == campaign_key, FeePaymentError::InvalidCampaignVariant);
+                let campaign = Account::<Campaign>::try_from(info)?;
+                assigned_variant(&campaign, &audience.user).is_ok_and(|variant| variant == *key)
+            }
+            None => true,
+        };
+
+        let pool_admits = audience.allowed_ads.is_empty() || audience.allowed_ads.contains(key);
+        if *key != selected_ad.key()
+            && assigned
+            && pool_admits
+            && is_eligible(&ad)
+            && !frequency_capped(audience.impressions, key, &ad, now)
+            && matches_targeting(&ad, audience)
+        {
+            rivals.push(ad);
+        }
+    }
+    require!(
+        candidates.next().is_none(),
+        FeePaymentError::AuctionCandidatesMismatch
+    );
+
+    // Ties go to the selected ad, then to the earlier registered ad
+    let mut winner: Option<usize> = None;
+    let mut best_bid = if is_eligible(selected_ad) && !selected_capped {
+        selected_ad.bid_per_view
+    } else {
+        0
+    };
+    let mut runner_up = 0;
+    for (index, ad) in rivals.iter().enumerate() {
+        if ad.bid_per_view > best_bid {
+            runner_up = best_bid;
+            best_bid = ad.bid_per_view;
+            winner = Some(index);
+        } else if ad.bid_per_view > runner_up {
+            runner_up = ad.bid_per_view;
+        }
+    }
+
+    require!(
+        winner.is_some() || !selected_capped,
+        FeePaymentError::FrequencyCapReached
+    );
+
+    if best_bid == 0 {
+        return Ok(AdPlacement {
+            key: selected_ad.key(),
+            ad: (**selected_ad).clone(),
+            price: 0,
+        });
+    }
+
+    let price = runner_up.max(reserve_price);
+    match winner {
+        Some(index) => {
+            let mut ad = rivals.swap_remove(index);
+            ad.reserved_budget = ad.reserved_budget
+                .checked_add(price)
+                .ok_or(FeePaymentError::MathOverflow)?;
+            ad.exit(&crate::ID)?;
+            Ok(AdPlacement {
+                key: ad.key(),
+                ad: (*ad).clone(),
+                price,
+            })
+        }
+        None => {
+            selected_ad.reserved_budget = selected_ad.reserved_budget
+                .checked_add(price)
+                .ok_or(FeePaymentError::MathOverflow)?;
+            Ok(AdPlacement {
+                key: selected_ad.key(),
+                ad: (**selected_ad).clone(),
+                price,
+            })
+        }
+    }
+}
+
+/// Whether the user has seen an ad as often as its cap allows in the current
+/// window
+pub(crate) fn frequency_capped(impressions: &UserImpressions, key: &Pubkey, ad: &Advertisement, now: i64) -> bool {
+    ad.max_impressions_per_user > 0
+        && impressions.entries.iter().any(|entry| {
+            entry.ad == *key
+                && now.saturating_sub(entry.window_start) < ad.frequency_window
+                && entry.count >= ad.max_impressions_per_user
+        })
+}
+
+/// Count an impression of the placed ad for the user, rejecting it once the
+/// ad's frequency cap is reached within the current window. Only capped ads
+/// are tracked; when all slots are taken the oldest window is evicted.
+pub(crate) fn record_impression(
+    impressions: &mut UserImpressions,
+    user: Pubkey,
+    bump: u8,
+    placement: &AdPlacement,
+    now: i64,
+) -> Result<()> {
+    // First impression tracked for this user
+    if impressions.user == Pubkey::default() {
+        impressions.user = user;
+        impressions.bump = bump;
+    }
+
+    let ad = &placement.ad;
+    if ad.max_impressions_per_user == 0 {
+        return Ok(());
+    }
+
+    let index = match impressions.entries.iter().position(|entry| entry.ad == placement.key) {
+        Some(index) => index,
+        None => {
+            if impressions.entries.len() >= MAX_TRACKED_ADS {
+                let oldest = impressions.entries
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, entry)| entry.window_start)
+                    .map(|(index, _)| index)
+                    .ok_or(FeePaymentError::MathUnderflow)?;
+                impressions.entries.swap_remove(oldest);
+            }
+            impressions.entries.push(ImpressionEntry {
+                ad: placement.key,
+                count: 0,
+                window_start: now,
+            });
+            impressions.entries.len() - 1
+        }
+    };
+
+    let entry = &mut impressions.entries[index];
+    if now.saturating_sub(entry.window_start) >= ad.frequency_window {
+        entry.window_start = now;
+        entry.count = 0;
+    }
+    require!(
+        entry.count < ad.max_impressions_per_user,
+        FeePaymentError::FrequencyCapReached
+    );
+    entry.count += 1;
+
+    Ok(())
+}
+
+/// The part of an ad charge of `price` paid to the publisher that served it
+pub(crate) fn publisher_share(state: &ProgramState, price: u64, publisher: Option<&Publisher>) -> u64 {
+    match publisher {
+        Some(publisher) if publisher.is_active => {
+            (price as u128 * state.publisher_share_bps as u128 / MAX_FEE_BPS as u128) as u64
+        }
+        _ => 0,
+    }
+}
+
+/// Charge an ad `price` from its budget. The publisher that served it, if
+/// still active, takes its share and the treasury keeps the rest. Returns the
+/// publisher's share.
+pub(crate) fn charge_ad<'info>(
+    ad: &mut Account<'info, Advertisement>,
+    treasury: &AccountInfo<'info>,
+    state: &mut ProgramState,
+    price: u64,
+    publisher: Option<&mut Account<'info, Publisher>>,
+) -> Result<u64> {
+    ad.budget = ad.budget
+        .checked_sub(price)
+        .ok_or(FeePaymentError::MathUnderflow)?;
+    ad.sub_lamports(price)?;
+
+    let share = match publisher.filter(|publisher| publisher.is_active) {
+        Some(publisher) => {
+            let share = publisher_share(state, price, Some(&**publisher));
+            publisher.add_lamports(share)?;
+            publisher.claimable = publisher.claimable
+                .checked_add(share)
+                .ok_or(FeePaymentError::MathOverflow)?;
+            publisher.total_earned = publisher.total_earned
+                .checked_add(share)
+                .ok_or(FeePaymentError::MathOverflow)?;
+
+            emit!(PublisherPaid {
+                publisher: publisher.authority,
+                ad_id: ad.id.clone(),
+                amount: share,
+                claimable: publisher.claimable,
+            });
+            share
+        }
+        None => 0,
+    };
+
+    let treasury_amount = price - share;
+    treasury.add_lamports(treasury_amount)?;
+    state.total_funds = state.total_funds
+        .checked_add(treasury_amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    emit!(AdCharged {
+        ad_id: ad.id.clone(),
+        advertiser: ad.advertiser,
+        price,
+        remaining_budget: ad.budget,
+    });
+
+    if ad.status == AdStatus::Approved
+        && ad.budget.saturating_sub(ad.reserved_budget) < ad.bid_per_view
+    {
+        ad.status = AdStatus::Exhausted;
+
+        emit!(AdExhausted {
+            ad_id: ad.id.clone(),
+            remaining_budget: ad.budget,
+        });
+    }
+
+    Ok(share)
+}
+
+/// Release the clearing price a cancelled or expired placement held on its ad
+pub(crate) fn release_ad_budget(ad: Option<&mut Account<'_, Advertisement>>, ad_price: u64) -> Result<()> {
+    if ad_price == 0 {
+        return Ok(());
+    }
+
+    let ad = ad.ok_or(FeePaymentError::AdRequired)?;
+    ad.reserved_budget = ad.reserved_budget
+        .checked_sub(ad_price)
+        .ok_or(FeePaymentError::MathUnderflow)?;
+    Ok(())
+}
+
+/// Record a completed view on the user's receipt for the ad, which a click
+/// or conversion is then attributed to. Cost-per-click ads owe their
+/// clearing price on click.
+pub(crate) fn record_view(
+    view_receipt: &mut ViewReceipt,
+    ad: &Advertisement,
+    ad_price: u64,
+    publisher: Option<Pubkey>,
+    now: i64,
+) -> Result<()> {
+    view_receipt.viewed_at = now;
+    view_receipt.view_count = view_receipt.view_count
+        .checked_add(1)
+        .ok_or(FeePaymentError::MathOverflow)?;
+    view_receipt.clicked = false;
+    view_receipt.converted = false;
+    view_receipt.click_price = match ad.billing_model {
+        BillingModel::PerClick => ad_price,
+        BillingModel::PerView => 0,
+    };
+    view_receipt.publisher = publisher;
+    Ok(())
+}
+
+/// Track sponsored lamports in the current slot window before paying them out.
+/// A payout that would take the window over the threshold is refused and
+/// pauses completions: the caller must return without paying, and the admin
+/// unpauses `PAUSE_COMPLETE` afterwards. Returns whether the payout may proceed.
+pub(crate) fn admit_treasury_outflow(state: &mut ProgramState, amount: u64, slot: u64) -> Result<bool> {
+    if state.outflow_threshold == 0 || amount == 0 {
+        return Ok(true);
+    }
+
+    if slot.saturating_sub(state.outflow_window_start) >= state.outflow_window_slots {
+        state.outflow_window_start = slot;
+        state.outflow_in_window = 0;
+    }
+    let outflow = state.outflow_in_window
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    if outflow > state.outflow_threshold {
+        emit!(CircuitBreakerTripped {
+            outflow,
+            threshold: state.outflow_threshold,
+            window_start: state.outflow_window_start,
+            slot,
+        });
+
+        // Start a fresh window so completions resume cleanly once unpaused
+        state.paused_operations |= PAUSE_COMPLETE;
+        state.outflow_window_start = slot;
+        state.outflow_in_window = 0;
+        return Ok(false);
+    }
+
+    state.outflow_in_window = outflow;
+    Ok(true)
+}
+
+/// Grow a program-owned account to `new_space`, topping up rent from the payer.
+/// New bytes are zeroed, so appended fields read back as zero.
+pub(crate) fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    new_space: usize,
+) -> Result<()> {
+    if account.data_len() >= new_space {
+        return Ok(());
+    }
+
+    let rent_due = Rent::get()?
+        .minimum_balance(new_space)
+        .saturating_sub(account.lamports());
+    if rent_due > 0 {
+        transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            rent_due,
+        )?;
+    }
+
+    account.resize(new_space)?;
+    Ok(())
+}
+
+/// Treasury funds not reserved for pending requests
+pub(crate) fn available_funds(state: &ProgramState) -> u64 {
+    state.total_funds.saturating_sub(state.reserved_funds)
+}
+
+/// Reserve treasury funds for a pending request
+pub(crate) fn reserve_funds(state: &mut ProgramState, amount: u64) -> Result<()> {
+    require!(
+        available_funds(state) >= amount,
+        FeePaymentError::InsufficientProgramFunds
+    );
+    state.reserved_funds = state.reserved_funds
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+    Ok(())
+}
+
+/// Credit a treasury-sponsored payment to everyone who funded the treasury,
+/// in proportion to what they deposited. Donors collect their share through
+/// `settle_sponsorship`.
+pub(crate) fn attribute_sponsorship(state: &mut ProgramState) -> Result<()> {
+    if state.total_deposited == 0 {
+        return Ok(());
+    }
+    state.sponsorship_index = state.sponsorship_index
+        .checked_add(SPONSORSHIP_PRECISION / u128::from(state.total_deposited))
+        .ok_or(FeePaymentError::MathOverflow)?;
+    Ok(())
+}
+
+/// Bring a donor's attributed sponsorships up to date, at what they had
+/// contributed since the last settle
+pub(crate) fn settle_sponsorship(
+    contribution: &mut SponsorContribution,
+    state: &ProgramState,
+) -> Result<()> {
+    let accrued = u128::from(contribution.total_contributed)
+        .checked_mul(state.sponsorship_index - contribution.sponsorship_checkpoint)
+        .ok_or(FeePaymentError::MathOverflow)?;
+    contribution.sponsorship_units = contribution.sponsorship_units
+        .checked_add(accrued)
+        .ok_or(FeePaymentError::MathOverflow)?;
+    contribution.sponsored_count = u64::try_from(contribution.sponsorship_units / SPONSORSHIP_PRECISION)
+        .map_err(|_| FeePaymentError::MathOverflow)?;
+    contribution.sponsorship_checkpoint = state.sponsorship_index;
+    Ok(())
+}
+
+/// Release a reservation once its request completes, is cancelled or expires
+pub(crate) fn release_funds(state: &mut ProgramState, amount: u64) -> Result<()> {
+    state.reserved_funds = state.reserved_funds
+        .checked_sub(amount)
+        .ok_or(FeePaymentError::MathUnderflow)?;
+    Ok(())
+}
+
+/// Treasury funds a request holds in reserve: its gas fee unless a pool
+/// sponsors it, plus any relayer reimbursement
+pub(crate) fn treasury_reservation(request: &TransactionRequest) -> Result<u64> {
+    let gas_fee = if request.pool.is_some() { 0 } else { request.calculated_fee };
+    Ok(gas_fee
+        .checked_add(request.relayer_fee)
+        .ok_or(FeePaymentError::MathOverflow)?)
+}
+
+/// Calculate gas fee under a sponsor pool's fee schedule
+pub(crate) fn calculate_pool_fee(amount: u64, pool: &SponsorPool) -> Result<u64> {
+    let percentage_fee = (amount as u128 * pool.fee_bps as u128 / MAX_FEE_BPS as u128) as u64;
+    Ok(pool.base_fee
+        .checked_add(percentage_fee)
+        .ok_or(FeePaymentError::MathOverflow)?)
+}
+
+pub(crate) fn validate_pool_rules(rules: &PoolRules) -> Result<()> {
+    require!(rules.fee_bps <= MAX_FEE_BPS, FeePaymentError::InvalidFee);
+    require!(
+        rules.allowed_recipients.len() <= MAX_POOL_RECIPIENTS
+            && rules.allowed_ads.len() <= MAX_POOL_ADS,
+        FeePaymentError::TooManyPoolRules
+    );
+    Ok(())
+}
+
+/// Empty allowlists and a zero max amount mean "no restriction"
+pub(crate) fn check_pool_eligibility(pool: &SponsorPool, recipient: &Pubkey, amount: u64, ad: &Pubkey) -> Result<()> {
+    require!(pool.is_active, FeePaymentError::PoolNotActive);
+    require!(
+        pool.max_amount == 0 || amount <= pool.max_amount,
+        FeePaymentError::NotEligibleForPool
+    );
+    require!(
+        pool.allowed_recipients.is_empty() || pool.allowed_recipients.contains(recipient),
+        FeePaymentError::NotEligibleForPool
+    );
+    require!(
+        pool.allowed_ads.is_empty() || pool.allowed_ads.contains(ad),
+        FeePaymentError::NotEligibleForPool
+    );
+    Ok(())
+}
+
+pub(crate) fn reserve_pool_funds(pool: &mut SponsorPool, amount: u64) -> Result<()> {
+    require!(
+        pool.balance.saturating_sub(pool.reserved) >= amount,
+        FeePaymentError::InsufficientPoolFunds
+    );
+    pool.reserved = pool.reserved
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+    Ok(())
+}
+
+pub(crate) fn release_pool_funds(pool: &mut SponsorPool, amount: u64) -> Result<()> {
+    pool.reserved = pool.reserved
+        .checked_sub(amount)
+        .ok_or(FeePaymentError::MathUnderflow)?;
+    Ok(())
+}
+
+/// Reject blocked wallets and, in allowlist mode, wallets not allowlisted.
+/// `blocked` must be the address's BlockedAddress PDA, which only exists
+/// while the address is blocked.
+pub(crate) fn check_address_access(
+    state: &ProgramState,
+    address: &Pubkey,
+    blocked: &AccountInfo,
+    allowed: Option<&AccountInfo>,
+) -> Result<()> {
+    let (blocked_key, _) = Pubkey::find_program_address(&[b"blocked", address.as_ref()], &crate::ID);
+    require!(blocked.key() == blocked_key, FeePaymentError::InvalidAccessEntry);
+    require!(blocked.data_is_empty(), FeePaymentError::AddressBlocked);
+
+    if state.allowlist_mode {
+        let allowed = allowed.ok_or(FeePaymentError::AddressNotAllowed)?;
+        let (allowed_key, _) = Pubkey::find_program_address(&[b"allowed", address.as_ref()], &crate::ID);
+        require!(
+            allowed.key() == allowed_key && allowed.owner == &crate::ID && !allowed.data_is_empty(),
+            FeePaymentError::AddressNotAllowed
+        );
+    }
+
+    Ok(())
+}
+
+/// Verify that the instruction preceding the current one is an Ed25519
+/// precompile check of `message` signed by `signer`
+pub(crate) fn verify_signed_intent(instructions: &AccountInfo, signer: &Pubkey, message: &[u8]) -> Result<()> {
+    let current_index = load_current_index_checked(instructions)?;
+    require!(current_index > 0, FeePaymentError::MissingSignatureInstruction);
+
+    let ix = load_instruction_at_checked((current_index - 1) as usize, instructions)?;
+    require!(
+        ix.program_id == ed25519_program::ID && ix.accounts.is_empty(),
+        FeePaymentError::MissingSignatureInstruction
+    );
+
+    // Layout: [num_signatures: u8, padding: u8, offsets: 7 x u16], single signature
+    // with the pubkey, signature and message all embedded in this instruction
+    let data = &ix.data;
+    require!(data.len() >= 16 && data[0] == 1, FeePaymentError::InvalidSignatureInstruction);
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let signature_ix_index = read_u16(4);
+    let public_key_offset = read_u16(6) as usize;
+    let public_key_ix_index = read_u16(8);
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    let message_ix_index = read_u16(14);
+    require!(
+        signature_ix_index == u16::MAX
+            && public_key_ix_index == u16::MAX
+            && message_ix_index == u16::MAX,
+        FeePaymentError::InvalidSignatureInstruction
+    );
+
+    let public_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(FeePaymentError::InvalidSignatureInstruction)?;
+    let signed_message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(FeePaymentError::InvalidSignatureInstruction)?;
+    require!(public_key == signer.as_ref(), FeePaymentError::SignerMismatch);
+    require!(signed_message == message, FeePaymentError::IntentMismatch);
+
+    Ok(())
+}
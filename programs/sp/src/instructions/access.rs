@@ -0,0 +1,157 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::instructions::admin::AdminAction;
+use crate::state::*;
+
+pub(crate) fn block_address(ctx: Context<BlockAddress>, address: Pubkey, reason: u8) -> Result<()> {
+    let blocked = &mut ctx.accounts.blocked;
+
+    blocked.address = address;
+    blocked.reason = reason;
+    blocked.blocked_at = Clock::get()?.unix_timestamp;
+    blocked.bump = ctx.bumps.blocked;
+
+    emit!(AddressBlockUpdated {
+        address,
+        blocked: true,
+        reason,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn unblock_address(ctx: Context<UnblockAddress>) -> Result<()> {
+    let blocked = &ctx.accounts.blocked;
+
+    emit!(AddressBlockUpdated {
+        address: blocked.address,
+        blocked: false,
+        reason: blocked.reason,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn allow_address(ctx: Context<AllowAddress>, address: Pubkey) -> Result<()> {
+    let allowed = &mut ctx.accounts.allowed;
+
+    allowed.address = address;
+    allowed.allowed_at = Clock::get()?.unix_timestamp;
+    allowed.bump = ctx.bumps.allowed;
+
+    emit!(AddressAllowUpdated {
+        address,
+        allowed: true,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn disallow_address(ctx: Context<DisallowAddress>) -> Result<()> {
+    emit!(AddressAllowUpdated {
+        address: ctx.accounts.allowed.address,
+        allowed: false,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn set_allowlist_mode(ctx: Context<AdminAction>, enabled: bool) -> Result<()> {
+    let state = &mut ctx.accounts.state;
+    state.allowlist_mode = enabled;
+
+    emit!(AllowlistModeUpdated {
+        enabled,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(address: Pubkey)]
+pub struct BlockAddress<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 42,
+        seeds = [b"blocked", address.as_ref()],
+        bump
+    )]
+    pub blocked: Account<'info, BlockedAddress>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UnblockAddress<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"blocked", blocked.address.as_ref()],
+        bump = blocked.bump,
+        close = admin
+    )]
+    pub blocked: Account<'info, BlockedAddress>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(address: Pubkey)]
+pub struct AllowAddress<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 41,
+        seeds = [b"allowed", address.as_ref()],
+        bump
+    )]
+    pub allowed: Account<'info, AllowedAddress>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DisallowAddress<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"allowed", allowed.address.as_ref()],
+        bump = allowed.bump,
+        close = admin
+    )]
+    pub allowed: Account<'info, AllowedAddress>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
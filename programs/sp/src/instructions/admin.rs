@@ -0,0 +1,285 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::state::*;
+
+pub(crate) fn initialize(ctx: Context<Initialize>) -> Result<()> {
+    let state = &mut ctx.accounts.state;
+    
+    state.admin = ctx.accounts.deployer.key();
+    state.total_funds = 0;
+    state.total_ads_viewed = 0;
+    state.total_transactions = 0;
+    state.fee_per_ad = DEFAULT_FEE_PER_AD;
+    state.base_transaction_fee = BASE_TRANSACTION_FEE;
+    state.paused_operations = 0;
+    state.bump = ctx.bumps.state;
+    state.treasury_bump = ctx.bumps.treasury;
+    state.relayer_fee = DEFAULT_RELAYER_FEE;
+    state.reserved_funds = 0;
+    state.staked_funds = 0;
+    state.min_liquid_buffer = DEFAULT_MIN_LIQUID_BUFFER;
+    state.allowlist_mode = false;
+    state.guardian = Pubkey::default();
+    state.outflow_threshold = 0;
+    state.outflow_window_slots = DEFAULT_OUTFLOW_WINDOW_SLOTS;
+    state.outflow_window_start = 0;
+    state.outflow_in_window = 0;
+    state.version = STATE_VERSION;
+    state.publisher_share_bps = DEFAULT_PUBLISHER_SHARE_BPS;
+    state.moderator = state.admin;
+    state.report_threshold = DEFAULT_REPORT_THRESHOLD;
+    state.total_deposited = 0;
+    state.sponsorship_index = 0;
+
+    emit!(ProgramInitialized {
+        admin: state.admin,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn update_base_fee(ctx: Context<AdminAction>, new_base_fee: u64) -> Result<()> {
+    require!(new_base_fee > 0, FeePaymentError::InvalidAmount);
+    
+    let state = &mut ctx.accounts.state;
+    let old_fee = state.base_transaction_fee;
+    state.base_transaction_fee = new_base_fee;
+    emit!(BaseFeeUpdated {
+        old_fee,
+        new_fee: new_base_fee,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn update_relayer_fee(ctx: Context<AdminAction>, new_relayer_fee: u64) -> Result<()> {
+    let state = &mut ctx.accounts.state;
+    let old_fee = state.relayer_fee;
+    state.relayer_fee = new_relayer_fee;
+
+    emit!(RelayerFeeUpdated {
+        old_fee,
+        new_fee: new_relayer_fee,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn update_publisher_share(ctx: Context<AdminAction>, new_share_bps: u16) -> Result<()> {
+    require!(new_share_bps <= MAX_FEE_BPS, FeePaymentError::InvalidFee);
+
+    let state = &mut ctx.accounts.state;
+    let old_share_bps = state.publisher_share_bps;
+    state.publisher_share_bps = new_share_bps;
+
+    emit!(PublisherShareUpdated {
+        old_share_bps,
+        new_share_bps,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn update_report_threshold(ctx: Context<AdminAction>, new_threshold: u32) -> Result<()> {
+    require!(new_threshold > 0, FeePaymentError::InvalidReportThreshold);
+
+    let state = &mut ctx.accounts.state;
+    let old_threshold = state.report_threshold;
+    state.report_threshold = new_threshold;
+
+    emit!(ReportThresholdUpdated {
+        old_threshold,
+        new_threshold,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn set_moderator(ctx: Context<AdminAction>, moderator: Pubkey) -> Result<()> {
+    let state = &mut ctx.accounts.state;
+    let old_moderator = state.moderator;
+    state.moderator = moderator;
+
+    emit!(ModeratorUpdated {
+        old_moderator,
+        new_moderator: moderator,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn set_guardian(ctx: Context<AdminAction>, guardian: Pubkey) -> Result<()> {
+    let state = &mut ctx.accounts.state;
+    let old_guardian = state.guardian;
+    state.guardian = guardian;
+
+    emit!(GuardianUpdated {
+        old_guardian,
+        new_guardian: guardian,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn pause_operations(ctx: Context<GuardianAction>, operations: u8) -> Result<()> {
+    require!(
+        operations != 0 && operations & !PAUSE_ALL == 0,
+        FeePaymentError::InvalidPauseFlags
+    );
+
+    let state = &mut ctx.accounts.state;
+    let authority = ctx.accounts.authority.key();
+    require!(
+        authority == state.admin || authority == state.guardian,
+        FeePaymentError::Unauthorized
+    );
+
+    state.paused_operations |= operations;
+
+    emit!(PauseFlagsUpdated {
+        paused_operations: state.paused_operations,
+        changed: operations,
+        paused: true,
+        authority,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn unpause_operations(ctx: Context<AdminAction>, operations: u8) -> Result<()> {
+    require!(
+        operations != 0 && operations & !PAUSE_ALL == 0,
+        FeePaymentError::InvalidPauseFlags
+    );
+
+    let state = &mut ctx.accounts.state;
+    state.paused_operations &= !operations;
+
+    emit!(PauseFlagsUpdated {
+        paused_operations: state.paused_operations,
+        changed: operations,
+        paused: false,
+        authority: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn update_liquid_buffer(ctx: Context<AdminAction>, new_buffer: u64) -> Result<()> {
+    let state = &mut ctx.accounts.state;
+    let old_buffer = state.min_liquid_buffer;
+    state.min_liquid_buffer = new_buffer;
+
+    emit!(LiquidBufferUpdated {
+        old_buffer,
+        new_buffer,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn update_circuit_breaker(
+    ctx: Context<AdminAction>,
+    threshold: u64,
+    window_slots: u64,
+) -> Result<()> {
+    require!(window_slots > 0, FeePaymentError::InvalidOutflowWindow);
+
+    let state = &mut ctx.accounts.state;
+    state.outflow_threshold = threshold;
+    state.outflow_window_slots = window_slots;
+    state.outflow_window_start = Clock::get()?.slot;
+    state.outflow_in_window = 0;
+
+    emit!(CircuitBreakerUpdated {
+        threshold,
+        window_slots,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn create_ad_registry(ctx: Context<CreateAdRegistry>) -> Result<()> {
+    let registry = &mut ctx.accounts.ad_registry;
+    registry.ads = Vec::new();
+    registry.bump = ctx.bumps.ad_registry;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = deployer,
+        space = PROGRAM_STATE_SPACE,
+        seeds = [b"state"],
+        bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Treasury PDA for holding funds
+    #[account(
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury: AccountInfo<'info>,
+    #[account(mut)]
+    pub deployer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GuardianAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AdminAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateAdRegistry<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 261,
+        seeds = [b"ad_registry"],
+        bump
+    )]
+    pub ad_registry: Account<'info, AdRegistry>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,579 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::helpers::*;
+use crate::state::*;
+
+pub(crate) fn create_ad(
+    ctx: Context<CreateAd>,
+    ad_id: String,
+    ad_url: String,
+    ad_content: String,
+    reward_amount: u64,
+    display_duration: i64,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_AD_MANAGEMENT)?;
+    require!(
+        !ad_id.is_empty() && ad_id.len() <= MAX_AD_ID_LENGTH,
+        FeePaymentError::InvalidAdId
+    );
+    require!(
+        ad_url.starts_with("https://") && ad_url.len() <= MAX_AD_URL_LENGTH,
+        FeePaymentError::InvalidAdUrl
+    );
+    require!(
+        !ad_content.is_empty() && ad_content.len() <= MAX_AD_CONTENT_LENGTH,
+        FeePaymentError::InvalidAdContent
+    );
+    require!(reward_amount >= MIN_AD_REWARD, FeePaymentError::RewardTooLow);
+    require!(display_duration >= MIN_AD_VIEW_TIME, FeePaymentError::InvalidDisplayTime);
+
+    let ad = &mut ctx.accounts.ad;
+    let clock = Clock::get()?;
+
+    init_advertisement(
+        ad,
+        ad_id.clone(),
+        reward_amount,
+        display_duration,
+        ctx.accounts.creator.key(),
+        ctx.bumps.ad,
+        clock.unix_timestamp,
+    );
+    // Ads from anyone but the admin wait for a moderator's review
+    ad.status = if ctx.accounts.creator.key() == ctx.accounts.state.admin {
+        AdStatus::Approved
+    } else {
+        AdStatus::PendingReview
+    };
+    ad.url = ad_url.clone();
+    ad.content = ad_content.clone();
+
+    emit!(AdCreated {
+        ad_id,
+        reward_amount,
+        display_duration,
+        creator: ctx.accounts.creator.key(),
+        status: ad.status,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn create_reference_ad(
+    ctx: Context<CreateReferenceAd>,
+    ad_id: String,
+    content_uri: String,
+    content_hash: [u8; 32],
+    mime_type: String,
+    reward_amount: u64,
+    display_duration: i64,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_AD_MANAGEMENT)?;
+    require!(
+        !ad_id.is_empty() && ad_id.len() <= MAX_AD_ID_LENGTH,
+        FeePaymentError::InvalidAdId
+    );
+    require!(
+        ["ar://", "ipfs://", "https://"]
+            .iter()
+            .any(|scheme| content_uri.starts_with(scheme))
+            && content_uri.len() <= MAX_CONTENT_URI_LENGTH,
+        FeePaymentError::InvalidContentUri
+    );
+    require!(content_hash != [0; 32], FeePaymentError::InvalidCreative);
+    require!(
+        !mime_type.is_empty() && mime_type.len() <= MAX_MIME_TYPE_LENGTH,
+        FeePaymentError::InvalidCreative
+    );
+    require!(reward_amount >= MIN_AD_REWARD, FeePaymentError::RewardTooLow);
+    require!(display_duration >= MIN_AD_VIEW_TIME, FeePaymentError::InvalidDisplayTime);
+
+    let ad = &mut ctx.accounts.ad;
+    let clock = Clock::get()?;
+
+    init_advertisement(
+        ad,
+        ad_id.clone(),
+        reward_amount,
+        display_duration,
+        ctx.accounts.creator.key(),
+        ctx.bumps.ad,
+        clock.unix_timestamp,
+    );
+    // Ads from anyone but the admin wait for a moderator's review
+    ad.status = if ctx.accounts.creator.key() == ctx.accounts.state.admin {
+        AdStatus::Approved
+    } else {
+        AdStatus::PendingReview
+    };
+    ad.creative_mode = CreativeMode::Reference;
+    ad.content_uri = content_uri;
+    ad.content_hash = content_hash;
+    ad.mime_type = mime_type;
+
+    emit!(AdCreated {
+        ad_id,
+        reward_amount,
+        display_duration,
+        creator: ctx.accounts.creator.key(),
+        status: ad.status,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn toggle_ad(ctx: Context<ToggleAd>) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_AD_MANAGEMENT)?;
+
+    let ad = &mut ctx.accounts.ad;
+    ad.status = match ad.status {
+        AdStatus::Approved => AdStatus::Paused,
+        AdStatus::Paused => AdStatus::Approved,
+        _ => return err!(FeePaymentError::InvalidAdStatus),
+    };
+
+    emit!(AdToggled {
+        ad_id: ad.id.clone(),
+        status: ad.status,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn set_ad_billing_model(
+    ctx: Context<AdvertiserAction>,
+    billing_model: BillingModel,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_AD_MANAGEMENT)?;
+
+    let ad = &mut ctx.accounts.ad;
+    ad.billing_model = billing_model;
+
+    emit!(AdBillingModelUpdated {
+        ad_id: ad.id.clone(),
+        billing_model,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn set_ad_end_time(ctx: Context<AdvertiserAction>, ends_at: i64) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_AD_MANAGEMENT)?;
+    require!(
+        ends_at == 0 || ends_at > Clock::get()?.unix_timestamp,
+        FeePaymentError::InvalidAdSchedule
+    );
+
+    let ad = &mut ctx.accounts.ad;
+    require!(ad.status != AdStatus::Expired, FeePaymentError::InvalidAdStatus);
+    ad.ends_at = ends_at;
+
+    emit!(AdEndTimeUpdated {
+        ad_id: ad.id.clone(),
+        ends_at,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn expire_ad(ctx: Context<ExpireAd>) -> Result<()> {
+    let ad = &mut ctx.accounts.ad;
+    require!(
+        ad.ends_at != 0 && Clock::get()?.unix_timestamp > ad.ends_at,
+        FeePaymentError::InvalidAdSchedule
+    );
+    require!(
+        matches!(
+            ad.status,
+            AdStatus::Approved | AdStatus::Paused | AdStatus::Exhausted
+        ),
+        FeePaymentError::InvalidAdStatus
+    );
+
+    ad.status = AdStatus::Expired;
+
+    emit!(AdExpired {
+        ad_id: ad.id.clone(),
+        ends_at: ad.ends_at,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn set_ad_advertiser(ctx: Context<ToggleAd>, advertiser: Pubkey) -> Result<()> {
+    let ad = &mut ctx.accounts.ad;
+    ad.advertiser = advertiser;
+
+    emit!(AdAdvertiserUpdated {
+        ad_id: ad.id.clone(),
+        advertiser,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn set_ad_bid(ctx: Context<SetAdBid>, bid_per_view: u64) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_AD_MANAGEMENT)?;
+
+    let ad = &mut ctx.accounts.ad;
+    ad.bid_per_view = bid_per_view;
+
+    emit!(AdBidUpdated {
+        ad_id: ad.id.clone(),
+        advertiser: ad.advertiser,
+        bid_per_view,
+    });
+
+    // Ads bidding at least the reserve price enter the auction registry,
+    // and leave it when they bid below it
+    let ad_key = ad.key();
+    let registry = &mut ctx.accounts.ad_registry;
+    let registered = registry.ads.contains(&ad_key);
+    let bidding = bid_per_view >= ctx.accounts.state.fee_per_ad;
+    if bidding == registered {
+        return Ok(());
+    }
+
+    if bidding {
+        require!(ad.status == AdStatus::Approved, FeePaymentError::AdNotActive);
+        require!(registry.ads.len() < MAX_AUCTION_ADS, FeePaymentError::AdRegistryFull);
+        registry.ads.push(ad_key);
+    } else {
+        registry.ads.retain(|key| *key != ad_key);
+    }
+
+    emit!(AdRegistryUpdated {
+        ad: ad_key,
+        registered: bidding,
+        registered_ads: registry.ads.len() as u8,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn prune_ad_registry(ctx: Context<PruneAdRegistry>) -> Result<()> {
+    let ad = &ctx.accounts.ad;
+    let registry = &mut ctx.accounts.ad_registry;
+    require!(registry.ads.contains(&ad.key()), FeePaymentError::AdNotRegistered);
+
+    // Anyone may free the slot of an ad that can no longer win
+    let eligible = is_servable(ad, Clock::get()?.unix_timestamp)
+        && ad.bid_per_view >= ctx.accounts.state.fee_per_ad
+        && ad.budget.saturating_sub(ad.reserved_budget) >= ad.bid_per_view;
+    require!(!eligible, FeePaymentError::AdStillEligible);
+
+    registry.ads.retain(|key| *key != ad.key());
+
+    emit!(AdRegistryUpdated {
+        ad: ad.key(),
+        registered: false,
+        registered_ads: registry.ads.len() as u8,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn set_ad_targeting(
+    ctx: Context<AdvertiserAction>,
+    categories: u32,
+    locales: Vec<[u8; 2]>,
+    min_transfer_amount: u64,
+    recipient_programs: Vec<Pubkey>,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_AD_MANAGEMENT)?;
+    require!(
+        locales.len() <= MAX_AD_LOCALES
+            && recipient_programs.len() <= MAX_AD_TARGET_RECIPIENTS,
+        FeePaymentError::InvalidTargeting
+    );
+
+    let ad = &mut ctx.accounts.ad;
+    ad.categories = categories;
+    ad.locales = locales;
+    ad.min_transfer_amount = min_transfer_amount;
+    ad.recipient_programs = recipient_programs;
+
+    emit!(AdTargetingUpdated {
+        ad_id: ad.id.clone(),
+        categories,
+        locale_count: ad.locales.len() as u8,
+        min_transfer_amount,
+        recipient_count: ad.recipient_programs.len() as u8,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn set_ad_frequency_cap(
+    ctx: Context<AdvertiserAction>,
+    max_impressions_per_user: u16,
+    frequency_window: i64,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_AD_MANAGEMENT)?;
+    require!(
+        max_impressions_per_user == 0 || frequency_window > 0,
+        FeePaymentError::InvalidFrequencyCap
+    );
+
+    let ad = &mut ctx.accounts.ad;
+    ad.max_impressions_per_user = max_impressions_per_user;
+    ad.frequency_window = frequency_window;
+
+    emit!(AdFrequencyCapUpdated {
+        ad_id: ad.id.clone(),
+        max_impressions_per_user,
+        frequency_window,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn fund_ad_budget(ctx: Context<FundAdBudget>, amount: u64) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_DEPOSIT)?;
+    require!(amount > 0 && amount <= MAX_SINGLE_DEPOSIT, FeePaymentError::InvalidAmount);
+
+    transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.funder.to_account_info(),
+                to: ctx.accounts.ad.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+
+    let ad = &mut ctx.accounts.ad;
+    ad.budget = ad.budget
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+    if ad.status == AdStatus::Exhausted
+        && ad.budget.saturating_sub(ad.reserved_budget) >= ad.bid_per_view
+    {
+        ad.status = AdStatus::Approved;
+    }
+
+    emit!(AdBudgetFunded {
+        ad_id: ad.id.clone(),
+        funder: ctx.accounts.funder.key(),
+        amount,
+        budget: ad.budget,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn withdraw_ad_budget(ctx: Context<AdvertiserAction>, amount: u64) -> Result<()> {
+    require!(amount > 0, FeePaymentError::InvalidAmount);
+
+    let ad = &mut ctx.accounts.ad;
+    require!(
+        ad.budget.saturating_sub(ad.reserved_budget) >= amount,
+        FeePaymentError::InsufficientAdBudget
+    );
+
+    ad.budget -= amount;
+    ad.sub_lamports(amount)?;
+    ctx.accounts.advertiser.add_lamports(amount)?;
+
+    emit!(AdBudgetWithdrawn {
+        ad_id: ad.id.clone(),
+        amount,
+        remaining: ad.budget,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn set_user_preferences(
+    ctx: Context<SetUserPreferences>,
+    categories: u32,
+    language: [u8; 2],
+) -> Result<()> {
+    let preferences = &mut ctx.accounts.preferences;
+    preferences.user = ctx.accounts.user.key();
+    preferences.categories = categories;
+    preferences.language = language;
+    preferences.updated_at = Clock::get()?.unix_timestamp;
+    preferences.bump = ctx.bumps.preferences;
+
+    emit!(UserPreferencesUpdated {
+        user: preferences.user,
+        categories,
+        language,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn get_random_ad(ctx: Context<GetRandomAd>) -> Result<()> {
+    let ad = &ctx.accounts.ad;
+    require!(ad.status == AdStatus::Approved, FeePaymentError::AdNotActive);
+
+    emit!(AdRetrieved {
+        ad_id: ad.id.clone(),
+        ad_content: ad.content.clone(),
+        ad_url: ad.url.clone(),
+        display_duration: ad.display_duration,
+        reward_amount: ad.reward_amount,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(ad_id: String)]
+pub struct CreateAd<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        init,
+        payer = creator,
+        space = ADVERTISEMENT_SPACE,
+        seeds = [b"ad", ad_id.as_bytes()],
+        bump
+    )]
+    pub ad: Account<'info, Advertisement>,
+    /// Admin, or an advertiser submitting for review
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(ad_id: String)]
+pub struct CreateReferenceAd<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        init,
+        payer = creator,
+        space = REFERENCE_AD_SPACE,
+        seeds = [b"ad", ad_id.as_bytes()],
+        bump
+    )]
+    pub ad: Account<'info, Advertisement>,
+    /// Admin, or an advertiser submitting for review
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireAd<'info> {
+    #[account(mut)]
+    pub ad: Account<'info, Advertisement>,
+}
+
+#[derive(Accounts)]
+pub struct ToggleAd<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(mut)]
+    pub ad: Account<'info, Advertisement>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FundAdBudget<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(mut)]
+    pub ad: Account<'info, Advertisement>,
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdvertiserAction<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        has_one = advertiser @ FeePaymentError::Unauthorized
+    )]
+    pub ad: Account<'info, Advertisement>,
+    #[account(mut)]
+    pub advertiser: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetAdBid<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        has_one = advertiser @ FeePaymentError::Unauthorized
+    )]
+    pub ad: Account<'info, Advertisement>,
+    #[account(
+        mut,
+        seeds = [b"ad_registry"],
+        bump = ad_registry.bump
+    )]
+    pub ad_registry: Account<'info, AdRegistry>,
+    pub advertiser: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PruneAdRegistry<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    pub ad: Account<'info, Advertisement>,
+    #[account(
+        mut,
+        seeds = [b"ad_registry"],
+        bump = ad_registry.bump
+    )]
+    pub ad_registry: Account<'info, AdRegistry>,
+}
+
+#[derive(Accounts)]
+pub struct SetUserPreferences<'info> {
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 47,
+        seeds = [b"preferences", user.key().as_ref()],
+        bump
+    )]
+    pub preferences: Account<'info, UserPreferences>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetRandomAd<'info> {
+    #[account(constraint = ad.status == AdStatus::Approved @ FeePaymentError::AdNotActive)]
+    pub ad: Account<'info, Advertisement>,
+}
@@ -0,0 +1,475 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::helpers::*;
+use crate::state::*;
+
+pub(crate) fn initiate_batch_send<'info>(
+    ctx: Context<'_, '_, 'info, 'info, InitiateBatchSend<'info>>,
+    payouts: Vec<BatchPayout>,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_INITIATE)?;
+    require!(
+        !payouts.is_empty() && payouts.len() <= MAX_BATCH_RECIPIENTS,
+        FeePaymentError::InvalidBatchSize
+    );
+
+    check_address_access(
+        &ctx.accounts.state,
+        &ctx.accounts.user.key(),
+        &ctx.accounts.user_blocked,
+        ctx.accounts.user_allowed.as_deref(),
+    )?;
+
+    // Remaining accounts: each recipient's BlockedAddress PDA, followed by
+    // its AllowedAddress PDA when allowlist mode is on, then every registered
+    // ad bidding for this placement
+    let stride = if ctx.accounts.state.allowlist_mode { 2 } else { 1 };
+    require!(
+        ctx.remaining_accounts.len() >= payouts.len() * stride,
+        FeePaymentError::InvalidAccessEntry
+    );
+    let (access_entries, candidates) = ctx.remaining_accounts.split_at(payouts.len() * stride);
+
+    let mut total_amount: u64 = 0;
+    for (payout, entries) in payouts.iter().zip(access_entries.chunks(stride)) {
+        require!(payout.recipient != Pubkey::default(), FeePaymentError::InvalidRecipient);
+        require!(payout.amount > 0, FeePaymentError::InvalidAmount);
+        check_address_access(&ctx.accounts.state, &payout.recipient, &entries[0], entries.get(1))?;
+        total_amount = total_amount
+            .checked_add(payout.amount)
+            .ok_or(FeePaymentError::MathOverflow)?;
+    }
+
+    // A campaign's creative is the user's assigned variant
+    check_ad_campaign(&ctx.accounts.selected_ad, ctx.accounts.campaign.as_ref())?;
+    if let Some(campaign) = ctx.accounts.campaign.as_ref() {
+        require!(
+            assigned_variant(campaign, &ctx.accounts.user.key())? == ctx.accounts.selected_ad.key(),
+            FeePaymentError::VariantMismatch
+        );
+    }
+
+    let recipients: Vec<Pubkey> = payouts.iter().map(|payout| payout.recipient).collect();
+    let audience = AdAudience {
+        user: ctx.accounts.user.key(),
+        preferences: ctx.accounts.preferences.as_deref(),
+        recipients: &recipients,
+        amount: total_amount,
+        allowed_ads: &[],
+        impressions: &ctx.accounts.impressions,
+    };
+    let placement = run_ad_auction(
+        &ctx.accounts.state,
+        &ctx.accounts.ad_registry,
+        &mut ctx.accounts.selected_ad,
+        candidates,
+        &audience,
+    )?;
+    let ad = &placement.ad;
+    let clock = Clock::get()?;
+    record_impression(
+        &mut ctx.accounts.impressions,
+        ctx.accounts.user.key(),
+        ctx.bumps.impressions,
+        &placement,
+        clock.unix_timestamp,
+    )?;
+
+    let state = &mut ctx.accounts.state;
+    let calculated_fee = calculate_gas_fee(total_amount, state);
+    reserve_funds(state, calculated_fee)?;
+
+    let batch = &mut ctx.accounts.batch;
+
+    batch.user = ctx.accounts.user.key();
+    batch.recipient_count = payouts.len() as u8;
+    batch.payouts = payouts;
+    batch.total_amount = total_amount;
+    batch.calculated_fee = calculated_fee;
+    batch.status = RequestStatus::WaitingForAd;
+    batch.selected_ad_id = ad.id.clone();
+    batch.ad_display_started_at = Some(clock.unix_timestamp);
+    batch.created_at = clock.unix_timestamp;
+    batch.expires_at = clock.unix_timestamp + TRANSACTION_TIMEOUT;
+    batch.bump = ctx.bumps.batch;
+    batch.ad_price = placement.price;
+
+    emit!(BatchInitiated {
+        user: batch.user,
+        recipient_count: batch.recipient_count,
+        total_amount,
+        calculated_fee,
+        ad_id: ad.id.clone(),
+        ad_content: ad.content.clone(),
+        ad_url: ad.url.clone(),
+        display_duration: ad.display_duration,
+        ad_price: placement.price,
+        request_id: batch.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn complete_batch_after_ad<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CompleteBatch<'info>>,
+    view_duration: i64,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_COMPLETE)?;
+
+    let batch = &mut ctx.accounts.batch;
+    let ad = &mut ctx.accounts.ad;
+    let clock = Clock::get()?;
+
+    require!(
+        batch.status == RequestStatus::WaitingForAd,
+        FeePaymentError::InvalidStatus
+    );
+    require!(
+        clock.unix_timestamp <= batch.expires_at,
+        FeePaymentError::RequestExpired
+    );
+
+    check_ad_campaign(ad, ctx.accounts.campaign.as_ref())?;
+
+    let ad_started_at = batch.ad_display_started_at.ok_or(FeePaymentError::AdNotStarted)?;
+    let actual_view_time = clock.unix_timestamp - ad_started_at;
+    require!(
+        view_duration >= ad.display_duration && actual_view_time >= ad.display_duration,
+        FeePaymentError::InsufficientViewTime
+    );
+
+    let gas_fee = batch.calculated_fee;
+    let treasury_bump = ctx.accounts.state.treasury_bump;
+    require!(
+        ctx.accounts.state.total_funds >= gas_fee,
+        FeePaymentError::InsufficientProgramFunds
+    );
+    require!(
+        ctx.remaining_accounts.len() == batch.payouts.len(),
+        FeePaymentError::RecipientMismatch
+    );
+
+    // A payout the circuit breaker refuses leaves the batch waiting
+    if !admit_treasury_outflow(&mut ctx.accounts.state, gas_fee, clock.slot)? {
+        return Ok(());
+    }
+
+    // Transfer 1..N: User → each recipient (exact amounts)
+    for (index, (payout, recipient)) in batch
+        .payouts
+        .iter()
+        .zip(ctx.remaining_accounts.iter())
+        .enumerate()
+    {
+        require!(
+            recipient.key() == payout.recipient && recipient.is_writable,
+            FeePaymentError::RecipientMismatch
+        );
+
+        transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.user.to_account_info(),
+                    to: recipient.clone(),
+                },
+            ),
+            payout.amount,
+        )?;
+
+        emit!(BatchPayoutSent {
+            user: batch.user,
+            recipient: payout.recipient,
+            amount: payout.amount,
+            index: index as u8,
+            request_id: batch.key(),
+        });
+    }
+
+    // Treasury → Fee account (one gas fee sponsorship for the whole batch)
+    let treasury_signer_seeds = &[b"treasury".as_ref(), &[treasury_bump]];
+
+    transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.treasury.to_account_info(),
+                to: ctx.accounts.fee_account.to_account_info(),
+            },
+            &[treasury_signer_seeds],
+        ),
+        gas_fee,
+    )?;
+
+    let state = &mut ctx.accounts.state;
+    release_funds(state, gas_fee)?;
+    state.total_funds = state.total_funds
+        .checked_sub(gas_fee)
+        .ok_or(FeePaymentError::MathUnderflow)?;
+    attribute_sponsorship(state)?;
+
+    // Charge the winning ad the clearing price reserved at initiation.
+    // Cost-per-click ads are only charged if the viewer clicks through.
+    let ad_price = batch.ad_price;
+    if ad_price > 0 {
+        ad.reserved_budget = ad.reserved_budget
+            .checked_sub(ad_price)
+            .ok_or(FeePaymentError::MathUnderflow)?;
+        if ad.billing_model == BillingModel::PerView {
+            charge_ad(ad, &ctx.accounts.treasury, state, ad_price, None)?;
+        }
+    }
+
+    // Receipt proving this user saw the ad, for click tracking
+    let view_receipt = &mut ctx.accounts.view_receipt;
+    if view_receipt.user == Pubkey::default() {
+        view_receipt.user = batch.user;
+        view_receipt.ad = ad.key();
+        view_receipt.bump = ctx.bumps.view_receipt;
+    }
+    record_view(view_receipt, ad, ad_price, None, clock.unix_timestamp)?;
+
+    ad.view_count = ad.view_count
+        .checked_add(1)
+        .ok_or(FeePaymentError::MathOverflow)?;
+    if let Some(campaign) = ctx.accounts.campaign.as_mut() {
+        let variant = campaign_variant(campaign, &ad.key())?;
+        variant.views = variant.views
+            .checked_add(1)
+            .ok_or(FeePaymentError::MathOverflow)?;
+    }
+
+    state.total_ads_viewed = state.total_ads_viewed
+        .checked_add(1)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    state.total_transactions = state.total_transactions
+        .checked_add(batch.payouts.len() as u64)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    batch.status = RequestStatus::Completed;
+
+    emit!(BatchCompleted {
+        user: batch.user,
+        recipient_count: batch.recipient_count,
+        total_amount: batch.total_amount,
+        gas_fee_sponsored: gas_fee,
+        ad_id: ad.id.clone(),
+        view_duration,
+        timestamp: clock.unix_timestamp,
+    });
+
+    ctx.accounts.batch.close(ctx.accounts.user.to_account_info())?;
+
+    Ok(())
+}
+
+pub(crate) fn cancel_batch_request(ctx: Context<CancelBatchRequest>) -> Result<()> {
+    let batch = &mut ctx.accounts.batch;
+
+    require!(
+        batch.status == RequestStatus::WaitingForAd,
+        FeePaymentError::InvalidStatus
+    );
+
+    batch.status = RequestStatus::Cancelled;
+    release_funds(&mut ctx.accounts.state, batch.calculated_fee)?;
+    release_ad_budget(ctx.accounts.ad.as_mut(), batch.ad_price)?;
+
+    emit!(RequestCancelled {
+        user: batch.user,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn expire_batch_request(ctx: Context<ExpireBatchRequest>) -> Result<()> {
+    let batch = &ctx.accounts.batch;
+    let clock = Clock::get()?;
+
+    require!(
+        batch.status == RequestStatus::WaitingForAd,
+        FeePaymentError::InvalidStatus
+    );
+    require!(
+        clock.unix_timestamp > batch.expires_at,
+        FeePaymentError::RequestNotExpired
+    );
+
+    release_funds(&mut ctx.accounts.state, batch.calculated_fee)?;
+    release_ad_budget(ctx.accounts.ad.as_mut(), batch.ad_price)?;
+
+    emit!(ExpiredRequestClosed {
+        user: batch.user,
+        funds_returned: false,
+        closed_by: ctx.accounts.closer.key(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitiateBatchSend<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        init,
+        payer = user,
+        space = 8 + 525,
+        seeds = [b"batch", user.key().as_ref()],
+        bump
+    )]
+    pub batch: Account<'info, BatchRequest>,
+    /// Wins the placement unless a competing ad outbids it
+    #[account(
+        mut,
+        constraint = selected_ad.status == AdStatus::Approved @ FeePaymentError::AdNotActive
+    )]
+    pub selected_ad: Account<'info, Advertisement>,
+    #[account(
+        seeds = [b"ad_registry"],
+        bump = ad_registry.bump
+    )]
+    pub ad_registry: Account<'info, AdRegistry>,
+    /// Per-user impression counts backing advertiser frequency caps
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 709,
+        seeds = [b"impressions", user.key().as_ref()],
+        bump
+    )]
+    pub impressions: Account<'info, UserImpressions>,
+    /// User's declared preferences, matched against ad targeting
+    #[account(
+        seeds = [b"preferences", user.key().as_ref()],
+        bump = preferences.bump
+    )]
+    pub preferences: Option<Account<'info, UserPreferences>>,
+    /// Required when the selected ad is a campaign variant
+    #[account(
+        seeds = [b"campaign", campaign.campaign_id.as_bytes()],
+        bump = campaign.bump,
+        constraint = selected_ad.campaign == Some(campaign.key()) @ FeePaymentError::InvalidCampaignVariant
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: User's BlockedAddress PDA, validated in check_address_access
+    pub user_blocked: UncheckedAccount<'info>,
+    /// CHECK: User's AllowedAddress PDA, required in allowlist mode
+    pub user_allowed: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CompleteBatch<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Treasury PDA for holding funds
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = state.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = ad.id == batch.selected_ad_id @ FeePaymentError::AdMismatch
+    )]
+    pub ad: Account<'info, Advertisement>,
+    #[account(
+        mut,
+        seeds = [b"batch", user.key().as_ref()],
+        bump = batch.bump,
+        has_one = user @ FeePaymentError::Unauthorized
+    )]
+    pub batch: Account<'info, BatchRequest>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: Fee account to receive sponsored gas fees
+    #[account(mut)]
+    pub fee_account: AccountInfo<'info>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 120,
+        seeds = [b"view_receipt", user.key().as_ref(), ad.key().as_ref()],
+        bump
+    )]
+    pub view_receipt: Account<'info, ViewReceipt>,
+    /// Required when the ad is a campaign variant, for per-variant counters
+    #[account(
+        mut,
+        seeds = [b"campaign", campaign.campaign_id.as_bytes()],
+        bump = campaign.bump,
+        constraint = ad.campaign == Some(campaign.key()) @ FeePaymentError::InvalidCampaignVariant
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelBatchRequest<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        has_one = user @ FeePaymentError::Unauthorized,
+        close = user
+    )]
+    pub batch: Account<'info, BatchRequest>,
+    /// Required when the batch reserved a clearing price on its ad
+    #[account(
+        mut,
+        constraint = ad.id == batch.selected_ad_id @ FeePaymentError::AdMismatch
+    )]
+    pub ad: Option<Account<'info, Advertisement>>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireBatchRequest<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"batch", batch.user.as_ref()],
+        bump = batch.bump,
+        close = user
+    )]
+    pub batch: Account<'info, BatchRequest>,
+    /// Required when the batch reserved a clearing price on its ad
+    #[account(
+        mut,
+        constraint = ad.id == batch.selected_ad_id @ FeePaymentError::AdMismatch
+    )]
+    pub ad: Option<Account<'info, Advertisement>>,
+    /// CHECK: Receives the batch request rent back
+    #[account(
+        mut,
+        address = batch.user @ FeePaymentError::Unauthorized
+    )]
+    pub user: AccountInfo<'info>,
+    pub closer: Signer<'info>,
+}
@@ -0,0 +1,129 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::helpers::*;
+use crate::state::*;
+
+pub(crate) fn create_campaign(ctx: Context<CreateCampaign>, campaign_id: String) -> Result<()> {
+    require!(
+        !campaign_id.is_empty() && campaign_id.len() <= MAX_CAMPAIGN_ID_LENGTH,
+        FeePaymentError::InvalidCampaignId
+    );
+
+    let campaign = &mut ctx.accounts.campaign;
+    let clock = Clock::get()?;
+
+    campaign.campaign_id = campaign_id.clone();
+    campaign.advertiser = ctx.accounts.advertiser.key();
+    campaign.variants = Vec::new();
+    campaign.is_active = true;
+    campaign.created_at = clock.unix_timestamp;
+    campaign.bump = ctx.bumps.campaign;
+
+    emit!(CampaignCreated {
+        campaign_id,
+        advertiser: campaign.advertiser,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn add_campaign_variant(ctx: Context<ManageCampaignVariant>, weight: u16) -> Result<()> {
+    let campaign = &mut ctx.accounts.campaign;
+    let ad = &mut ctx.accounts.ad;
+    require!(ad.campaign.is_none(), FeePaymentError::InvalidCampaignVariant);
+    require!(
+        campaign.variants.len() < MAX_CAMPAIGN_VARIANTS,
+        FeePaymentError::TooManyVariants
+    );
+
+    campaign.variants.push(CampaignVariant {
+        ad: ad.key(),
+        weight,
+        views: 0,
+        clicks: 0,
+    });
+    ad.campaign = Some(campaign.key());
+
+    emit!(CampaignVariantUpdated {
+        campaign_id: campaign.campaign_id.clone(),
+        ad_id: ad.id.clone(),
+        weight,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn set_variant_weight(ctx: Context<ManageCampaignVariant>, weight: u16) -> Result<()> {
+    let ad_key = ctx.accounts.ad.key();
+    let campaign = &mut ctx.accounts.campaign;
+    campaign_variant(campaign, &ad_key)?.weight = weight;
+
+    emit!(CampaignVariantUpdated {
+        campaign_id: campaign.campaign_id.clone(),
+        ad_id: ctx.accounts.ad.id.clone(),
+        weight,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn toggle_campaign(ctx: Context<ToggleCampaign>) -> Result<()> {
+    let campaign = &mut ctx.accounts.campaign;
+    campaign.is_active = !campaign.is_active;
+
+    emit!(CampaignToggled {
+        campaign_id: campaign.campaign_id.clone(),
+        is_active: campaign.is_active,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(campaign_id: String)]
+pub struct CreateCampaign<'info> {
+    #[account(
+        init,
+        payer = advertiser,
+        space = 8 + 332,
+        seeds = [b"campaign", campaign_id.as_bytes()],
+        bump
+    )]
+    pub campaign: Account<'info, Campaign>,
+    #[account(mut)]
+    pub advertiser: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageCampaignVariant<'info> {
+    #[account(
+        mut,
+        seeds = [b"campaign", campaign.campaign_id.as_bytes()],
+        bump = campaign.bump,
+        has_one = advertiser @ FeePaymentError::Unauthorized
+    )]
+    pub campaign: Account<'info, Campaign>,
+    #[account(
+        mut,
+        has_one = advertiser @ FeePaymentError::Unauthorized
+    )]
+    pub ad: Account<'info, Advertisement>,
+    pub advertiser: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ToggleCampaign<'info> {
+    #[account(
+        mut,
+        seeds = [b"campaign", campaign.campaign_id.as_bytes()],
+        bump = campaign.bump,
+        has_one = advertiser @ FeePaymentError::Unauthorized
+    )]
+    pub campaign: Account<'info, Campaign>,
+    pub advertiser: Signer<'info>,
+}
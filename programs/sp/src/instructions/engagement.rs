@@ -0,0 +1,191 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::helpers::*;
+use crate::instructions::ads::AdvertiserAction;
+use crate::state::*;
+
+pub(crate) fn record_click(ctx: Context<RecordClick>) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_COMPLETE)?;
+
+    let clock = Clock::get()?;
+    let view_receipt = &mut ctx.accounts.view_receipt;
+    require!(
+        view_receipt.viewed_at > 0
+            && clock.unix_timestamp - view_receipt.viewed_at <= CLICK_WINDOW,
+        FeePaymentError::ClickWindowClosed
+    );
+    require!(!view_receipt.clicked, FeePaymentError::AlreadyClicked);
+    check_ad_campaign(&ctx.accounts.ad, ctx.accounts.campaign.as_ref())?;
+
+    // The publisher that served the view shares in the click charge
+    match (view_receipt.publisher, ctx.accounts.publisher.as_ref()) {
+        (Some(publisher_key), Some(publisher)) => {
+            require!(publisher.key() == publisher_key, FeePaymentError::PublisherMismatch);
+        }
+        (Some(_), None) => return err!(FeePaymentError::PublisherRequired),
+        (None, Some(_)) => return err!(FeePaymentError::PublisherMismatch),
+        (None, None) => {}
+    }
+
+    // A click the remaining budget can't cover is still counted, unbilled
+    let ad = &mut ctx.accounts.ad;
+    let click_price = view_receipt.click_price;
+    let charged = click_price > 0
+        && ad.budget.saturating_sub(ad.reserved_budget) >= click_price;
+
+    view_receipt.clicked = true;
+    ad.click_count = ad.click_count
+        .checked_add(1)
+        .ok_or(FeePaymentError::MathOverflow)?;
+    if let Some(campaign) = ctx.accounts.campaign.as_mut() {
+        let variant = campaign_variant(campaign, &ad.key())?;
+        variant.clicks = variant.clicks
+            .checked_add(1)
+            .ok_or(FeePaymentError::MathOverflow)?;
+    }
+
+    if charged {
+        charge_ad(
+            ad,
+            &ctx.accounts.treasury,
+            &mut ctx.accounts.state,
+            click_price,
+            ctx.accounts.publisher.as_mut(),
+        )?;
+    }
+
+    emit!(AdClicked {
+        ad_id: ad.id.clone(),
+        user: view_receipt.user,
+        price: if charged { click_price } else { 0 },
+        click_count: ad.click_count,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn set_conversion_authority(
+    ctx: Context<AdvertiserAction>,
+    conversion_authority: Pubkey,
+    attribution_window: i64,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_AD_MANAGEMENT)?;
+    require!(
+        conversion_authority == Pubkey::default() || attribution_window > 0,
+        FeePaymentError::InvalidAttributionWindow
+    );
+
+    let ad = &mut ctx.accounts.ad;
+    ad.conversion_authority = conversion_authority;
+    ad.attribution_window = attribution_window;
+
+    emit!(ConversionAuthorityUpdated {
+        ad_id: ad.id.clone(),
+        conversion_authority,
+        attribution_window,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn record_conversion(
+    ctx: Context<RecordConversion>,
+    user: Pubkey,
+    ad_id: String,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_COMPLETE)?;
+    let clock = Clock::get()?;
+    let ad = &mut ctx.accounts.ad;
+    let view_receipt = &mut ctx.accounts.view_receipt;
+
+    require!(
+        view_receipt.viewed_at > 0
+            && clock.unix_timestamp - view_receipt.viewed_at <= ad.attribution_window,
+        FeePaymentError::AttributionWindowClosed
+    );
+    require!(!view_receipt.converted, FeePaymentError::AlreadyConverted);
+    view_receipt.converted = true;
+
+    ad.conversions = ad.conversions
+        .checked_add(1)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    // The ad was looked up by this id
+    emit!(AdConversion {
+        ad_id,
+        user,
+        authority: ctx.accounts.conversion_authority.key(),
+        viewed_at: view_receipt.viewed_at,
+        conversions: ad.conversions,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RecordClick<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Treasury PDA for holding funds
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = state.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+    #[account(mut)]
+    pub ad: Account<'info, Advertisement>,
+    #[account(
+        mut,
+        seeds = [b"view_receipt", user.key().as_ref(), ad.key().as_ref()],
+        bump = view_receipt.bump
+    )]
+    pub view_receipt: Account<'info, ViewReceipt>,
+    /// Required when the ad is a campaign variant, for per-variant counters
+    #[account(
+        mut,
+        seeds = [b"campaign", campaign.campaign_id.as_bytes()],
+        bump = campaign.bump,
+        constraint = ad.campaign == Some(campaign.key()) @ FeePaymentError::InvalidCampaignVariant
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+    /// Required when a publisher served the view
+    #[account(
+        mut,
+        seeds = [b"publisher", publisher.authority.as_ref()],
+        bump = publisher.bump
+    )]
+    pub publisher: Option<Account<'info, Publisher>>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(user: Pubkey, ad_id: String)]
+pub struct RecordConversion<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"ad", ad_id.as_bytes()],
+        bump = ad.bump,
+        constraint = ad.conversion_authority == conversion_authority.key() @ FeePaymentError::Unauthorized
+    )]
+    pub ad: Account<'info, Advertisement>,
+    #[account(
+        mut,
+        seeds = [b"view_receipt", user.as_ref(), ad.key().as_ref()],
+        bump = view_receipt.bump
+    )]
+    pub view_receipt: Account<'info, ViewReceipt>,
+    pub conversion_authority: Signer<'info>,
+}
@@ -0,0 +1,85 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::helpers::*;
+use crate::state::*;
+
+pub(crate) fn release_escrow(ctx: Context<ResolveEscrow>) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_COMPLETE)?;
+    let escrow = &ctx.accounts.escrow;
+    let authority = ctx.accounts.authority.key();
+
+    require!(
+        authority == escrow.user || authority == escrow.arbiter,
+        FeePaymentError::Unauthorized
+    );
+    require!(escrow.status == EscrowStatus::Held, FeePaymentError::InvalidStatus);
+
+    escrow.sub_lamports(escrow.amount)?;
+    ctx.accounts.recipient.add_lamports(escrow.amount)?;
+
+    emit!(EscrowReleased {
+        escrow: escrow.key(),
+        recipient: escrow.recipient,
+        amount: escrow.amount,
+        released_by: authority,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn refund_escrow(ctx: Context<ResolveEscrow>) -> Result<()> {
+    let escrow = &ctx.accounts.escrow;
+    let authority = ctx.accounts.authority.key();
+    let clock = Clock::get()?;
+
+    require!(
+        authority == escrow.arbiter
+            || (authority == escrow.user && clock.unix_timestamp >= escrow.refund_after),
+        FeePaymentError::Unauthorized
+    );
+    // An AwaitingAd escrow is still tied to its pending request, which
+    // returns the funds on cancel or expiry
+    require!(escrow.status == EscrowStatus::Held, FeePaymentError::InvalidStatus);
+
+    // The escrow lamports, rent included, go back to the user on close
+    emit!(EscrowRefunded {
+        escrow: escrow.key(),
+        user: escrow.user,
+        amount: escrow.amount,
+        refunded_by: authority,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ResolveEscrow<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"escrow", escrow.user.as_ref(), &escrow.escrow_id.to_le_bytes()],
+        bump = escrow.bump,
+        close = user
+    )]
+    pub escrow: Account<'info, Escrow>,
+    /// CHECK: Receives the escrow rent, and the amount on refund
+    #[account(
+        mut,
+        address = escrow.user @ FeePaymentError::Unauthorized
+    )]
+    pub user: AccountInfo<'info>,
+    /// CHECK: Recipient validation through constraint
+    #[account(
+        mut,
+        address = escrow.recipient @ FeePaymentError::RecipientMismatch
+    )]
+    pub recipient: AccountInfo<'info>,
+    pub authority: Signer<'info>,
+}
@@ -0,0 +1,148 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::helpers::*;
+use crate::state::*;
+
+pub(crate) fn register_merchant_sponsor(ctx: Context<RegisterMerchantSponsor>, skip_ad: bool) -> Result<()> {
+    let merchant_sponsor = &mut ctx.accounts.merchant_sponsor;
+    let clock = Clock::get()?;
+
+    merchant_sponsor.merchant = ctx.accounts.merchant.key();
+    merchant_sponsor.budget = 0;
+    merchant_sponsor.skip_ad = skip_ad;
+    merchant_sponsor.is_active = true;
+    merchant_sponsor.total_sponsored = 0;
+    merchant_sponsor.sponsored_count = 0;
+    merchant_sponsor.created_at = clock.unix_timestamp;
+    merchant_sponsor.bump = ctx.bumps.merchant_sponsor;
+
+    emit!(MerchantSponsorRegistered {
+        merchant: merchant_sponsor.merchant,
+        skip_ad,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn update_merchant_sponsor(
+    ctx: Context<ManageMerchantSponsor>,
+    skip_ad: bool,
+    is_active: bool,
+) -> Result<()> {
+    let merchant_sponsor = &mut ctx.accounts.merchant_sponsor;
+    merchant_sponsor.skip_ad = skip_ad;
+    merchant_sponsor.is_active = is_active;
+
+    emit!(MerchantSponsorUpdated {
+        merchant: merchant_sponsor.merchant,
+        skip_ad,
+        is_active,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn fund_merchant_sponsor(ctx: Context<FundMerchantSponsor>, amount: u64) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_DEPOSIT)?;
+    require!(amount > 0 && amount <= MAX_SINGLE_DEPOSIT, FeePaymentError::InvalidAmount);
+
+    transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.funder.to_account_info(),
+                to: ctx.accounts.merchant_sponsor.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+
+    let merchant_sponsor = &mut ctx.accounts.merchant_sponsor;
+    merchant_sponsor.budget = merchant_sponsor.budget
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    emit!(MerchantSponsorFunded {
+        merchant: merchant_sponsor.merchant,
+        funder: ctx.accounts.funder.key(),
+        amount,
+        budget: merchant_sponsor.budget,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn withdraw_merchant_budget(ctx: Context<ManageMerchantSponsor>, amount: u64) -> Result<()> {
+    require!(amount > 0, FeePaymentError::InvalidAmount);
+
+    let merchant_sponsor = &mut ctx.accounts.merchant_sponsor;
+    require!(merchant_sponsor.budget >= amount, FeePaymentError::InsufficientMerchantBudget);
+
+    merchant_sponsor.budget -= amount;
+    merchant_sponsor.sub_lamports(amount)?;
+    ctx.accounts.merchant.add_lamports(amount)?;
+
+    emit!(MerchantSponsorWithdrawn {
+        merchant: merchant_sponsor.merchant,
+        amount,
+        remaining: merchant_sponsor.budget,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RegisterMerchantSponsor<'info> {
+    #[account(
+        init,
+        payer = merchant,
+        space = 8 + 67,
+        seeds = [b"merchant", merchant.key().as_ref()],
+        bump
+    )]
+    pub merchant_sponsor: Account<'info, MerchantSponsor>,
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageMerchantSponsor<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.key().as_ref()],
+        bump = merchant_sponsor.bump,
+        has_one = merchant @ FeePaymentError::Unauthorized
+    )]
+    pub merchant_sponsor: Account<'info, MerchantSponsor>,
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FundMerchantSponsor<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant_sponsor.merchant.as_ref()],
+        bump = merchant_sponsor.bump
+    )]
+    pub merchant_sponsor: Account<'info, MerchantSponsor>,
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,156 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::helpers::*;
+use crate::state::*;
+
+pub(crate) fn migrate_state(ctx: Context<MigrateState>) -> Result<()> {
+    let state_info = ctx.accounts.state.to_account_info();
+    grow_account(
+        &state_info,
+        &ctx.accounts.admin,
+        &ctx.accounts.system_program,
+        PROGRAM_STATE_SPACE,
+    )?;
+
+    let mut state = ProgramState::try_deserialize(&mut &state_info.try_borrow_data()?[..])?;
+    require!(
+        state.admin == ctx.accounts.admin.key(),
+        FeePaymentError::Unauthorized
+    );
+    require!(state.version < STATE_VERSION, FeePaymentError::AlreadyMigrated);
+    let from_version = state.version;
+
+    // The legacy `is_paused` bool shares its byte with `paused_operations`
+    if state.paused_operations != 0 {
+        state.paused_operations = PAUSE_ALL;
+    }
+    // Appended fields read back as zero; give them their defaults
+    state.relayer_fee = DEFAULT_RELAYER_FEE;
+    state.min_liquid_buffer = DEFAULT_MIN_LIQUID_BUFFER;
+    state.outflow_window_slots = DEFAULT_OUTFLOW_WINDOW_SLOTS;
+    state.publisher_share_bps = DEFAULT_PUBLISHER_SHARE_BPS;
+    state.moderator = state.admin;
+    state.report_threshold = DEFAULT_REPORT_THRESHOLD;
+    // Deposits from before tracking are best approximated by the balance
+    state.total_deposited = state.total_funds;
+    state.version = STATE_VERSION;
+    state.try_serialize(&mut &mut state_info.try_borrow_mut_data()?[..])?;
+
+    emit!(AccountMigrated {
+        account: state_info.key(),
+        from_version,
+        to_version: STATE_VERSION,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn migrate_ad(ctx: Context<MigrateAd>) -> Result<()> {
+    let ad_info = ctx.accounts.ad.to_account_info();
+    grow_account(
+        &ad_info,
+        &ctx.accounts.admin,
+        &ctx.accounts.system_program,
+        ADVERTISEMENT_SPACE,
+    )?;
+
+    let mut ad = Advertisement::try_deserialize(&mut &ad_info.try_borrow_data()?[..])?;
+    require!(ad.version < AD_VERSION, FeePaymentError::AlreadyMigrated);
+    let from_version = ad.version;
+
+    // Legacy ads were created by the admin
+    ad.advertiser = ctx.accounts.state.admin;
+    // `status` replaced the `is_active` bool in place: true reads back as
+    // Approved, false as PendingReview
+    if ad.status == AdStatus::PendingReview {
+        ad.status = AdStatus::Paused;
+    }
+    ad.version = AD_VERSION;
+    ad.try_serialize(&mut &mut ad_info.try_borrow_mut_data()?[..])?;
+
+    emit!(AccountMigrated {
+        account: ad_info.key(),
+        from_version,
+        to_version: AD_VERSION,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn close_legacy_request(ctx: Context<CloseLegacyRequest>) -> Result<()> {
+    let request_info = ctx.accounts.request.to_account_info();
+    require!(
+        request_info.data_len() == LEGACY_TRANSACTION_REQUEST_SPACE,
+        FeePaymentError::AlreadyMigrated
+    );
+    require!(
+        request_info.try_borrow_data()?.starts_with(TransactionRequest::DISCRIMINATOR),
+        ErrorCode::AccountDiscriminatorMismatch
+    );
+
+    // Legacy requests neither locked the amount nor reserved the fee, so
+    // there is only the rent to return
+    let lamports = request_info.lamports();
+    request_info.sub_lamports(lamports)?;
+    ctx.accounts.user.add_lamports(lamports)?;
+    request_info.assign(&system_program::ID);
+    request_info.resize(0)?;
+
+    emit!(RequestCancelled {
+        user: ctx.accounts.user.key(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateState<'info> {
+    /// CHECK: Older layouts don't deserialize as `ProgramState` until reallocated;
+    /// the discriminator and admin are checked after the resize
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump,
+        owner = crate::ID
+    )]
+    pub state: AccountInfo<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateAd<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Older layouts don't deserialize as `Advertisement` until
+    /// reallocated; the discriminator is checked after the resize
+    #[account(mut, owner = crate::ID)]
+    pub ad: AccountInfo<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseLegacyRequest<'info> {
+    /// CHECK: Legacy requests are too short to deserialize as
+    /// `TransactionRequest`; the size and discriminator are checked instead
+    #[account(
+        mut,
+        seeds = [b"request", user.key().as_ref()],
+        bump,
+        owner = crate::ID
+    )]
+    pub request: AccountInfo<'info>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
@@ -0,0 +1,33 @@
+pub mod access;
+pub mod admin;
+pub mod ads;
+pub mod batch;
+pub mod campaign;
+pub mod engagement;
+pub mod escrow;
+pub mod merchant;
+pub mod migration;
+pub mod moderation;
+pub mod publisher;
+pub mod relayer;
+pub mod send;
+pub mod sponsor_pool;
+pub mod subscription;
+pub mod treasury;
+
+pub use access::*;
+pub use admin::*;
+pub use ads::*;
+pub use batch::*;
+pub use campaign::*;
+pub use engagement::*;
+pub use escrow::*;
+pub use merchant::*;
+pub use migration::*;
+pub use moderation::*;
+pub use publisher::*;
+pub use relayer::*;
+pub use send::*;
+pub use sponsor_pool::*;
+pub use subscription::*;
+pub use treasury::*;
@@ -0,0 +1,136 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::state::*;
+
+pub(crate) fn approve_ad(ctx: Context<ModerateAd>) -> Result<()> {
+    let ad = &mut ctx.accounts.ad;
+    require!(
+        matches!(ad.status, AdStatus::PendingReview | AdStatus::Suspended),
+        FeePaymentError::InvalidAdStatus
+    );
+
+    // Past reporters keep their report PDAs, so they can't re-report
+    if ad.status == AdStatus::Suspended {
+        ad.report_count = 0;
+    }
+    ad.status = AdStatus::Approved;
+    ad.rejection_reason = 0;
+
+    emit!(AdApproved {
+        ad_id: ad.id.clone(),
+        moderator: ctx.accounts.moderator.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn reject_ad(ctx: Context<ModerateAd>, reason_code: u8) -> Result<()> {
+    let ad = &mut ctx.accounts.ad;
+    require!(
+        matches!(
+            ad.status,
+            AdStatus::PendingReview
+                | AdStatus::Approved
+                | AdStatus::Paused
+                | AdStatus::Exhausted
+                | AdStatus::Suspended
+        ),
+        FeePaymentError::InvalidAdStatus
+    );
+
+    ad.status = AdStatus::Rejected;
+    ad.rejection_reason = reason_code;
+
+    emit!(AdRejected {
+        ad_id: ad.id.clone(),
+        reason_code,
+        moderator: ctx.accounts.moderator.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn report_ad(ctx: Context<ReportAd>, reason: u8) -> Result<()> {
+    require!(ctx.accounts.view_receipt.view_count > 0, FeePaymentError::NoViewReceipt);
+    let clock = Clock::get()?;
+
+    let report = &mut ctx.accounts.report;
+    report.ad = ctx.accounts.ad.key();
+    report.user = ctx.accounts.user.key();
+    report.reason = reason;
+    report.reported_at = clock.unix_timestamp;
+    report.bump = ctx.bumps.report;
+
+    let ad = &mut ctx.accounts.ad;
+    ad.report_count = ad.report_count
+        .checked_add(1)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    emit!(AdReported {
+        ad_id: ad.id.clone(),
+        user: report.user,
+        reason,
+        report_count: ad.report_count,
+    });
+
+    if ad.report_count > ctx.accounts.state.report_threshold
+        && matches!(
+            ad.status,
+            AdStatus::PendingReview | AdStatus::Approved | AdStatus::Paused | AdStatus::Exhausted
+        )
+    {
+        ad.status = AdStatus::Suspended;
+
+        emit!(AdSuspended {
+            ad_id: ad.id.clone(),
+            report_count: ad.report_count,
+            timestamp: clock.unix_timestamp,
+        });
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ModerateAd<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump,
+        constraint = state.moderator == moderator.key() @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(mut)]
+    pub ad: Account<'info, Advertisement>,
+    pub moderator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReportAd<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(mut)]
+    pub ad: Account<'info, Advertisement>,
+    /// Only exists once the user has completed a view of the ad
+    #[account(
+        seeds = [b"view_receipt", user.key().as_ref(), ad.key().as_ref()],
+        bump = view_receipt.bump
+    )]
+    pub view_receipt: Account<'info, ViewReceipt>,
+    /// One report per (ad, user)
+    #[account(
+        init,
+        payer = user,
+        space = 8 + 74,
+        seeds = [b"report", ad.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub report: Account<'info, AdReport>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
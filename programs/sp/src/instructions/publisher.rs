@@ -0,0 +1,107 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::state::*;
+
+pub(crate) fn register_publisher(ctx: Context<RegisterPublisher>, authority: Pubkey) -> Result<()> {
+    let publisher = &mut ctx.accounts.publisher;
+    let clock = Clock::get()?;
+
+    publisher.authority = authority;
+    publisher.is_active = true;
+    publisher.claimable = 0;
+    publisher.total_earned = 0;
+    publisher.views_served = 0;
+    publisher.registered_at = clock.unix_timestamp;
+    publisher.bump = ctx.bumps.publisher;
+
+    emit!(PublisherRegistered {
+        publisher: authority,
+        admin: ctx.accounts.admin.key(),
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn toggle_publisher(ctx: Context<TogglePublisher>) -> Result<()> {
+    let publisher = &mut ctx.accounts.publisher;
+    publisher.is_active = !publisher.is_active;
+
+    emit!(PublisherToggled {
+        publisher: publisher.authority,
+        is_active: publisher.is_active,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn claim_publisher_revenue(ctx: Context<ClaimPublisherRevenue>) -> Result<()> {
+    let publisher = &mut ctx.accounts.publisher;
+    let amount = publisher.claimable;
+    require!(amount > 0, FeePaymentError::InvalidAmount);
+
+    publisher.claimable = 0;
+    publisher.sub_lamports(amount)?;
+    ctx.accounts.authority.add_lamports(amount)?;
+
+    emit!(PublisherRevenueClaimed {
+        publisher: ctx.accounts.authority.key(),
+        amount,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(authority: Pubkey)]
+pub struct RegisterPublisher<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 66,
+        seeds = [b"publisher", authority.as_ref()],
+        bump
+    )]
+    pub publisher: Account<'info, Publisher>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TogglePublisher<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"publisher", publisher.authority.as_ref()],
+        bump = publisher.bump
+    )]
+    pub publisher: Account<'info, Publisher>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimPublisherRevenue<'info> {
+    #[account(
+        mut,
+        seeds = [b"publisher", authority.key().as_ref()],
+        bump = publisher.bump,
+        has_one = authority @ FeePaymentError::Unauthorized
+    )]
+    pub publisher: Account<'info, Publisher>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
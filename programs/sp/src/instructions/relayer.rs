@@ -0,0 +1,417 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::helpers::*;
+use crate::state::*;
+
+pub(crate) fn initiate_relayed_send<'info>(
+    ctx: Context<'_, '_, 'info, 'info, InitiateRelayedSend<'info>>,
+    recipient: Pubkey,
+    amount: u64,
+    nonce: u64,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_INITIATE)?;
+    require!(recipient != Pubkey::default(), FeePaymentError::InvalidRecipient);
+    require!(amount > 0, FeePaymentError::InvalidAmount);
+    check_address_access(
+        &ctx.accounts.state,
+        &ctx.accounts.user.key(),
+        &ctx.accounts.user_blocked,
+        ctx.accounts.user_allowed.as_deref(),
+    )?;
+    check_address_access(
+        &ctx.accounts.state,
+        &recipient,
+        &ctx.accounts.recipient_blocked,
+        ctx.accounts.recipient_allowed.as_deref(),
+    )?;
+
+    // Intents are consumed strictly in order, so a signed intent can neither
+    // be replayed nor run ahead of ones the user signed earlier
+    let user_profile = &mut ctx.accounts.user_profile;
+    require!(nonce == user_profile.nonce, FeePaymentError::InvalidNonce);
+
+    let intent = SendIntent {
+        program_id: crate::ID,
+        user: ctx.accounts.user.key(),
+        recipient,
+        amount,
+        nonce,
+    };
+    verify_signed_intent(
+        &ctx.accounts.instructions.to_account_info(),
+        &intent.user,
+        &intent.try_to_vec()?,
+    )?;
+
+    user_profile.nonce = nonce
+        .checked_add(1)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    // A campaign's creative is the user's assigned variant
+    check_ad_campaign(&ctx.accounts.selected_ad, ctx.accounts.campaign.as_ref())?;
+    if let Some(campaign) = ctx.accounts.campaign.as_ref() {
+        require!(
+            assigned_variant(campaign, &ctx.accounts.user.key())? == ctx.accounts.selected_ad.key(),
+            FeePaymentError::VariantMismatch
+        );
+    }
+
+    // Every registered ad, passed as remaining accounts, bids for this placement
+    let audience = AdAudience {
+        user: ctx.accounts.user.key(),
+        preferences: ctx.accounts.preferences.as_deref(),
+        recipients: std::slice::from_ref(&recipient),
+        amount,
+        allowed_ads: &[],
+        impressions: &ctx.accounts.impressions,
+    };
+    let placement = run_ad_auction(
+        &ctx.accounts.state,
+        &ctx.accounts.ad_registry,
+        &mut ctx.accounts.selected_ad,
+        ctx.remaining_accounts,
+        &audience,
+    )?;
+    let ad = &placement.ad;
+    record_impression(
+        &mut ctx.accounts.impressions,
+        ctx.accounts.user.key(),
+        ctx.bumps.impressions,
+        &placement,
+        Clock::get()?.unix_timestamp,
+    )?;
+
+    let state = &mut ctx.accounts.state;
+    let calculated_fee = calculate_gas_fee(amount, state);
+    let relayer_fee = state.relayer_fee;
+    reserve_funds(
+        state,
+        calculated_fee
+            .checked_add(relayer_fee)
+            .ok_or(FeePaymentError::MathOverflow)?,
+    )?;
+
+    // The user can't sign the transfer on-chain, so the amount is locked
+    // from their prefunded profile balance instead
+    let user_profile = &mut ctx.accounts.user_profile;
+    user_profile.balance = user_profile.balance
+        .checked_sub(amount)
+        .ok_or(FeePaymentError::InsufficientProfileBalance)?;
+    user_profile.sub_lamports(amount)?;
+    ctx.accounts.request.add_lamports(amount)?;
+
+    let request = &mut ctx.accounts.request;
+    let clock = Clock::get()?;
+
+    request.user = intent.user;
+    request.recipient = recipient;
+    request.amount = amount;
+    request.calculated_fee = calculated_fee;
+    request.status = RequestStatus::WaitingForAd;
+    request.selected_ad_id = ad.id.clone();
+    request.ad_display_started_at = Some(clock.unix_timestamp);
+    request.created_at = clock.unix_timestamp;
+    request.expires_at = clock.unix_timestamp + TRANSACTION_TIMEOUT;
+    request.bump = ctx.bumps.request;
+    request.version = REQUEST_VERSION;
+    request.relayer = Some(ctx.accounts.relayer_authority.key());
+    request.escrow = None;
+    request.funds_locked = true;
+    request.relayer_fee = relayer_fee;
+    request.pool = None;
+    request.ad_price = placement.price;
+    request.publisher = ctx.accounts.publisher.as_ref().map(|publisher| publisher.key());
+
+    emit!(TransactionInitiated {
+        user: request.user,
+        recipient,
+        amount,
+        calculated_fee,
+        funds_locked: true,
+        ad_id: ad.id.clone(),
+        ad_content: ad.content.clone(),
+        ad_url: ad.url.clone(),
+        display_duration: ad.display_duration,
+        creative_mode: ad.creative_mode,
+        content_uri: ad.content_uri.clone(),
+        content_hash: ad.content_hash,
+        mime_type: ad.mime_type.clone(),
+        ad_price: placement.price,
+        request_id: request.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn create_user_profile(ctx: Context<CreateUserProfile>) -> Result<()> {
+    let user_profile = &mut ctx.accounts.user_profile;
+
+    user_profile.user = ctx.accounts.user.key();
+    user_profile.nonce = 0;
+    user_profile.created_at = Clock::get()?.unix_timestamp;
+    user_profile.bump = ctx.bumps.user_profile;
+    user_profile.balance = 0;
+
+    emit!(UserProfileCreated {
+        user: user_profile.user,
+        payer: ctx.accounts.payer.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn deposit_to_profile(ctx: Context<DepositToProfile>, amount: u64) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_DEPOSIT)?;
+    require!(amount > 0 && amount <= MAX_SINGLE_DEPOSIT, FeePaymentError::InvalidAmount);
+
+    transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.user.to_account_info(),
+                to: ctx.accounts.user_profile.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+
+    let user_profile = &mut ctx.accounts.user_profile;
+    user_profile.balance = user_profile.balance
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    emit!(UserProfileFunded {
+        user: user_profile.user,
+        amount,
+        balance: user_profile.balance,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn withdraw_from_profile(ctx: Context<WithdrawFromProfile>, amount: u64) -> Result<()> {
+    require!(amount > 0, FeePaymentError::InvalidAmount);
+
+    let user_profile = &mut ctx.accounts.user_profile;
+    user_profile.balance = user_profile.balance
+        .checked_sub(amount)
+        .ok_or(FeePaymentError::InsufficientProfileBalance)?;
+    user_profile.sub_lamports(amount)?;
+    ctx.accounts.user.add_lamports(amount)?;
+
+    emit!(UserProfileWithdrawn {
+        user: user_profile.user,
+        amount,
+        balance: user_profile.balance,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn register_relayer(ctx: Context<RegisterRelayer>, authority: Pubkey) -> Result<()> {
+    let relayer = &mut ctx.accounts.relayer;
+    let clock = Clock::get()?;
+
+    relayer.authority = authority;
+    relayer.is_active = true;
+    relayer.total_relayed = 0;
+    relayer.total_reimbursed = 0;
+    relayer.registered_at = clock.unix_timestamp;
+    relayer.bump = ctx.bumps.relayer;
+
+    emit!(RelayerRegistered {
+        relayer: authority,
+        admin: ctx.accounts.admin.key(),
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn toggle_relayer(ctx: Context<ToggleRelayer>) -> Result<()> {
+    let relayer = &mut ctx.accounts.relayer;
+    relayer.is_active = !relayer.is_active;
+
+    emit!(RelayerToggled {
+        relayer: relayer.authority,
+        is_active: relayer.is_active,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitiateRelayedSend<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        seeds = [b"relayer", relayer_authority.key().as_ref()],
+        bump = relayer.bump,
+        constraint = relayer.is_active @ FeePaymentError::RelayerNotActive
+    )]
+    pub relayer: Account<'info, Relayer>,
+    #[account(
+        init,
+        payer = relayer_authority,
+        space = TRANSACTION_REQUEST_SPACE,
+        seeds = [b"request", user.key().as_ref()],
+        bump
+    )]
+    pub request: Account<'info, TransactionRequest>,
+    #[account(
+        mut,
+        seeds = [b"user_profile", user.key().as_ref()],
+        bump = user_profile.bump
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+    /// Wins the placement unless a competing ad outbids it
+    #[account(
+        mut,
+        constraint = selected_ad.status == AdStatus::Approved @ FeePaymentError::AdNotActive
+    )]
+    pub selected_ad: Account<'info, Advertisement>,
+    #[account(
+        seeds = [b"ad_registry"],
+        bump = ad_registry.bump
+    )]
+    pub ad_registry: Account<'info, AdRegistry>,
+    /// Per-user impression counts backing advertiser frequency caps
+    #[account(
+        init_if_needed,
+        payer = relayer_authority,
+        space = 8 + 709,
+        seeds = [b"impressions", user.key().as_ref()],
+        bump
+    )]
+    pub impressions: Account<'info, UserImpressions>,
+    /// User's declared preferences, matched against ad targeting
+    #[account(
+        seeds = [b"preferences", user.key().as_ref()],
+        bump = preferences.bump
+    )]
+    pub preferences: Option<Account<'info, UserPreferences>>,
+    /// Required when the selected ad is a campaign variant
+    #[account(
+        seeds = [b"campaign", campaign.campaign_id.as_bytes()],
+        bump = campaign.bump,
+        constraint = selected_ad.campaign == Some(campaign.key()) @ FeePaymentError::InvalidCampaignVariant
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+    /// Publisher serving the ad, credited with a share of its charge
+    #[account(
+        seeds = [b"publisher", publisher.authority.as_ref()],
+        bump = publisher.bump,
+        constraint = publisher.is_active @ FeePaymentError::PublisherNotActive
+    )]
+    pub publisher: Option<Account<'info, Publisher>>,
+    /// CHECK: User authorizes through the Ed25519 signed intent, not as a signer
+    pub user: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub relayer_authority: Signer<'info>,
+    /// CHECK: Instructions sysvar used to read the Ed25519 precompile instruction
+    #[account(address = solana_instructions_sysvar::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    /// CHECK: User's BlockedAddress PDA, validated in check_address_access
+    pub user_blocked: UncheckedAccount<'info>,
+    /// CHECK: User's AllowedAddress PDA, required in allowlist mode
+    pub user_allowed: Option<UncheckedAccount<'info>>,
+    /// CHECK: Recipient's BlockedAddress PDA, validated in check_address_access
+    pub recipient_blocked: UncheckedAccount<'info>,
+    /// CHECK: Recipient's AllowedAddress PDA, required in allowlist mode
+    pub recipient_allowed: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateUserProfile<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 57,
+        seeds = [b"user_profile", user.key().as_ref()],
+        bump
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+    /// CHECK: Only used as the profile seed
+    pub user: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositToProfile<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"user_profile", user.key().as_ref()],
+        bump = user_profile.bump
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFromProfile<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_profile", user.key().as_ref()],
+        bump = user_profile.bump
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(authority: Pubkey)]
+pub struct RegisterRelayer<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 58,
+        seeds = [b"relayer", authority.as_ref()],
+        bump
+    )]
+    pub relayer: Account<'info, Relayer>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ToggleRelayer<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"relayer", relayer.authority.as_ref()],
+        bump = relayer.bump
+    )]
+    pub relayer: Account<'info, Relayer>,
+    pub admin: Signer<'info>,
+}
@@ -0,0 +1,1055 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::helpers::*;
+use crate::state::*;
+
+pub(crate) fn initiate_send<'info>(
+    ctx: Context<'_, '_, 'info, 'info, InitiateSend<'info>>,
+    recipient: Pubkey,
+    amount: u64,
+    lock_funds: bool,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_INITIATE)?;
+    require!(recipient != Pubkey::default(), FeePaymentError::InvalidRecipient);
+    require!(amount > 0, FeePaymentError::InvalidAmount);
+    check_address_access(
+        &ctx.accounts.state,
+        &ctx.accounts.user.key(),
+        &ctx.accounts.user_blocked,
+        ctx.accounts.user_allowed.as_deref(),
+    )?;
+    check_address_access(
+        &ctx.accounts.state,
+        &recipient,
+        &ctx.accounts.recipient_blocked,
+        ctx.accounts.recipient_allowed.as_deref(),
+    )?;
+
+    // A campaign's creative is the user's assigned variant
+    check_ad_campaign(&ctx.accounts.selected_ad, ctx.accounts.campaign.as_ref())?;
+    if let Some(campaign) = ctx.accounts.campaign.as_ref() {
+        require!(
+            assigned_variant(campaign, &ctx.accounts.user.key())? == ctx.accounts.selected_ad.key(),
+            FeePaymentError::VariantMismatch
+        );
+    }
+
+    // Every registered ad, passed as remaining accounts, bids for this placement
+    let audience = AdAudience {
+        user: ctx.accounts.user.key(),
+        preferences: ctx.accounts.preferences.as_deref(),
+        recipients: std::slice::from_ref(&recipient),
+        amount,
+        allowed_ads: ctx.accounts.pool
+            .as_ref()
+            .map_or(&[], |pool| pool.allowed_ads.as_slice()),
+        impressions: &ctx.accounts.impressions,
+    };
+    let placement = run_ad_auction(
+        &ctx.accounts.state,
+        &ctx.accounts.ad_registry,
+        &mut ctx.accounts.selected_ad,
+        ctx.remaining_accounts,
+        &audience,
+    )?;
+    let ad = &placement.ad;
+    record_impression(
+        &mut ctx.accounts.impressions,
+        ctx.accounts.user.key(),
+        ctx.bumps.impressions,
+        &placement,
+        Clock::get()?.unix_timestamp,
+    )?;
+
+    // Reserve the fee so concurrent requests can't oversubscribe the treasury
+    // or the selected pool
+    let calculated_fee = match ctx.accounts.pool.as_mut() {
+        Some(pool) => {
+            check_pool_eligibility(pool, &recipient, amount, &placement.key)?;
+            let pool_fee = calculate_pool_fee(amount, pool)?;
+            reserve_pool_funds(pool, pool_fee)?;
+            pool_fee
+        }
+        None => {
+            let state = &mut ctx.accounts.state;
+            let calculated_fee = calculate_gas_fee(amount, state);
+            reserve_funds(state, calculated_fee)?;
+            calculated_fee
+        }
+    };
+
+    let request = &mut ctx.accounts.request;
+    let clock = Clock::get()?;
+
+    // Validate ad is live
+    require!(ad.status == AdStatus::Approved, FeePaymentError::AdNotActive);
+
+    if lock_funds {
+        transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.user.to_account_info(),
+                    to: request.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+    }
+
+    request.user = ctx.accounts.user.key();
+    request.recipient = recipient;
+    request.amount = amount;
+    request.calculated_fee = calculated_fee;
+    request.status = RequestStatus::WaitingForAd;
+    request.selected_ad_id = ad.id.clone();
+    request.ad_display_started_at = Some(clock.unix_timestamp);
+    request.created_at = clock.unix_timestamp;
+    request.expires_at = clock.unix_timestamp + TRANSACTION_TIMEOUT;
+    request.bump = ctx.bumps.request;
+    request.version = REQUEST_VERSION;
+    request.relayer = None;
+    request.escrow = None;
+    request.funds_locked = lock_funds;
+    request.relayer_fee = 0;
+    request.pool = ctx.accounts.pool.as_ref().map(|pool| pool.key());
+    request.ad_price = placement.price;
+    request.publisher = ctx.accounts.publisher.as_ref().map(|publisher| publisher.key());
+
+    // Emit event with ad content for frontend to display
+    emit!(TransactionInitiated {
+        user: request.user,
+        recipient,
+        amount,
+        calculated_fee,
+        funds_locked: lock_funds,
+        ad_id: ad.id.clone(),
+        ad_content: ad.content.clone(),
+        ad_url: ad.url.clone(),
+        display_duration: ad.display_duration,
+        creative_mode: ad.creative_mode,
+        content_uri: ad.content_uri.clone(),
+        content_hash: ad.content_hash,
+        mime_type: ad.mime_type.clone(),
+        ad_price: placement.price,
+        request_id: request.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn initiate_escrow_send<'info>(
+    ctx: Context<'_, '_, 'info, 'info, InitiateEscrowSend<'info>>,
+    escrow_id: u64,
+    recipient: Pubkey,
+    amount: u64,
+    arbiter: Pubkey,
+    refund_after: i64,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_INITIATE)?;
+    require!(recipient != Pubkey::default(), FeePaymentError::InvalidRecipient);
+    require!(amount > 0, FeePaymentError::InvalidAmount);
+    check_address_access(
+        &ctx.accounts.state,
+        &ctx.accounts.user.key(),
+        &ctx.accounts.user_blocked,
+        ctx.accounts.user_allowed.as_deref(),
+    )?;
+    check_address_access(
+        &ctx.accounts.state,
+        &recipient,
+        &ctx.accounts.recipient_blocked,
+        ctx.accounts.recipient_allowed.as_deref(),
+    )?;
+
+    let clock = Clock::get()?;
+    require!(
+        refund_after > clock.unix_timestamp + TRANSACTION_TIMEOUT,
+        FeePaymentError::InvalidRefundDeadline
+    );
+
+    // A campaign's creative is the user's assigned variant
+    check_ad_campaign(&ctx.accounts.selected_ad, ctx.accounts.campaign.as_ref())?;
+    if let Some(campaign) = ctx.accounts.campaign.as_ref() {
+        require!(
+            assigned_variant(campaign, &ctx.accounts.user.key())? == ctx.accounts.selected_ad.key(),
+            FeePaymentError::VariantMismatch
+        );
+    }
+
+    // Every registered ad, passed as remaining accounts, bids for this placement
+    let audience = AdAudience {
+        user: ctx.accounts.user.key(),
+        preferences: ctx.accounts.preferences.as_deref(),
+        recipients: std::slice::from_ref(&recipient),
+        amount,
+        allowed_ads: &[],
+        impressions: &ctx.accounts.impressions,
+    };
+    let placement = run_ad_auction(
+        &ctx.accounts.state,
+        &ctx.accounts.ad_registry,
+        &mut ctx.accounts.selected_ad,
+        ctx.remaining_accounts,
+        &audience,
+    )?;
+    let ad = &placement.ad;
+    record_impression(
+        &mut ctx.accounts.impressions,
+        ctx.accounts.user.key(),
+        ctx.bumps.impressions,
+        &placement,
+        Clock::get()?.unix_timestamp,
+    )?;
+
+    let state = &mut ctx.accounts.state;
+    let calculated_fee = calculate_gas_fee(amount, state);
+    reserve_funds(state, calculated_fee)?;
+
+    // Lock the amount up front
+    transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.user.to_account_info(),
+                to: ctx.accounts.escrow.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+
+    let escrow = &mut ctx.accounts.escrow;
+    escrow.user = ctx.accounts.user.key();
+    escrow.recipient = recipient;
+    escrow.arbiter = arbiter;
+    escrow.escrow_id = escrow_id;
+    escrow.amount = amount;
+    escrow.status = EscrowStatus::AwaitingAd;
+    escrow.refund_after = refund_after;
+    escrow.created_at = clock.unix_timestamp;
+    escrow.bump = ctx.bumps.escrow;
+
+    let request = &mut ctx.accounts.request;
+
+    request.user = ctx.accounts.user.key();
+    request.recipient = recipient;
+    request.amount = amount;
+    request.calculated_fee = calculated_fee;
+    request.status = RequestStatus::WaitingForAd;
+    request.selected_ad_id = ad.id.clone();
+    request.ad_display_started_at = Some(clock.unix_timestamp);
+    request.created_at = clock.unix_timestamp;
+    request.expires_at = clock.unix_timestamp + TRANSACTION_TIMEOUT;
+    request.bump = ctx.bumps.request;
+    request.version = REQUEST_VERSION;
+    request.relayer = None;
+    request.escrow = Some(escrow.key());
+    request.funds_locked = false;
+    request.relayer_fee = 0;
+    request.pool = None;
+    request.ad_price = placement.price;
+    request.publisher = ctx.accounts.publisher.as_ref().map(|publisher| publisher.key());
+
+    emit!(EscrowCreated {
+        escrow: escrow.key(),
+        user: escrow.user,
+        recipient,
+        arbiter,
+        amount,
+        refund_after,
+    });
+
+    emit!(TransactionInitiated {
+        user: request.user,
+        recipient,
+        amount,
+        calculated_fee,
+        funds_locked: true,
+        ad_id: ad.id.clone(),
+        ad_content: ad.content.clone(),
+        ad_url: ad.url.clone(),
+        display_duration: ad.display_duration,
+        creative_mode: ad.creative_mode,
+        content_uri: ad.content_uri.clone(),
+        content_hash: ad.content_hash,
+        mime_type: ad.mime_type.clone(),
+        ad_price: placement.price,
+        request_id: request.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn complete_transaction_after_ad(
+    ctx: Context<CompleteTransaction>,
+    view_duration: i64,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_COMPLETE)?;
+
+    let request = &mut ctx.accounts.request;
+    let ad = &mut ctx.accounts.ad;
+    let clock = Clock::get()?;
+
+    // Validate request state
+    require!(
+        request.status == RequestStatus::WaitingForAd,
+        FeePaymentError::InvalidStatus
+    );
+    require!(
+        clock.unix_timestamp <= request.expires_at,
+        FeePaymentError::RequestExpired
+    );
+    require!(
+        request.selected_ad_id == ad.id,
+        FeePaymentError::AdMismatch
+    );
+    check_ad_campaign(ad, ctx.accounts.campaign.as_ref())?;
+
+    // Get values before mutable borrowing
+    let user_amount = request.amount;
+    let gas_fee = request.calculated_fee;
+    let treasury_bump = ctx.accounts.state.treasury_bump;
+
+    // A funded merchant sponsor for the recipient pays the fee instead,
+    // and may waive the ad requirement for payments made to it. Its address
+    // is always passed, so a registered sponsor can't be left out.
+    let merchant_info = ctx.accounts.merchant_sponsor.to_account_info();
+    let mut merchant_sponsor = if merchant_info.data_is_empty() {
+        None
+    } else {
+        require_keys_eq!(*merchant_info.owner, crate::ID, ErrorCode::AccountOwnedByWrongProgram);
+        Some(MerchantSponsor::try_deserialize(&mut &merchant_info.try_borrow_data()?[..])?)
+    };
+    let merchant_pays = merchant_sponsor
+        .as_ref()
+        .is_some_and(|merchant| merchant.is_active && merchant.budget >= gas_fee);
+    let ad_skipped = merchant_pays
+        && merchant_sponsor
+            .as_ref()
+            .is_some_and(|merchant| merchant.skip_ad);
+
+    // Validate ad viewing time
+    if !ad_skipped {
+        let ad_started_at = request.ad_display_started_at.ok_or(FeePaymentError::AdNotStarted)?;
+        let actual_view_time = clock.unix_timestamp - ad_started_at;
+        require!(
+            view_duration >= ad.display_duration && actual_view_time >= ad.display_duration,
+            FeePaymentError::InsufficientViewTime
+        );
+    }
+
+    // Relayed requests are submitted by their relayer, and also reimburse it
+    // from the treasury. Anything else needs the user's signature.
+    let relayer_fee = request.relayer_fee;
+    match (request.relayer, ctx.accounts.relayer.as_ref()) {
+        (Some(relayer_key), Some(relayer)) => {
+            require!(relayer.authority == relayer_key, FeePaymentError::Unauthorized);
+            require!(relayer.is_active, FeePaymentError::RelayerNotActive);
+            require!(ctx.accounts.payer.key() == relayer_key, FeePaymentError::Unauthorized);
+        }
+        (Some(_), None) => return err!(FeePaymentError::RelayerRequired),
+        (None, Some(_)) => return err!(FeePaymentError::UnexpectedRelayer),
+        (None, None) => {
+            require!(ctx.accounts.user.is_signer, FeePaymentError::Unauthorized);
+        }
+    }
+
+    // Only the publisher bound at initiation shares in the ad charge
+    match (request.publisher, ctx.accounts.publisher.as_ref()) {
+        (Some(publisher_key), Some(publisher)) => {
+            require!(publisher.key() == publisher_key, FeePaymentError::PublisherMismatch);
+        }
+        (Some(_), None) => return err!(FeePaymentError::PublisherRequired),
+        (None, Some(_)) => return err!(FeePaymentError::PublisherMismatch),
+        (None, None) => {}
+    }
+    let treasury_reserved = treasury_reservation(request)?;
+    let treasury_pays_fee = !merchant_pays && request.pool.is_none();
+    let total_sponsored = if treasury_pays_fee { treasury_reserved } else { relayer_fee };
+    
+    // Validate sufficient program funds for gas fee sponsorship
+    require!(
+        ctx.accounts.state.total_funds >= total_sponsored,
+        FeePaymentError::InsufficientProgramFunds
+    );
+
+    // A payout the circuit breaker refuses leaves the request waiting, so it
+    // can be completed or cancelled once the admin unpauses. The publisher's
+    // share comes out of the ad's lamports, not the treasury.
+    let ad_price = request.ad_price;
+    if !admit_treasury_outflow(&mut ctx.accounts.state, total_sponsored, clock.slot)? {
+        return Ok(());
+    }
+    
+    msg!("Executing gas fee sponsorship transaction...");
+    msg!("User sends: {} lamports", user_amount);
+    msg!("Recipient receives: {} lamports (exact same amount)", user_amount);
+    msg!("Program sponsors gas fee: {} lamports", gas_fee);
+    
+    // Transfer 1: User → Recipient (exact amount, no gas fee added),
+    // unless the amount is escrowed until release or refund
+    match request.escrow {
+        Some(escrow_key) => {
+            let escrow = ctx.accounts.escrow.as_mut().ok_or(FeePaymentError::EscrowRequired)?;
+            require!(escrow.key() == escrow_key, FeePaymentError::EscrowMismatch);
+            require!(
+                escrow.status == EscrowStatus::AwaitingAd,
+                FeePaymentError::InvalidStatus
+            );
+            escrow.status = EscrowStatus::Held;
+
+            emit!(EscrowHeld {
+                escrow: escrow_key,
+                user: escrow.user,
+                recipient: escrow.recipient,
+                amount: escrow.amount,
+            });
+        }
+        None if request.funds_locked => {
+            request.sub_lamports(user_amount)?;
+            ctx.accounts.recipient.add_lamports(user_amount)?;
+        }
+        None => {
+            transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.user.to_account_info(),
+                        to: ctx.accounts.recipient.to_account_info(),
+                    },
+                ),
+                user_amount,
+            )?;
+        }
+    }
+
+    // Transfer 2: Merchant, sponsor pool or treasury → Fee account (gas fee sponsorship)
+    let treasury_signer_seeds = &[b"treasury".as_ref(), &[treasury_bump]];
+
+    if let (true, Some(pool_key)) = (merchant_pays, request.pool) {
+        // The pool's reservation is no longer needed
+        let pool = ctx.accounts.pool.as_mut().ok_or(FeePaymentError::PoolRequired)?;
+        require!(pool.key() == pool_key, FeePaymentError::PoolMismatch);
+        release_pool_funds(pool, gas_fee)?;
+    }
+
+    match request.pool {
+        _ if merchant_pays => {
+            let merchant = merchant_sponsor
+                .as_mut()
+                .ok_or(FeePaymentError::MerchantSponsorRequired)?;
+            merchant.budget = merchant.budget
+                .checked_sub(gas_fee)
+                .ok_or(FeePaymentError::MathUnderflow)?;
+            merchant.total_sponsored = merchant.total_sponsored
+                .checked_add(gas_fee)
+                .ok_or(FeePaymentError::MathOverflow)?;
+            merchant.sponsored_count = merchant.sponsored_count
+                .checked_add(1)
+                .ok_or(FeePaymentError::MathOverflow)?;
+            merchant.try_serialize(&mut &mut merchant_info.try_borrow_mut_data()?[..])?;
+            merchant_info.sub_lamports(gas_fee)?;
+            ctx.accounts.fee_account.add_lamports(gas_fee)?;
+
+            emit!(MerchantSponsoredPayment {
+                merchant: merchant.merchant,
+                user: request.user,
+                amount: user_amount,
+                gas_fee,
+                ad_skipped,
+                remaining_budget: merchant.budget,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+        Some(pool_key) => {
+            let pool = ctx.accounts.pool.as_mut().ok_or(FeePaymentError::PoolRequired)?;
+            require!(pool.key() == pool_key, FeePaymentError::PoolMismatch);
+
+            release_pool_funds(pool, gas_fee)?;
+            pool.balance = pool.balance
+                .checked_sub(gas_fee)
+                .ok_or(FeePaymentError::MathUnderflow)?;
+            pool.total_sponsored = pool.total_sponsored
+                .checked_add(gas_fee)
+                .ok_or(FeePaymentError::MathOverflow)?;
+            pool.sponsored_count = pool.sponsored_count
+                .checked_add(1)
+                .ok_or(FeePaymentError::MathOverflow)?;
+            pool.sub_lamports(gas_fee)?;
+            ctx.accounts.fee_account.add_lamports(gas_fee)?;
+
+            emit!(PoolSponsorship {
+                pool: pool_key,
+                pool_id: pool.pool_id.clone(),
+                user: request.user,
+                gas_fee,
+                remaining_balance: pool.balance,
+            });
+        }
+        None => {
+            transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.system_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.treasury.to_account_info(),
+                        to: ctx.accounts.fee_account.to_account_info(),
+                    },
+                    &[treasury_signer_seeds],
+                ),
+                gas_fee,
+            )?;
+        }
+    }
+
+    // Transfer 3: Treasury → Relayer (reimbursement for rent and fees fronted)
+    if relayer_fee > 0 {
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.treasury.to_account_info(),
+                    to: ctx.accounts.rent_payer.to_account_info(),
+                },
+                &[treasury_signer_seeds],
+            ),
+            relayer_fee,
+        )?;
+    }
+
+    if let Some(relayer) = ctx.accounts.relayer.as_mut() {
+        relayer.total_relayed = relayer.total_relayed
+            .checked_add(1)
+            .ok_or(FeePaymentError::MathOverflow)?;
+        relayer.total_reimbursed = relayer.total_reimbursed
+            .checked_add(relayer_fee)
+            .ok_or(FeePaymentError::MathOverflow)?;
+
+        emit!(RelayerReimbursed {
+            relayer: relayer.authority,
+            user: request.user,
+            amount: relayer_fee,
+        });
+    }
+
+    // Update program state
+    let state = &mut ctx.accounts.state;
+    release_funds(state, treasury_reserved)?;
+    state.total_funds = state.total_funds
+        .checked_sub(total_sponsored)
+        .ok_or(FeePaymentError::MathUnderflow)?;
+    if treasury_pays_fee {
+        attribute_sponsorship(state)?;
+    }
+
+    // Charge the winning ad the clearing price reserved at initiation.
+    // Cost-per-click ads are only charged if the viewer clicks through.
+    if ad_price > 0 {
+        ad.reserved_budget = ad.reserved_budget
+            .checked_sub(ad_price)
+            .ok_or(FeePaymentError::MathUnderflow)?;
+        if !ad_skipped && ad.billing_model == BillingModel::PerView {
+            charge_ad(
+                ad,
+                &ctx.accounts.treasury,
+                state,
+                ad_price,
+                ctx.accounts.publisher.as_mut(),
+            )?;
+        }
+    }
+
+    // Receipt proving this user saw the ad, for click tracking
+    let view_receipt = &mut ctx.accounts.view_receipt;
+    if view_receipt.user == Pubkey::default() {
+        view_receipt.user = request.user;
+        view_receipt.ad = ad.key();
+        view_receipt.bump = ctx.bumps.view_receipt;
+    }
+    
+    // Update counters
+    if !ad_skipped {
+        record_view(view_receipt, ad, ad_price, request.publisher, clock.unix_timestamp)?;
+
+        ad.view_count = ad.view_count
+            .checked_add(1)
+            .ok_or(FeePaymentError::MathOverflow)?;
+        if let Some(campaign) = ctx.accounts.campaign.as_mut() {
+            let variant = campaign_variant(campaign, &ad.key())?;
+            variant.views = variant.views
+                .checked_add(1)
+                .ok_or(FeePaymentError::MathOverflow)?;
+        }
+
+        state.total_ads_viewed = state.total_ads_viewed
+            .checked_add(1)
+            .ok_or(FeePaymentError::MathOverflow)?;
+
+        if let Some(publisher) = ctx.accounts.publisher.as_mut() {
+            publisher.views_served = publisher.views_served
+                .checked_add(1)
+                .ok_or(FeePaymentError::MathOverflow)?;
+        }
+    }
+    
+    state.total_transactions = state.total_transactions
+        .checked_add(1)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    // Mark request as completed
+    request.status = RequestStatus::Completed;
+    request.completed_at = Some(clock.unix_timestamp);
+    request.ad_view_duration = Some(view_duration);
+
+    emit!(TransactionCompleted {
+        user: request.user,
+        recipient: request.recipient,
+        amount_sent: user_amount,
+        amount_received: user_amount,
+        gas_fee_sponsored: gas_fee,
+        ad_id: ad.id.clone(),
+        view_duration,
+        timestamp: clock.unix_timestamp,
+    });
+
+    msg!("✅ Transaction completed with gas fee sponsorship!");
+
+    ctx.accounts.request.close(ctx.accounts.rent_payer.to_account_info())?;
+
+    Ok(())
+}
+
+pub(crate) fn cancel_request(ctx: Context<CancelRequest>) -> Result<()> {
+    let request = &mut ctx.accounts.request;
+    
+    require!(
+        request.status == RequestStatus::WaitingForAd,
+        FeePaymentError::InvalidStatus
+    );
+
+    // An escrowed amount goes back to the user with the request
+    if let Some(escrow_key) = request.escrow {
+        let escrow = ctx.accounts.escrow.as_ref().ok_or(FeePaymentError::EscrowRequired)?;
+        require!(escrow.key() == escrow_key, FeePaymentError::EscrowMismatch);
+        require!(
+            escrow.status == EscrowStatus::AwaitingAd,
+            FeePaymentError::InvalidStatus
+        );
+
+        emit!(EscrowRefunded {
+            escrow: escrow_key,
+            user: escrow.user,
+            amount: escrow.amount,
+            refunded_by: request.user,
+        });
+    }
+
+    // A locked amount goes back to the user, even if a relayer paid the rent
+    if request.funds_locked {
+        request.sub_lamports(request.amount)?;
+        ctx.accounts.user.add_lamports(request.amount)?;
+    }
+
+    request.status = RequestStatus::Cancelled;
+    request.cancelled_at = Some(Clock::get()?.unix_timestamp);
+
+    release_funds(&mut ctx.accounts.state, treasury_reservation(request)?)?;
+    if let Some(pool_key) = request.pool {
+        let pool = ctx.accounts.pool.as_mut().ok_or(FeePaymentError::PoolRequired)?;
+        require!(pool.key() == pool_key, FeePaymentError::PoolMismatch);
+        release_pool_funds(pool, request.calculated_fee)?;
+    }
+    release_ad_budget(ctx.accounts.ad.as_mut(), request.ad_price)?;
+
+    emit!(RequestCancelled {
+        user: request.user,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn expire_request(ctx: Context<ExpireRequest>) -> Result<()> {
+    let request = &ctx.accounts.request;
+    let clock = Clock::get()?;
+
+    require!(
+        request.status == RequestStatus::WaitingForAd,
+        FeePaymentError::InvalidStatus
+    );
+    require!(
+        clock.unix_timestamp > request.expires_at,
+        FeePaymentError::RequestNotExpired
+    );
+
+    if let Some(escrow_key) = request.escrow {
+        let escrow = ctx.accounts.escrow.as_ref().ok_or(FeePaymentError::EscrowRequired)?;
+        require!(escrow.key() == escrow_key, FeePaymentError::EscrowMismatch);
+        require!(
+            escrow.status == EscrowStatus::AwaitingAd,
+            FeePaymentError::InvalidStatus
+        );
+
+        emit!(EscrowRefunded {
+            escrow: escrow_key,
+            user: escrow.user,
+            amount: escrow.amount,
+            refunded_by: ctx.accounts.closer.key(),
+        });
+    }
+
+    if request.funds_locked {
+        request.sub_lamports(request.amount)?;
+        ctx.accounts.user.add_lamports(request.amount)?;
+    }
+
+    release_funds(&mut ctx.accounts.state, treasury_reservation(request)?)?;
+    if let Some(pool_key) = request.pool {
+        let pool = ctx.accounts.pool.as_mut().ok_or(FeePaymentError::PoolRequired)?;
+        require!(pool.key() == pool_key, FeePaymentError::PoolMismatch);
+        release_pool_funds(pool, request.calculated_fee)?;
+    }
+    release_ad_budget(ctx.accounts.ad.as_mut(), request.ad_price)?;
+
+    emit!(ExpiredRequestClosed {
+        user: request.user,
+        funds_returned: request.funds_locked || request.escrow.is_some(),
+        closed_by: ctx.accounts.closer.key(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitiateSend<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        init,
+        payer = user,
+        space = TRANSACTION_REQUEST_SPACE,
+        seeds = [b"request", user.key().as_ref()],
+        bump
+    )]
+    pub request: Account<'info, TransactionRequest>,
+    /// Wins the placement unless a competing ad outbids it
+    #[account(
+        mut,
+        constraint = selected_ad.status == AdStatus::Approved @ FeePaymentError::AdNotActive
+    )]
+    pub selected_ad: Account<'info, Advertisement>,
+    #[account(
+        seeds = [b"ad_registry"],
+        bump = ad_registry.bump
+    )]
+    pub ad_registry: Account<'info, AdRegistry>,
+    /// Per-user impression counts backing advertiser frequency caps
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 709,
+        seeds = [b"impressions", user.key().as_ref()],
+        bump
+    )]
+    pub impressions: Account<'info, UserImpressions>,
+    /// User's declared preferences, matched against ad targeting
+    #[account(
+        seeds = [b"preferences", user.key().as_ref()],
+        bump = preferences.bump
+    )]
+    pub preferences: Option<Account<'info, UserPreferences>>,
+    /// Required when the selected ad is a campaign variant
+    #[account(
+        seeds = [b"campaign", campaign.campaign_id.as_bytes()],
+        bump = campaign.bump,
+        constraint = selected_ad.campaign == Some(campaign.key()) @ FeePaymentError::InvalidCampaignVariant
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+    /// Publisher serving the ad, credited with a share of its charge
+    #[account(
+        seeds = [b"publisher", publisher.authority.as_ref()],
+        bump = publisher.bump,
+        constraint = publisher.is_active @ FeePaymentError::PublisherNotActive
+    )]
+    pub publisher: Option<Account<'info, Publisher>>,
+    /// Sponsor pool funding the fee instead of the treasury, if any
+    #[account(
+        mut,
+        seeds = [b"pool", pool.pool_id.as_bytes()],
+        bump = pool.bump
+    )]
+    pub pool: Option<Account<'info, SponsorPool>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: User's BlockedAddress PDA, validated in check_address_access
+    pub user_blocked: UncheckedAccount<'info>,
+    /// CHECK: User's AllowedAddress PDA, required in allowlist mode
+    pub user_allowed: Option<UncheckedAccount<'info>>,
+    /// CHECK: Recipient's BlockedAddress PDA, validated in check_address_access
+    pub recipient_blocked: UncheckedAccount<'info>,
+    /// CHECK: Recipient's AllowedAddress PDA, required in allowlist mode
+    pub recipient_allowed: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(escrow_id: u64)]
+pub struct InitiateEscrowSend<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        init,
+        payer = user,
+        space = TRANSACTION_REQUEST_SPACE,
+        seeds = [b"request", user.key().as_ref()],
+        bump
+    )]
+    pub request: Account<'info, TransactionRequest>,
+    #[account(
+        init,
+        payer = user,
+        space = 8 + 131,
+        seeds = [b"escrow", user.key().as_ref(), &escrow_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+    /// Wins the placement unless a competing ad outbids it
+    #[account(
+        mut,
+        constraint = selected_ad.status == AdStatus::Approved @ FeePaymentError::AdNotActive
+    )]
+    pub selected_ad: Account<'info, Advertisement>,
+    #[account(
+        seeds = [b"ad_registry"],
+        bump = ad_registry.bump
+    )]
+    pub ad_registry: Account<'info, AdRegistry>,
+    /// Per-user impression counts backing advertiser frequency caps
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 709,
+        seeds = [b"impressions", user.key().as_ref()],
+        bump
+    )]
+    pub impressions: Account<'info, UserImpressions>,
+    /// User's declared preferences, matched against ad targeting
+    #[account(
+        seeds = [b"preferences", user.key().as_ref()],
+        bump = preferences.bump
+    )]
+    pub preferences: Option<Account<'info, UserPreferences>>,
+    /// Required when the selected ad is a campaign variant
+    #[account(
+        seeds = [b"campaign", campaign.campaign_id.as_bytes()],
+        bump = campaign.bump,
+        constraint = selected_ad.campaign == Some(campaign.key()) @ FeePaymentError::InvalidCampaignVariant
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+    /// Publisher serving the ad, credited with a share of its charge
+    #[account(
+        seeds = [b"publisher", publisher.authority.as_ref()],
+        bump = publisher.bump,
+        constraint = publisher.is_active @ FeePaymentError::PublisherNotActive
+    )]
+    pub publisher: Option<Account<'info, Publisher>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: User's BlockedAddress PDA, validated in check_address_access
+    pub user_blocked: UncheckedAccount<'info>,
+    /// CHECK: User's AllowedAddress PDA, required in allowlist mode
+    pub user_allowed: Option<UncheckedAccount<'info>>,
+    /// CHECK: Recipient's BlockedAddress PDA, validated in check_address_access
+    pub recipient_blocked: UncheckedAccount<'info>,
+    /// CHECK: Recipient's AllowedAddress PDA, required in allowlist mode
+    pub recipient_allowed: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CompleteTransaction<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Treasury PDA for holding funds
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = state.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = ad.id == request.selected_ad_id @ FeePaymentError::AdMismatch
+    )]
+    pub ad: Account<'info, Advertisement>,
+    #[account(
+        mut,
+        has_one = user @ FeePaymentError::Unauthorized,
+        constraint = request.recipient == recipient.key() @ FeePaymentError::RecipientMismatch
+    )]
+    pub request: Account<'info, TransactionRequest>,
+    /// CHECK: Request owner. Signs unless a relayer initiated the request, in
+    /// which case the amount is already locked in the request account.
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+    /// Pays for the view receipt: the user, or the relayer of a relayed request
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: Whoever funded the request rent - the relayer or the user
+    #[account(
+        mut,
+        address = request.relayer.unwrap_or(request.user) @ FeePaymentError::Unauthorized
+    )]
+    pub rent_payer: AccountInfo<'info>,
+    /// Required when the request was initiated by a relayer
+    #[account(
+        mut,
+        seeds = [b"relayer", rent_payer.key().as_ref()],
+        bump = relayer.bump
+    )]
+    pub relayer: Option<Account<'info, Relayer>>,
+    /// Required when the request's amount is escrowed
+    #[account(mut)]
+    pub escrow: Option<Account<'info, Escrow>>,
+    /// Required when the request is sponsored by a pool
+    #[account(mut)]
+    pub pool: Option<Account<'info, SponsorPool>>,
+    /// CHECK: Recipient validation through constraint
+    #[account(mut)]
+    pub recipient: AccountInfo<'info>,
+    /// CHECK: Fee account to receive sponsored gas fees
+    #[account(mut)]
+    pub fee_account: AccountInfo<'info>,
+    /// CHECK: Recipient's merchant sponsor address, empty unless the
+    /// recipient registered one. Deserialized in the handler.
+    #[account(
+        mut,
+        seeds = [b"merchant", recipient.key().as_ref()],
+        bump
+    )]
+    pub merchant_sponsor: UncheckedAccount<'info>,
+    /// Required when a publisher was bound to the request at initiation
+    #[account(
+        mut,
+        seeds = [b"publisher", publisher.authority.as_ref()],
+        bump = publisher.bump
+    )]
+    pub publisher: Option<Account<'info, Publisher>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + 120,
+        seeds = [b"view_receipt", user.key().as_ref(), ad.key().as_ref()],
+        bump
+    )]
+    pub view_receipt: Account<'info, ViewReceipt>,
+    /// Required when the ad is a campaign variant, for per-variant counters
+    #[account(
+        mut,
+        seeds = [b"campaign", campaign.campaign_id.as_bytes()],
+        bump = campaign.bump,
+        constraint = ad.campaign == Some(campaign.key()) @ FeePaymentError::InvalidCampaignVariant
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRequest<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        has_one = user @ FeePaymentError::Unauthorized,
+        close = rent_payer
+    )]
+    pub request: Account<'info, TransactionRequest>,
+    /// Required when the request's amount is escrowed
+    #[account(
+        mut,
+        has_one = user @ FeePaymentError::Unauthorized,
+        close = user
+    )]
+    pub escrow: Option<Account<'info, Escrow>>,
+    /// Required when the request is sponsored by a pool
+    #[account(mut)]
+    pub pool: Option<Account<'info, SponsorPool>>,
+    /// Required when the request reserved a clearing price on its ad
+    #[account(
+        mut,
+        constraint = ad.id == request.selected_ad_id @ FeePaymentError::AdMismatch
+    )]
+    pub ad: Option<Account<'info, Advertisement>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: Whoever funded the request rent - the relayer or the user
+    #[account(
+        mut,
+        address = request.relayer.unwrap_or(request.user) @ FeePaymentError::Unauthorized
+    )]
+    pub rent_payer: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireRequest<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"request", request.user.as_ref()],
+        bump = request.bump,
+        close = rent_payer
+    )]
+    pub request: Account<'info, TransactionRequest>,
+    /// CHECK: Whoever funded the request rent - the relayer or the user
+    #[account(
+        mut,
+        address = request.relayer.unwrap_or(request.user) @ FeePaymentError::Unauthorized
+    )]
+    pub rent_payer: AccountInfo<'info>,
+    /// Required when the request's amount is escrowed
+    #[account(
+        mut,
+        has_one = user @ FeePaymentError::Unauthorized,
+        close = user
+    )]
+    pub escrow: Option<Account<'info, Escrow>>,
+    /// Required when the request is sponsored by a pool
+    #[account(mut)]
+    pub pool: Option<Account<'info, SponsorPool>>,
+    /// Required when the request reserved a clearing price on its ad
+    #[account(
+        mut,
+        constraint = ad.id == request.selected_ad_id @ FeePaymentError::AdMismatch
+    )]
+    pub ad: Option<Account<'info, Advertisement>>,
+    /// CHECK: Receives a locked or escrowed amount back
+    #[account(
+        mut,
+        address = request.user @ FeePaymentError::Unauthorized
+    )]
+    pub user: AccountInfo<'info>,
+    pub closer: Signer<'info>,
+}
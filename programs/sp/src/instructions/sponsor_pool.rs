@@ -0,0 +1,190 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::helpers::*;
+use crate::state::*;
+
+pub(crate) fn create_sponsor_pool(
+    ctx: Context<CreateSponsorPool>,
+    pool_id: String,
+    authority: Pubkey,
+    rules: PoolRules,
+) -> Result<()> {
+    require!(
+        !pool_id.is_empty() && pool_id.len() <= MAX_POOL_ID_LENGTH,
+        FeePaymentError::InvalidPoolId
+    );
+    validate_pool_rules(&rules)?;
+
+    let pool = &mut ctx.accounts.pool;
+    let clock = Clock::get()?;
+
+    pool.pool_id = pool_id.clone();
+    pool.authority = authority;
+    pool.balance = 0;
+    pool.reserved = 0;
+    pool.base_fee = rules.base_fee;
+    pool.fee_bps = rules.fee_bps;
+    pool.max_amount = rules.max_amount;
+    pool.allowed_recipients = rules.allowed_recipients;
+    pool.allowed_ads = rules.allowed_ads;
+    pool.is_active = true;
+    pool.total_sponsored = 0;
+    pool.sponsored_count = 0;
+    pool.created_at = clock.unix_timestamp;
+    pool.bump = ctx.bumps.pool;
+
+    emit!(SponsorPoolCreated {
+        pool: pool.key(),
+        pool_id,
+        authority,
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn update_pool_rules(ctx: Context<ManageSponsorPool>, rules: PoolRules) -> Result<()> {
+    validate_pool_rules(&rules)?;
+
+    let pool = &mut ctx.accounts.pool;
+    pool.base_fee = rules.base_fee;
+    pool.fee_bps = rules.fee_bps;
+    pool.max_amount = rules.max_amount;
+    pool.allowed_recipients = rules.allowed_recipients;
+    pool.allowed_ads = rules.allowed_ads;
+
+    emit!(SponsorPoolRulesUpdated {
+        pool: pool.key(),
+        base_fee: pool.base_fee,
+        fee_bps: pool.fee_bps,
+        max_amount: pool.max_amount,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn toggle_sponsor_pool(ctx: Context<ManageSponsorPool>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    pool.is_active = !pool.is_active;
+
+    emit!(SponsorPoolToggled {
+        pool: pool.key(),
+        is_active: pool.is_active,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn fund_sponsor_pool(ctx: Context<FundSponsorPool>, amount: u64) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_DEPOSIT)?;
+    require!(amount > 0 && amount <= MAX_SINGLE_DEPOSIT, FeePaymentError::InvalidAmount);
+
+    transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.funder.to_account_info(),
+                to: ctx.accounts.pool.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+
+    let pool = &mut ctx.accounts.pool;
+    pool.balance = pool.balance
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    emit!(SponsorPoolFunded {
+        pool: pool.key(),
+        funder: ctx.accounts.funder.key(),
+        amount,
+        balance: pool.balance,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn withdraw_from_pool(ctx: Context<ManageSponsorPool>, amount: u64) -> Result<()> {
+    require!(amount > 0, FeePaymentError::InvalidAmount);
+
+    let pool = &mut ctx.accounts.pool;
+    require!(
+        pool.balance.saturating_sub(pool.reserved) >= amount,
+        FeePaymentError::InsufficientPoolFunds
+    );
+
+    pool.balance -= amount;
+    pool.sub_lamports(amount)?;
+    ctx.accounts.authority.add_lamports(amount)?;
+
+    emit!(SponsorPoolWithdrawn {
+        pool: pool.key(),
+        amount,
+        remaining: pool.balance,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(pool_id: String)]
+pub struct CreateSponsorPool<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 456,
+        seeds = [b"pool", pool_id.as_bytes()],
+        bump
+    )]
+    pub pool: Account<'info, SponsorPool>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageSponsorPool<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"pool", pool.pool_id.as_bytes()],
+        bump = pool.bump,
+        has_one = authority @ FeePaymentError::Unauthorized
+    )]
+    pub pool: Account<'info, SponsorPool>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FundSponsorPool<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"pool", pool.pool_id.as_bytes()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, SponsorPool>,
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
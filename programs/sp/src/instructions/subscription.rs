@@ -0,0 +1,558 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::helpers::*;
+use crate::state::*;
+
+pub(crate) fn create_subscription(
+    ctx: Context<CreateSubscription>,
+    subscription_id: u64,
+    recipient: Pubkey,
+    amount: u64,
+    interval: i64,
+    payment_count: u32,
+    sponsorship: SubscriptionSponsorship,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_INITIATE)?;
+    require!(recipient != Pubkey::default(), FeePaymentError::InvalidRecipient);
+    require!(amount > 0, FeePaymentError::InvalidAmount);
+    check_address_access(
+        &ctx.accounts.state,
+        &ctx.accounts.user.key(),
+        &ctx.accounts.user_blocked,
+        ctx.accounts.user_allowed.as_deref(),
+    )?;
+    check_address_access(
+        &ctx.accounts.state,
+        &recipient,
+        &ctx.accounts.recipient_blocked,
+        ctx.accounts.recipient_allowed.as_deref(),
+    )?;
+    require!(interval >= MIN_SUBSCRIPTION_INTERVAL, FeePaymentError::InvalidInterval);
+    require!(
+        payment_count > 0 && payment_count <= MAX_SUBSCRIPTION_PAYMENTS,
+        FeePaymentError::InvalidPaymentCount
+    );
+
+    let clock = Clock::get()?;
+    let fee_per_payment = calculate_gas_fee(amount, &ctx.accounts.state);
+    let fee_credits = match sponsorship {
+        SubscriptionSponsorship::AdPerCycle => 0,
+        SubscriptionSponsorship::PrepaidCredits => fee_per_payment
+            .checked_mul(payment_count as u64)
+            .ok_or(FeePaymentError::MathOverflow)?,
+    };
+    let deposit = amount
+        .checked_mul(payment_count as u64)
+        .and_then(|total| total.checked_add(fee_credits))
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.user.to_account_info(),
+                to: ctx.accounts.subscription.to_account_info(),
+            },
+        ),
+        deposit,
+    )?;
+
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.user = ctx.accounts.user.key();
+    subscription.recipient = recipient;
+    subscription.subscription_id = subscription_id;
+    subscription.amount = amount;
+    subscription.interval = interval;
+    subscription.remaining_payments = payment_count;
+    subscription.next_payment_at = clock.unix_timestamp; // First cycle is due immediately
+    subscription.sponsorship = sponsorship.clone();
+    subscription.fee_per_payment = fee_per_payment;
+    subscription.fee_credits = fee_credits;
+    subscription.cycle_ad_id = String::new();
+    subscription.ad_display_started_at = None;
+    subscription.cycle_ad_viewed = false;
+    subscription.cycle_ad_due_at = 0;
+    subscription.cycle_ad_price = 0;
+    subscription.created_at = clock.unix_timestamp;
+    subscription.bump = ctx.bumps.subscription;
+
+    emit!(SubscriptionCreated {
+        subscription: subscription.key(),
+        user: subscription.user,
+        recipient,
+        amount,
+        interval,
+        payment_count,
+        sponsorship,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn begin_subscription_ad<'info>(
+    ctx: Context<'_, '_, 'info, 'info, BeginSubscriptionAd<'info>>,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_INITIATE)?;
+
+    let subscription = &ctx.accounts.subscription;
+    let clock = Clock::get()?;
+
+    require!(
+        subscription.sponsorship == SubscriptionSponsorship::AdPerCycle,
+        FeePaymentError::InvalidSponsorship
+    );
+    require!(subscription.remaining_payments > 0, FeePaymentError::InvalidStatus);
+    require!(!subscription.cycle_ad_viewed, FeePaymentError::InvalidStatus);
+
+    // Each view sponsors the payment that is due now, not a future one
+    require!(
+        clock.unix_timestamp >= subscription.next_payment_at,
+        FeePaymentError::PaymentNotDue
+    );
+
+    // A view already begun for this cycle holds its ad's clearing price until
+    // it is confirmed
+    require!(
+        subscription.ad_display_started_at.is_none(),
+        FeePaymentError::InvalidStatus
+    );
+
+    // A campaign's creative is the user's assigned variant
+    check_ad_campaign(&ctx.accounts.selected_ad, ctx.accounts.campaign.as_ref())?;
+    if let Some(campaign) = ctx.accounts.campaign.as_ref() {
+        require!(
+            assigned_variant(campaign, &ctx.accounts.user.key())? == ctx.accounts.selected_ad.key(),
+            FeePaymentError::VariantMismatch
+        );
+    }
+
+    // Every registered ad, passed as remaining accounts, bids for this placement
+    let audience = AdAudience {
+        user: ctx.accounts.user.key(),
+        preferences: ctx.accounts.preferences.as_deref(),
+        recipients: std::slice::from_ref(&subscription.recipient),
+        amount: subscription.amount,
+        allowed_ads: &[],
+        impressions: &ctx.accounts.impressions,
+    };
+    let placement = run_ad_auction(
+        &ctx.accounts.state,
+        &ctx.accounts.ad_registry,
+        &mut ctx.accounts.selected_ad,
+        ctx.remaining_accounts,
+        &audience,
+    )?;
+    let ad = &placement.ad;
+    record_impression(
+        &mut ctx.accounts.impressions,
+        ctx.accounts.user.key(),
+        ctx.bumps.impressions,
+        &placement,
+        clock.unix_timestamp,
+    )?;
+
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.cycle_ad_id = ad.id.clone();
+    subscription.cycle_ad_price = placement.price;
+    subscription.ad_display_started_at = Some(clock.unix_timestamp);
+    subscription.cycle_ad_due_at = subscription.next_payment_at;
+
+    emit!(AdRetrieved {
+        ad_id: ad.id.clone(),
+        ad_content: ad.content.clone(),
+        ad_url: ad.url.clone(),
+        display_duration: ad.display_duration,
+        reward_amount: ad.reward_amount,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn confirm_subscription_ad(
+    ctx: Context<ConfirmSubscriptionAd>,
+    view_duration: i64,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_COMPLETE)?;
+
+    let subscription = &mut ctx.accounts.subscription;
+    let ad = &mut ctx.accounts.ad;
+    let clock = Clock::get()?;
+
+    require!(!subscription.cycle_ad_viewed, FeePaymentError::InvalidStatus);
+    require!(
+        subscription.cycle_ad_due_at == subscription.next_payment_at,
+        FeePaymentError::AdNotStarted
+    );
+
+    check_ad_campaign(ad, ctx.accounts.campaign.as_ref())?;
+
+    let ad_started_at = subscription.ad_display_started_at.ok_or(FeePaymentError::AdNotStarted)?;
+    let actual_view_time = clock.unix_timestamp - ad_started_at;
+    require!(
+        view_duration >= ad.display_duration && actual_view_time >= ad.display_duration,
+        FeePaymentError::InsufficientViewTime
+    );
+
+    subscription.cycle_ad_viewed = true;
+
+    // Charge the cycle's ad the clearing price reserved when its view began.
+    // Cost-per-click ads are only charged if the viewer clicks through.
+    let state = &mut ctx.accounts.state;
+    let ad_price = subscription.cycle_ad_price;
+    if ad_price > 0 {
+        ad.reserved_budget = ad.reserved_budget
+            .checked_sub(ad_price)
+            .ok_or(FeePaymentError::MathUnderflow)?;
+        if ad.billing_model == BillingModel::PerView {
+            charge_ad(ad, &ctx.accounts.treasury, state, ad_price, None)?;
+        }
+        subscription.cycle_ad_price = 0;
+    }
+
+    // Receipt proving this user saw the ad, for click tracking
+    let view_receipt = &mut ctx.accounts.view_receipt;
+    if view_receipt.user == Pubkey::default() {
+        view_receipt.user = subscription.user;
+        view_receipt.ad = ad.key();
+        view_receipt.bump = ctx.bumps.view_receipt;
+    }
+    record_view(view_receipt, ad, ad_price, None, clock.unix_timestamp)?;
+
+    ad.view_count = ad.view_count
+        .checked_add(1)
+        .ok_or(FeePaymentError::MathOverflow)?;
+    if let Some(campaign) = ctx.accounts.campaign.as_mut() {
+        let variant = campaign_variant(campaign, &ad.key())?;
+        variant.views = variant.views
+            .checked_add(1)
+            .ok_or(FeePaymentError::MathOverflow)?;
+    }
+
+    state.total_ads_viewed = state.total_ads_viewed
+        .checked_add(1)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    emit!(SubscriptionAdViewed {
+        subscription: subscription.key(),
+        user: subscription.user,
+        ad_id: ad.id.clone(),
+        view_duration,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn execute_subscription_payment(ctx: Context<ExecuteSubscriptionPayment>) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_COMPLETE)?;
+
+    let clock = Clock::get()?;
+    let subscription = &mut ctx.accounts.subscription;
+
+    require!(subscription.remaining_payments > 0, FeePaymentError::InvalidStatus);
+    require!(
+        clock.unix_timestamp >= subscription.next_payment_at,
+        FeePaymentError::PaymentNotDue
+    );
+
+    let amount = subscription.amount;
+
+    // The crank fee only reimburses a third-party executor's transaction fee;
+    // subscribers executing their own payments aren't paid for it
+    let gas_fee = if ctx.accounts.executor.key() == subscription.user {
+        0
+    } else {
+        subscription.fee_per_payment.min(MAX_CRANK_FEE)
+    };
+
+    // Ad-sponsored payments need a view made for this cycle
+    require!(
+        subscription.sponsorship != SubscriptionSponsorship::AdPerCycle
+            || (subscription.cycle_ad_viewed
+                && subscription.cycle_ad_due_at == subscription.next_payment_at),
+        FeePaymentError::AdNotViewed
+    );
+
+    // A payout the circuit breaker refuses leaves the cycle due
+    if subscription.sponsorship == SubscriptionSponsorship::AdPerCycle
+        && !admit_treasury_outflow(&mut ctx.accounts.state, gas_fee, clock.slot)?
+    {
+        return Ok(());
+    }
+
+    // Transfer 1: Subscription escrow → Recipient
+    subscription.sub_lamports(amount)?;
+    ctx.accounts.recipient.add_lamports(amount)?;
+
+    // Transfer 2: Gas fee to the executor, from the ad-sponsored treasury or prepaid credits
+    match subscription.sponsorship {
+        SubscriptionSponsorship::AdPerCycle => {
+            require!(
+                available_funds(&ctx.accounts.state) >= gas_fee,
+                FeePaymentError::InsufficientProgramFunds
+            );
+
+            let treasury_signer_seeds = &[b"treasury".as_ref(), &[ctx.accounts.state.treasury_bump]];
+
+            if gas_fee > 0 {
+                transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.system_program.to_account_info(),
+                        Transfer {
+                            from: ctx.accounts.treasury.to_account_info(),
+                            to: ctx.accounts.executor.to_account_info(),
+                        },
+                        &[treasury_signer_seeds],
+                    ),
+                    gas_fee,
+                )?;
+            }
+
+            let state = &mut ctx.accounts.state;
+            state.total_funds = state.total_funds
+                .checked_sub(gas_fee)
+                .ok_or(FeePaymentError::MathUnderflow)?;
+            if gas_fee > 0 {
+                attribute_sponsorship(state)?;
+            }
+        }
+        SubscriptionSponsorship::PrepaidCredits => {
+            subscription.fee_credits = subscription.fee_credits
+                .checked_sub(gas_fee)
+                .ok_or(FeePaymentError::MathUnderflow)?;
+            subscription.sub_lamports(gas_fee)?;
+            ctx.accounts.executor.add_lamports(gas_fee)?;
+        }
+    }
+
+    let state = &mut ctx.accounts.state;
+    state.total_transactions = state.total_transactions
+        .checked_add(1)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    subscription.remaining_payments -= 1;
+    subscription.next_payment_at = subscription.next_payment_at
+        .checked_add(subscription.interval)
+        .ok_or(FeePaymentError::MathOverflow)?;
+    subscription.cycle_ad_viewed = false;
+    subscription.ad_display_started_at = None;
+
+    emit!(SubscriptionPaymentExecuted {
+        subscription: subscription.key(),
+        user: subscription.user,
+        recipient: subscription.recipient,
+        amount,
+        gas_fee_sponsored: gas_fee,
+        remaining_payments: subscription.remaining_payments,
+        executor: ctx.accounts.executor.key(),
+        timestamp: clock.unix_timestamp,
+    });
+
+    // Final cycle: return rent to the user
+    if subscription.remaining_payments == 0 {
+        subscription.close(ctx.accounts.user.to_account_info())?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn cancel_subscription(ctx: Context<CancelSubscription>) -> Result<()> {
+    let subscription = &ctx.accounts.subscription;
+    release_ad_budget(ctx.accounts.ad.as_mut(), subscription.cycle_ad_price)?;
+
+    emit!(SubscriptionCancelled {
+        subscription: subscription.key(),
+        user: subscription.user,
+        remaining_payments: subscription.remaining_payments,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(subscription_id: u64)]
+pub struct CreateSubscription<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        init,
+        payer = user,
+        space = 8 + 190,
+        seeds = [b"subscription", user.key().as_ref(), &subscription_id.to_le_bytes()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: User's BlockedAddress PDA, validated in check_address_access
+    pub user_blocked: UncheckedAccount<'info>,
+    /// CHECK: User's AllowedAddress PDA, required in allowlist mode
+    pub user_allowed: Option<UncheckedAccount<'info>>,
+    /// CHECK: Recipient's BlockedAddress PDA, validated in check_address_access
+    pub recipient_blocked: UncheckedAccount<'info>,
+    /// CHECK: Recipient's AllowedAddress PDA, required in allowlist mode
+    pub recipient_allowed: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BeginSubscriptionAd<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        has_one = user @ FeePaymentError::Unauthorized
+    )]
+    pub subscription: Account<'info, Subscription>,
+    /// Wins the placement unless a competing ad outbids it
+    #[account(
+        mut,
+        constraint = selected_ad.status == AdStatus::Approved @ FeePaymentError::AdNotActive
+    )]
+    pub selected_ad: Account<'info, Advertisement>,
+    #[account(
+        seeds = [b"ad_registry"],
+        bump = ad_registry.bump
+    )]
+    pub ad_registry: Account<'info, AdRegistry>,
+    /// Per-user impression counts backing advertiser frequency caps
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 709,
+        seeds = [b"impressions", user.key().as_ref()],
+        bump
+    )]
+    pub impressions: Account<'info, UserImpressions>,
+    /// User's declared preferences, matched against ad targeting
+    #[account(
+        seeds = [b"preferences", user.key().as_ref()],
+        bump = preferences.bump
+    )]
+    pub preferences: Option<Account<'info, UserPreferences>>,
+    /// Required when the selected ad is a campaign variant
+    #[account(
+        seeds = [b"campaign", campaign.campaign_id.as_bytes()],
+        bump = campaign.bump,
+        constraint = selected_ad.campaign == Some(campaign.key()) @ FeePaymentError::InvalidCampaignVariant
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ConfirmSubscriptionAd<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Treasury PDA for holding funds
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = state.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+    #[account(
+        mut,
+        has_one = user @ FeePaymentError::Unauthorized
+    )]
+    pub subscription: Account<'info, Subscription>,
+    #[account(
+        mut,
+        constraint = ad.id == subscription.cycle_ad_id @ FeePaymentError::AdMismatch
+    )]
+    pub ad: Account<'info, Advertisement>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 120,
+        seeds = [b"view_receipt", user.key().as_ref(), ad.key().as_ref()],
+        bump
+    )]
+    pub view_receipt: Account<'info, ViewReceipt>,
+    /// Required when the ad is a campaign variant, for per-variant counters
+    #[account(
+        mut,
+        seeds = [b"campaign", campaign.campaign_id.as_bytes()],
+        bump = campaign.bump,
+        constraint = ad.campaign == Some(campaign.key()) @ FeePaymentError::InvalidCampaignVariant
+    )]
+    pub campaign: Option<Account<'info, Campaign>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteSubscriptionPayment<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Treasury PDA for holding funds
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = state.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"subscription",
+            subscription.user.as_ref(),
+            &subscription.subscription_id.to_le_bytes()
+        ],
+        bump = subscription.bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+    /// CHECK: Receives rent back once the final payment is made
+    #[account(
+        mut,
+        address = subscription.user @ FeePaymentError::Unauthorized
+    )]
+    pub user: AccountInfo<'info>,
+    /// CHECK: Recipient validation through constraint
+    #[account(
+        mut,
+        address = subscription.recipient @ FeePaymentError::RecipientMismatch
+    )]
+    pub recipient: AccountInfo<'info>,
+    #[account(mut)]
+    pub executor: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelSubscription<'info> {
+    #[account(
+        mut,
+        has_one = user @ FeePaymentError::Unauthorized,
+        close = user
+    )]
+    pub subscription: Account<'info, Subscription>,
+    /// Required while a cycle's view holds a clearing price on its ad
+    #[account(
+        mut,
+        constraint = ad.id == subscription.cycle_ad_id @ FeePaymentError::AdMismatch
+    )]
+    pub ad: Option<Account<'info, Advertisement>>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
@@ -0,0 +1,586 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::system_program::{allocate, assign, transfer, Allocate, Assign, Transfer};
+use solana_stake_interface::instruction as stake_instruction;
+use solana_stake_interface::state::{Authorized, Lockup, StakeStateV2};
+
+use crate::constants::*;
+use crate::errors::FeePaymentError;
+use crate::events::*;
+use crate::helpers::*;
+use crate::state::*;
+
+pub(crate) fn deposit_funds(ctx: Context<DepositFunds>, amount: u64) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_DEPOSIT)?;
+    require!(amount > 0 && amount <= MAX_SINGLE_DEPOSIT, FeePaymentError::InvalidAmount);
+
+    // Transfer to treasury PDA instead of state
+    transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.admin.to_account_info(),
+                to: ctx.accounts.treasury.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+
+    let state = &mut ctx.accounts.state;
+    state.total_funds = state.total_funds
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+    state.total_deposited = state.total_deposited
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    emit!(FundsDeposited {
+        admin: ctx.accounts.admin.key(),
+        amount,
+        total_funds: state.total_funds,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn donate(ctx: Context<Donate>, amount: u64) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_DEPOSIT)?;
+    require!(amount > 0 && amount <= MAX_SINGLE_DEPOSIT, FeePaymentError::InvalidAmount);
+
+    transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.donor.to_account_info(),
+                to: ctx.accounts.treasury.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+
+    let state = &mut ctx.accounts.state;
+    state.total_funds = state.total_funds
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    let contribution = &mut ctx.accounts.contribution;
+    let clock = Clock::get()?;
+
+    // First donation from this wallet
+    if contribution.donor == Pubkey::default() {
+        contribution.donor = ctx.accounts.donor.key();
+        contribution.first_contributed_at = clock.unix_timestamp;
+        contribution.bump = ctx.bumps.contribution;
+        contribution.sponsorship_checkpoint = state.sponsorship_index;
+    }
+    // Earlier payments are attributed at the previous contribution
+    settle_sponsorship(contribution, state)?;
+    state.total_deposited = state.total_deposited
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+    contribution.total_contributed = contribution.total_contributed
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+    contribution.last_contributed_at = clock.unix_timestamp;
+
+    emit!(SponsorDonation {
+        donor: contribution.donor,
+        amount,
+        total_contributed: contribution.total_contributed,
+        total_funds: state.total_funds,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn settle_contribution(ctx: Context<SettleContribution>) -> Result<()> {
+    let contribution = &mut ctx.accounts.contribution;
+    settle_sponsorship(contribution, &ctx.accounts.state)?;
+
+    emit!(SponsorshipAttributed {
+        donor: contribution.donor,
+        total_contributed: contribution.total_contributed,
+        sponsored_count: contribution.sponsored_count,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn withdraw_funds(ctx: Context<WithdrawFunds>, amount: u64) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_WITHDRAW)?;
+    require!(amount > 0, FeePaymentError::InvalidAmount);
+    
+    let treasury_bump = ctx.accounts.state.treasury_bump;
+    
+    // Funds reserved for pending requests can't be withdrawn
+    require!(
+        available_funds(&ctx.accounts.state) >= amount,
+        FeePaymentError::InsufficientProgramFunds
+    );
+
+    let treasury_signer_seeds = &[b"treasury".as_ref(), &[treasury_bump]];
+    
+    transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.treasury.to_account_info(),
+                to: ctx.accounts.admin.to_account_info(),
+            },
+            &[treasury_signer_seeds],
+        ),
+        amount,
+    )?;
+
+    let state = &mut ctx.accounts.state;
+    state.total_funds = state.total_funds
+        .checked_sub(amount)
+        .ok_or(FeePaymentError::MathUnderflow)?;
+
+    emit!(FundsWithdrawn {
+        amount,
+        admin: ctx.accounts.admin.key(),
+        remaining: state.total_funds,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn create_treasury_stake(
+    ctx: Context<CreateTreasuryStake>,
+    stake_index: u64,
+    amount: u64,
+) -> Result<()> {
+    require_not_paused(&ctx.accounts.state, PAUSE_WITHDRAW)?;
+
+    let stake_space = StakeStateV2::size_of();
+    require!(
+        amount > ctx.accounts.rent.minimum_balance(stake_space),
+        FeePaymentError::InvalidAmount
+    );
+    require!(
+        available_funds(&ctx.accounts.state)
+            >= amount
+                .checked_add(ctx.accounts.state.min_liquid_buffer)
+                .ok_or(FeePaymentError::MathOverflow)?,
+        FeePaymentError::LiquidBufferTooLow
+    );
+
+    let treasury_key = ctx.accounts.treasury.key();
+    let treasury_signer_seeds = &[b"treasury".as_ref(), &[ctx.accounts.state.treasury_bump]];
+    let stake_index_bytes = stake_index.to_le_bytes();
+    let stake_signer_seeds = &[
+        b"treasury_stake".as_ref(),
+        stake_index_bytes.as_ref(),
+        &[ctx.bumps.stake_account],
+    ];
+
+    // Fund, allocate and assign separately rather than `create_account`, which
+    // fails on an address someone has already sent lamports to. Those extra
+    // lamports simply end up staked too.
+    transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.treasury.to_account_info(),
+                to: ctx.accounts.stake_account.to_account_info(),
+            },
+            &[treasury_signer_seeds],
+        ),
+        amount,
+    )?;
+    allocate(
+        CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            Allocate {
+                account_to_allocate: ctx.accounts.stake_account.to_account_info(),
+            },
+            &[stake_signer_seeds],
+        ),
+        stake_space as u64,
+    )?;
+    assign(
+        CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            Assign {
+                account_to_assign: ctx.accounts.stake_account.to_account_info(),
+            },
+            &[stake_signer_seeds],
+        ),
+        &solana_stake_interface::program::ID,
+    )?;
+
+    invoke_signed(
+        &stake_instruction::initialize(
+            &ctx.accounts.stake_account.key(),
+            &Authorized {
+                staker: treasury_key,
+                withdrawer: treasury_key,
+            },
+            &Lockup::default(),
+        ),
+        &[
+            ctx.accounts.stake_account.to_account_info(),
+            ctx.accounts.rent.to_account_info(),
+        ],
+        &[],
+    )?;
+
+    // A fully withdrawn index can be staked again, starting a fresh record
+    let stake_record = &mut ctx.accounts.stake_record;
+    stake_record.stake_account = ctx.accounts.stake_account.key();
+    stake_record.principal = amount;
+    stake_record.bump = ctx.bumps.stake_record;
+
+    let state = &mut ctx.accounts.state;
+    state.total_funds = state.total_funds
+        .checked_sub(amount)
+        .ok_or(FeePaymentError::MathUnderflow)?;
+    state.staked_funds = state.staked_funds
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    emit!(TreasuryStakeCreated {
+        stake_account: ctx.accounts.stake_account.key(),
+        amount,
+        remaining_liquid: state.total_funds,
+    });
+
+    Ok(())
+}
+
+pub(crate) fn delegate_treasury_stake(ctx: Context<DelegateTreasuryStake>, _stake_index: u64) -> Result<()> {
+    let treasury_signer_seeds = &[b"treasury".as_ref(), &[ctx.accounts.state.treasury_bump]];
+
+    invoke_signed(
+        &stake_instruction::delegate_stake(
+            &ctx.accounts.stake_account.key(),
+            &ctx.accounts.treasury.key(),
+            &ctx.accounts.vote_account.key(),
+        ),
+        &[
+            ctx.accounts.stake_account.to_account_info(),
+            ctx.accounts.vote_account.to_account_info(),
+            ctx.accounts.clock.to_account_info(),
+            ctx.accounts.stake_history.to_account_info(),
+            ctx.accounts.stake_config.to_account_info(),
+            ctx.accounts.treasury.to_account_info(),
+        ],
+        &[treasury_signer_seeds],
+    )?;
+
+    emit!(TreasuryStakeDelegated {
+        stake_account: ctx.accounts.stake_account.key(),
+        vote_account: ctx.accounts.vote_account.key(),
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn deactivate_treasury_stake(ctx: Context<DeactivateTreasuryStake>, _stake_index: u64) -> Result<()> {
+    let treasury_signer_seeds = &[b"treasury".as_ref(), &[ctx.accounts.state.treasury_bump]];
+
+    invoke_signed(
+        &stake_instruction::deactivate_stake(
+            &ctx.accounts.stake_account.key(),
+            &ctx.accounts.treasury.key(),
+        ),
+        &[
+            ctx.accounts.stake_account.to_account_info(),
+            ctx.accounts.clock.to_account_info(),
+            ctx.accounts.treasury.to_account_info(),
+        ],
+        &[treasury_signer_seeds],
+    )?;
+
+    emit!(TreasuryStakeDeactivated {
+        stake_account: ctx.accounts.stake_account.key(),
+        admin: ctx.accounts.admin.key(),
+    });
+
+    Ok(())
+}
+
+pub(crate) fn withdraw_treasury_stake(
+    ctx: Context<WithdrawTreasuryStake>,
+    _stake_index: u64,
+    amount: u64,
+) -> Result<()> {
+    require!(amount > 0, FeePaymentError::InvalidAmount);
+
+    let treasury_signer_seeds = &[b"treasury".as_ref(), &[ctx.accounts.state.treasury_bump]];
+
+    invoke_signed(
+        &stake_instruction::withdraw(
+            &ctx.accounts.stake_account.key(),
+            &ctx.accounts.treasury.key(),
+            &ctx.accounts.treasury.key(),
+            amount,
+            None,
+        ),
+        &[
+            ctx.accounts.stake_account.to_account_info(),
+            ctx.accounts.treasury.to_account_info(),
+            ctx.accounts.clock.to_account_info(),
+            ctx.accounts.stake_history.to_account_info(),
+            ctx.accounts.treasury.to_account_info(),
+        ],
+        &[treasury_signer_seeds],
+    )?;
+
+    // Anything above this account's principal is rewards
+    let stake_record = &mut ctx.accounts.stake_record;
+    let principal = amount.min(stake_record.principal);
+    let rewards = amount - principal;
+    stake_record.principal -= principal;
+
+    let state = &mut ctx.accounts.state;
+    state.staked_funds = state.staked_funds
+        .checked_sub(principal)
+        .ok_or(FeePaymentError::MathUnderflow)?;
+    state.total_funds = state.total_funds
+        .checked_add(amount)
+        .ok_or(FeePaymentError::MathOverflow)?;
+
+    emit!(TreasuryStakeWithdrawn {
+        stake_account: ctx.accounts.stake_account.key(),
+        amount,
+        rewards,
+        total_funds: state.total_funds,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct DepositFunds<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Treasury PDA for holding funds
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = state.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Donate<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Treasury PDA for holding funds
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = state.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+    #[account(
+        init_if_needed,
+        payer = donor,
+        space = 8 + 97,
+        seeds = [b"contribution", donor.key().as_ref()],
+        bump
+    )]
+    pub contribution: Account<'info, SponsorContribution>,
+    #[account(mut)]
+    pub donor: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettleContribution<'info> {
+    #[account(seeds = [b"state"], bump = state.bump)]
+    pub state: Account<'info, ProgramState>,
+    #[account(
+        mut,
+        seeds = [b"contribution", contribution.donor.as_ref()],
+        bump = contribution.bump
+    )]
+    pub contribution: Account<'info, SponsorContribution>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFunds<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Treasury PDA for holding funds
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = state.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(stake_index: u64)]
+pub struct CreateTreasuryStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Treasury PDA for holding funds, staker and withdrawer of the stake account
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = state.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+    /// CHECK: Program-derived stake account, validated by the stake program
+    #[account(
+        mut,
+        seeds = [b"treasury_stake", &stake_index.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: AccountInfo<'info>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + 41,
+        seeds = [b"stake_record".as_ref(), &stake_index.to_le_bytes()],
+        bump
+    )]
+    pub stake_record: Account<'info, TreasuryStake>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub rent: Sysvar<'info, Rent>,
+    /// CHECK: Native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(stake_index: u64)]
+pub struct DelegateTreasuryStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Treasury PDA for holding funds, staker and withdrawer of the stake account
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = state.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+    /// CHECK: Program-derived stake account, validated by the stake program
+    #[account(
+        mut,
+        seeds = [b"treasury_stake", &stake_index.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: AccountInfo<'info>,
+    /// CHECK: Validator vote account, validated by the stake program
+    pub vote_account: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    /// CHECK: Stake history sysvar
+    #[account(address = solana_stake_interface::stake_history::ID)]
+    pub stake_history: AccountInfo<'info>,
+    /// CHECK: Legacy stake config account, validated by the stake program
+    pub stake_config: AccountInfo<'info>,
+    pub admin: Signer<'info>,
+    /// CHECK: Native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(stake_index: u64)]
+pub struct DeactivateTreasuryStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Treasury PDA for holding funds, staker and withdrawer of the stake account
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = state.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+    /// CHECK: Program-derived stake account, validated by the stake program
+    #[account(
+        mut,
+        seeds = [b"treasury_stake", &stake_index.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: AccountInfo<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub admin: Signer<'info>,
+    /// CHECK: Native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(stake_index: u64)]
+pub struct WithdrawTreasuryStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump = state.bump,
+        has_one = admin @ FeePaymentError::Unauthorized
+    )]
+    pub state: Account<'info, ProgramState>,
+    /// CHECK: Treasury PDA for holding funds, staker and withdrawer of the stake account
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = state.treasury_bump
+    )]
+    pub treasury: AccountInfo<'info>,
+    /// CHECK: Program-derived stake account, validated by the stake program
+    #[account(
+        mut,
+        seeds = [b"treasury_stake", &stake_index.to_le_bytes()],
+        bump
+    )]
+    pub stake_account: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"stake_record".as_ref(), &stake_index.to_le_bytes()],
+        bump = stake_record.bump
+    )]
+    pub stake_record: Account<'info, TreasuryStake>,
+    pub clock: Sysvar<'info, Clock>,
+    /// CHECK: Stake history sysvar
+    #[account(address = solana_stake_interface::stake_history::ID)]
+    pub stake_history: AccountInfo<'info>,
+    pub admin: Signer<'info>,
+    /// CHECK: Native stake program
+    #[account(address = solana_stake_interface::program::ID)]
+    pub stake_program: AccountInfo<'info>,
+}
@@ -1,18 +1,19 @@
 use anchor_lang::prelude::*;
-use anchor_lang::system_program::{transfer, Transfer};
 
-declare_id!("HRtVXSRabAJ8Mk2NfEFPhquhcgphYZJWnLBwbKxto2Xq");
+pub mod constants;
+pub mod errors;
+pub mod events;
+mod helpers;
+pub mod instructions;
+pub mod state;
+
+pub use constants::*;
+pub use errors::*;
+pub use events::*;
+pub use instructions::*;
+pub use state::*;
 
-// Program constants
-const MAX_AD_ID_LENGTH: usize = 32;
-const MAX_AD_URL_LENGTH: usize = 200;
-const MAX_AD_CONTENT_LENGTH: usize = 500;
-const DEFAULT_FEE_PER_AD: u64 = 5_000; // 0.005 SOL
-const MIN_AD_REWARD: u64 = 1_000; // 0.001 SOL
-const TRANSACTION_TIMEOUT: i64 = 300; // 5 minutes
-const MAX_SINGLE_DEPOSIT: u64 = 10_000_000_000; // 10 SOL
-const BASE_TRANSACTION_FEE: u64 = 5_000; // Base fee in lamports (0.005 SOL)
-const MIN_AD_VIEW_TIME: i64 = 5; // Minimum 5 seconds to view ad
+declare_id!("HRtVXSRabAJ8Mk2NfEFPhquhcgphYZJWnLBwbKxto2Xq");
 
 #[program]
 pub mod fee_payment_dapp {
@@ -20,58 +21,27 @@ pub mod fee_payment_dapp {
 
     /// Initialize program with deployer as admin
     pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
-        let state = &mut ctx.accounts.state;
-        
-        state.admin = ctx.accounts.deployer.key();
-        state.total_funds = 0;
-        state.total_ads_viewed = 0;
-        state.total_transactions = 0;
-        state.fee_per_ad = DEFAULT_FEE_PER_AD;
-        state.base_transaction_fee = BASE_TRANSACTION_FEE;
-        state.is_paused = false;
-        state.bump = ctx.bumps.state;
-        state.treasury_bump = ctx.bumps.treasury;
-
-        emit!(ProgramInitialized {
-            admin: state.admin,
-            timestamp: Clock::get()?.unix_timestamp,
-        });
-
-        Ok(())
+        instructions::admin::initialize(ctx)
     }
 
     /// Admin deposits funds into the program for gas fee sponsorship
     pub fn deposit_funds(ctx: Context<DepositFunds>, amount: u64) -> Result<()> {
-        require!(!ctx.accounts.state.is_paused, FeePaymentError::ProgramPaused);
-        require!(amount > 0 && amount <= MAX_SINGLE_DEPOSIT, FeePaymentError::InvalidAmount);
-
-        // Transfer to treasury PDA instead of state
-        transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.admin.to_account_info(),
-                    to: ctx.accounts.treasury.to_account_info(),
-                },
-            ),
-            amount,
-        )?;
-
-        let state = &mut ctx.accounts.state;
-        state.total_funds = state.total_funds
-            .checked_add(amount)
-            .ok_or(FeePaymentError::MathOverflow)?;
-
-        emit!(FundsDeposited {
-            admin: ctx.accounts.admin.key(),
-            amount,
-            total_funds: state.total_funds,
-        });
-
-        Ok(())
-    }
-
-    /// Create a new advertisement with content for popup display
+        instructions::treasury::deposit_funds(ctx, amount)
+    }
+
+    /// Anyone can co-fund gasless payments by donating to the treasury
+    pub fn donate(ctx: Context<Donate>, amount: u64) -> Result<()> {
+        instructions::treasury::donate(ctx, amount)
+    }
+
+    /// Bring a donor's attributed sponsored payments up to date. Anyone may
+    /// settle, since the count only depends on program state
+    pub fn settle_contribution(ctx: Context<SettleContribution>) -> Result<()> {
+        instructions::treasury::settle_contribution(ctx)
+    }
+
+    /// Create a new advertisement with content for popup display. Anyone may
+    /// submit an ad; only the admin's go live without moderation.
     pub fn create_ad(
         ctx: Context<CreateAd>,
         ad_id: String,
@@ -80,699 +50,554 @@ pub mod fee_payment_dapp {
         reward_amount: u64,
         display_duration: i64,
     ) -> Result<()> {
-        require!(!ctx.accounts.state.is_paused, FeePaymentError::ProgramPaused);
-        require!(
-            !ad_id.is_empty() && ad_id.len() <= MAX_AD_ID_LENGTH,
-            FeePaymentError::InvalidAdId
-        );
-        require!(
-            ad_url.starts_with("https://") && ad_url.len() <= MAX_AD_URL_LENGTH,
-            FeePaymentError::InvalidAdUrl
-        );
-        require!(
-            !ad_content.is_empty() && ad_content.len() <= MAX_AD_CONTENT_LENGTH,
-            FeePaymentError::InvalidAdContent
-        );
-        require!(reward_amount >= MIN_AD_REWARD, FeePaymentError::RewardTooLow);
-        require!(display_duration >= MIN_AD_VIEW_TIME, FeePaymentError::InvalidDisplayTime);
-
-        let ad = &mut ctx.accounts.ad;
-        let clock = Clock::get()?;
-
-        ad.id = ad_id.clone();
-        ad.url = ad_url.clone();
-        ad.content = ad_content.clone();
-        ad.reward_amount = reward_amount;
-        ad.display_duration = display_duration;
-        ad.is_active = true;
-        ad.view_count = 0;
-        ad.created_at = clock.unix_timestamp;
-        ad.bump = ctx.bumps.ad;
-
-        emit!(AdCreated {
-            ad_id,
-            reward_amount,
-            display_duration,
-            creator: ctx.accounts.admin.key(),
-            timestamp: clock.unix_timestamp,
-        });
-
-        Ok(())
-    }
-    
-    /// Toggle advertisement status
+        instructions::ads::create_ad(ctx, ad_id, ad_url, ad_content, reward_amount, display_duration)
+    }
+
+    /// Create an advertisement whose creative is stored off-chain (Arweave,
+    /// IPFS or HTTPS). Frontends fetch it from `content_uri` and check it
+    /// against the SHA-256 `content_hash`.
+    pub fn create_reference_ad(
+        ctx: Context<CreateReferenceAd>,
+        ad_id: String,
+        content_uri: String,
+        content_hash: [u8; 32],
+        mime_type: String,
+        reward_amount: u64,
+        display_duration: i64,
+    ) -> Result<()> {
+        instructions::ads::create_reference_ad(ctx, ad_id, content_uri, content_hash, mime_type, reward_amount, display_duration)
+    }
+
+    /// Toggle an approved advertisement between live and paused
     pub fn toggle_ad(ctx: Context<ToggleAd>) -> Result<()> {
-        let ad = &mut ctx.accounts.ad;
-        ad.is_active = !ad.is_active;
+        instructions::ads::toggle_ad(ctx)
+    }
+
+    /// Moderator approves a submitted ad so it can be served, or reinstates
+    /// one suspended by user reports
+    pub fn approve_ad(ctx: Context<ModerateAd>) -> Result<()> {
+        instructions::moderation::approve_ad(ctx)
+    }
+
+    /// Moderator rejects a submitted ad, or takes down a live one
+    pub fn reject_ad(ctx: Context<ModerateAd>, reason_code: u8) -> Result<()> {
+        instructions::moderation::reject_ad(ctx, reason_code)
+    }
+
+    /// User reports an ad shown to them, once per ad, backed by their view
+    /// receipt. Enough reports suspend the ad until a moderator reviews it.
+    pub fn report_ad(ctx: Context<ReportAd>, reason: u8) -> Result<()> {
+        instructions::moderation::report_ad(ctx, reason)
+    }
+
+    /// Viewer clicks through an ad they saw within the click window. Counted
+    /// once per view; cost-per-click ads are charged here if budget allows.
+    pub fn record_click(ctx: Context<RecordClick>) -> Result<()> {
+        instructions::engagement::record_click(ctx)
+    }
+
+    /// Advertiser registers the key allowed to report conversions, and how long
+    /// after a view a conversion is attributed to the ad
+    pub fn set_conversion_authority(
+        ctx: Context<AdvertiserAction>,
+        conversion_authority: Pubkey,
+        attribution_window: i64,
+    ) -> Result<()> {
+        instructions::engagement::set_conversion_authority(ctx, conversion_authority, attribution_window)
+    }
+
+    /// Conversion authority attributes a user's action to their latest view
+    /// of the ad, once per view and within the attribution window
+    pub fn record_conversion(
+        ctx: Context<RecordConversion>,
+        user: Pubkey,
+        ad_id: String,
+    ) -> Result<()> {
+        instructions::engagement::record_conversion(ctx, user, ad_id)
+    }
+
+    /// Advertiser creates a campaign to A/B test several creatives
+    pub fn create_campaign(ctx: Context<CreateCampaign>, campaign_id: String) -> Result<()> {
+        instructions::campaign::create_campaign(ctx, campaign_id)
+    }
+
+    /// Advertiser adds one of its ads to a campaign as a weighted variant
+    pub fn add_campaign_variant(ctx: Context<ManageCampaignVariant>, weight: u16) -> Result<()> {
+        instructions::campaign::add_campaign_variant(ctx, weight)
+    }
+
+    /// Advertiser rebalances traffic; a zero weight stops serving the variant
+    pub fn set_variant_weight(ctx: Context<ManageCampaignVariant>, weight: u16) -> Result<()> {
+        instructions::campaign::set_variant_weight(ctx, weight)
+    }
+
+    /// Toggle campaign status
+    pub fn toggle_campaign(ctx: Context<ToggleCampaign>) -> Result<()> {
+        instructions::campaign::toggle_campaign(ctx)
+    }
+
+    /// Advertiser chooses whether the ad is billed per view or per click
+    pub fn set_ad_billing_model(
+        ctx: Context<AdvertiserAction>,
+        billing_model: BillingModel,
+    ) -> Result<()> {
+        instructions::ads::set_ad_billing_model(ctx, billing_model)
+    }
+
+    /// Advertiser sets when the ad stops being served (0 = no end)
+    pub fn set_ad_end_time(ctx: Context<AdvertiserAction>, ends_at: i64) -> Result<()> {
+        instructions::ads::set_ad_end_time(ctx, ends_at)
+    }
+
+    /// Permissionless: mark an ad whose end time has passed as expired
+    pub fn expire_ad(ctx: Context<ExpireAd>) -> Result<()> {
+        instructions::ads::expire_ad(ctx)
+    }
+
+    /// Admin hands an ad over to the advertiser that funds and bids for it
+    pub fn set_ad_advertiser(ctx: Context<ToggleAd>, advertiser: Pubkey) -> Result<()> {
+        instructions::ads::set_ad_advertiser(ctx, advertiser)
+    }
+
+    /// Advertiser sets its sealed cost-per-view bid for placement auctions.
+    /// Approved ads bidding at least `fee_per_ad` join the auction registry;
+    /// lower bids leave it.
+    pub fn set_ad_bid(ctx: Context<SetAdBid>, bid_per_view: u64) -> Result<()> {
+        instructions::ads::set_ad_bid(ctx, bid_per_view)
+    }
+
+    /// Remove an ad that can no longer win from the auction registry
+    pub fn prune_ad_registry(ctx: Context<PruneAdRegistry>) -> Result<()> {
+        instructions::ads::prune_ad_registry(ctx)
+    }
+
+    /// Advertiser restricts who the ad is served to. Empty lists and zero
+    /// values leave that dimension untargeted.
+    pub fn set_ad_targeting(
+        ctx: Context<AdvertiserAction>,
+        categories: u32,
+        locales: Vec<[u8; 2]>,
+        min_transfer_amount: u64,
+        recipient_programs: Vec<Pubkey>,
+    ) -> Result<()> {
+        instructions::ads::set_ad_targeting(ctx, categories, locales, min_transfer_amount, recipient_programs)
+    }
+
+    /// Advertiser caps how often a single user is shown the ad per window
+    /// (`max_impressions_per_user` 0 removes the cap)
+    pub fn set_ad_frequency_cap(
+        ctx: Context<AdvertiserAction>,
+        max_impressions_per_user: u16,
+        frequency_window: i64,
+    ) -> Result<()> {
+        instructions::ads::set_ad_frequency_cap(ctx, max_impressions_per_user, frequency_window)
+    }
+
+    /// Anyone can top up an ad's campaign budget
+    pub fn fund_ad_budget(ctx: Context<FundAdBudget>, amount: u64) -> Result<()> {
+        instructions::ads::fund_ad_budget(ctx, amount)
+    }
+
+    /// Advertiser withdraws budget not reserved for pending placements
+    pub fn withdraw_ad_budget(ctx: Context<AdvertiserAction>, amount: u64) -> Result<()> {
+        instructions::ads::withdraw_ad_budget(ctx, amount)
+    }
 
-        emit!(AdToggled {
-            ad_id: ad.id.clone(),
-            is_active: ad.is_active,
-        });
+    /// STEP 1: User initiates send transaction - gets available ad for viewing.
+    /// Passing a sponsor pool funds the fee from that pool under its own fee
+    /// schedule. Every registered ad must follow as a remaining account to bid
+    /// for the placement, with its campaign after it if it has one.
+    pub fn initiate_send_transaction<'info>(
+        ctx: Context<'_, '_, 'info, 'info, InitiateSend<'info>>,
+        recipient: Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::send::initiate_send(ctx, recipient, amount, false)
+    }
 
-        Ok(())
+    /// STEP 1 (locked): Like `initiate_send_transaction`, but the amount is moved
+    /// into the request account up front, so the ad is only served for a send
+    /// that can actually complete.
+    pub fn initiate_locked_send<'info>(
+        ctx: Context<'_, '_, 'info, 'info, InitiateSend<'info>>,
+        recipient: Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::send::initiate_send(ctx, recipient, amount, true)
     }
 
-    /// STEP 1: User initiates send transaction - gets available ad for viewing
-    pub fn initiate_send_transaction(
-        ctx: Context<InitiateSend>,
+    /// STEP 1 (escrow): Like `initiate_send_transaction`, but the amount is locked
+    /// in an escrow PDA now and only released to the recipient by the user or
+    /// arbiter after the ad is viewed, or refunded once `refund_after` passes.
+    pub fn initiate_escrow_send<'info>(
+        ctx: Context<'_, '_, 'info, 'info, InitiateEscrowSend<'info>>,
+        escrow_id: u64,
         recipient: Pubkey,
         amount: u64,
+        arbiter: Pubkey,
+        refund_after: i64,
     ) -> Result<()> {
-        require!(!ctx.accounts.state.is_paused, FeePaymentError::ProgramPaused);
-        require!(recipient != Pubkey::default(), FeePaymentError::InvalidRecipient);
-        require!(amount > 0, FeePaymentError::InvalidAmount);
-
-        let state = &ctx.accounts.state;
-        let calculated_fee = calculate_gas_fee(amount, state);
-        
-        require!(
-            state.total_funds >= calculated_fee,
-            FeePaymentError::InsufficientProgramFunds
-        );
-
-        let request = &mut ctx.accounts.request;
-        let ad = &ctx.accounts.selected_ad;
-        let clock = Clock::get()?;
-
-        // Validate ad is active
-        require!(ad.is_active, FeePaymentError::AdNotActive);
-
-        request.user = ctx.accounts.user.key();
-        request.recipient = recipient;
-        request.amount = amount;
-        request.calculated_fee = calculated_fee;
-        request.status = RequestStatus::WaitingForAd;
-        request.selected_ad_id = ad.id.clone();
-        request.ad_display_started_at = Some(clock.unix_timestamp);
-        request.created_at = clock.unix_timestamp;
-        request.expires_at = clock.unix_timestamp + TRANSACTION_TIMEOUT;
-        request.bump = ctx.bumps.request;
-
-        // Emit event with ad content for frontend to display
-        emit!(TransactionInitiated {
-            user: request.user,
-            recipient,
-            amount,
-            calculated_fee,
-            ad_id: ad.id.clone(),
-            ad_content: ad.content.clone(),
-            ad_url: ad.url.clone(),
-            display_duration: ad.display_duration,
-            request_id: request.key(),
-        });
-
-        Ok(())
-    }
-
-    /// STEP 2: Complete transaction - Program sponsors gas fee separately
+        instructions::send::initiate_escrow_send(ctx, escrow_id, recipient, amount, arbiter, refund_after)
+    }
+
+    /// STEP 1 (relayed): A registered relayer initiates on the user's behalf.
+    /// The user only signs a `SendIntent` off-chain; the signature is checked
+    /// through the Ed25519 precompile instruction placed right before this one.
+    /// The amount is locked from the user's profile balance, so completing the
+    /// request doesn't need the user's signature either.
+    pub fn initiate_relayed_send<'info>(
+        ctx: Context<'_, '_, 'info, 'info, InitiateRelayedSend<'info>>,
+        recipient: Pubkey,
+        amount: u64,
+        nonce: u64,
+    ) -> Result<()> {
+        instructions::relayer::initiate_relayed_send(ctx, recipient, amount, nonce)
+    }
+
+    /// User declares the ad categories they opt into and their language
+    pub fn set_user_preferences(
+        ctx: Context<SetUserPreferences>,
+        categories: u32,
+        language: [u8; 2],
+    ) -> Result<()> {
+        instructions::ads::set_user_preferences(ctx, categories, language)
+    }
+
+    /// Create the profile tracking a user's signed intent nonce. Anyone may pay
+    /// for it so relayers can onboard wallets without SOL.
+    pub fn create_user_profile(ctx: Context<CreateUserProfile>) -> Result<()> {
+        instructions::relayer::create_user_profile(ctx)
+    }
+
+    /// User prefunds their profile balance, from which relayed sends lock their
+    /// amount. A relayer may pay the fee for this transaction.
+    pub fn deposit_to_profile(ctx: Context<DepositToProfile>, amount: u64) -> Result<()> {
+        instructions::relayer::deposit_to_profile(ctx, amount)
+    }
+
+    /// User withdraws unlocked funds from their profile balance
+    pub fn withdraw_from_profile(ctx: Context<WithdrawFromProfile>, amount: u64) -> Result<()> {
+        instructions::relayer::withdraw_from_profile(ctx, amount)
+    }
+
+    /// STEP 2: Complete transaction - Program sponsors gas fee separately.
+    /// Relayed requests are completed by their relayer, without the user's
+    /// signature.
     pub fn complete_transaction_after_ad(
         ctx: Context<CompleteTransaction>,
         view_duration: i64,
     ) -> Result<()> {
-        let request = &mut ctx.accounts.request;
-        let ad = &mut ctx.accounts.ad;
-        let clock = Clock::get()?;
-
-        // Validate request state
-        require!(
-            request.status == RequestStatus::WaitingForAd,
-            FeePaymentError::InvalidStatus
-        );
-        require!(
-            clock.unix_timestamp <= request.expires_at,
-            FeePaymentError::RequestExpired
-        );
-        require!(
-            request.selected_ad_id == ad.id,
-            FeePaymentError::AdMismatch
-        );
-
-        // Validate ad viewing time
-        let ad_started_at = request.ad_display_started_at.ok_or(FeePaymentError::AdNotStarted)?;
-        let actual_view_time = clock.unix_timestamp - ad_started_at;
-        require!(
-            view_duration >= ad.display_duration && actual_view_time >= ad.display_duration,
-            FeePaymentError::InsufficientViewTime
-        );
-
-        // Get values before mutable borrowing
-        let user_amount = request.amount;
-        let gas_fee = request.calculated_fee;
-        let treasury_bump = ctx.accounts.state.treasury_bump;
-        
-        // Validate sufficient program funds for gas fee sponsorship
-        require!(
-            ctx.accounts.state.total_funds >= gas_fee,
-            FeePaymentError::InsufficientProgramFunds
-        );
-        
-        msg!("Executing gas fee sponsorship transaction...");
-        msg!("User sends: {} lamports", user_amount);
-        msg!("Recipient receives: {} lamports (exact same amount)", user_amount);
-        msg!("Program sponsors gas fee: {} lamports", gas_fee);
-        
-        // Transfer 1: User → Recipient (exact amount, no gas fee added)
-        transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user.to_account_info(),
-                    to: ctx.accounts.recipient.to_account_info(),
-                },
-            ),
-            user_amount,
-        )?;
-
-        // Transfer 2: Treasury → Fee account (gas fee sponsorship)
-        let treasury_signer_seeds = &[b"treasury".as_ref(), &[treasury_bump]];
-        
-        transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.system_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.treasury.to_account_info(),
-                    to: ctx.accounts.fee_account.to_account_info(),
-                },
-                &[treasury_signer_seeds],
-            ),
-            gas_fee,
-        )?;
-
-        // Update program state
-        let state = &mut ctx.accounts.state;
-        state.total_funds = state.total_funds
-            .checked_sub(gas_fee)
-            .ok_or(FeePaymentError::MathUnderflow)?;
-        
-        // Update counters
-        ad.view_count = ad.view_count
-            .checked_add(1)
-            .ok_or(FeePaymentError::MathOverflow)?;
-        
-        state.total_ads_viewed = state.total_ads_viewed
-            .checked_add(1)
-            .ok_or(FeePaymentError::MathOverflow)?;
-        
-        state.total_transactions = state.total_transactions
-            .checked_add(1)
-            .ok_or(FeePaymentError::MathOverflow)?;
-
-        // Mark request as completed
-        request.status = RequestStatus::Completed;
-        request.completed_at = Some(clock.unix_timestamp);
-        request.ad_view_duration = Some(view_duration);
-
-        emit!(TransactionCompleted {
-            user: request.user,
-            recipient: request.recipient,
-            amount_sent: user_amount,
-            amount_received: user_amount,
-            gas_fee_sponsored: gas_fee,
-            ad_id: ad.id.clone(),
-            view_duration,
-            timestamp: clock.unix_timestamp,
-        });
-
-        msg!("✅ Transaction completed with gas fee sponsorship!");
-
-        Ok(())
+        instructions::send::complete_transaction_after_ad(ctx, view_duration)
     }
 
-    /// Get a random active ad for popup display
-    pub fn get_random_ad(ctx: Context<GetRandomAd>) -> Result<()> {
-        let ad = &ctx.accounts.ad;
-        require!(ad.is_active, FeePaymentError::AdNotActive);
+    /// STEP 1 (batch): User initiates a payout to several recipients behind a single ad view.
+    /// Registered ads, passed after the recipients' access entries, bid for the placement.
+    pub fn initiate_batch_send<'info>(
+        ctx: Context<'_, '_, 'info, 'info, InitiateBatchSend<'info>>,
+        payouts: Vec<BatchPayout>,
+    ) -> Result<()> {
+        instructions::batch::initiate_batch_send(ctx, payouts)
+    }
 
-        emit!(AdRetrieved {
-            ad_id: ad.id.clone(),
-            ad_content: ad.content.clone(),
-            ad_url: ad.url.clone(),
-            display_duration: ad.display_duration,
-            reward_amount: ad.reward_amount,
-        });
+    /// STEP 2 (batch): Pay every recipient after the ad view. Recipients are passed
+    /// as writable remaining accounts in the same order as the stored payouts.
+    pub fn complete_batch_after_ad<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CompleteBatch<'info>>,
+        view_duration: i64,
+    ) -> Result<()> {
+        instructions::batch::complete_batch_after_ad(ctx, view_duration)
+    }
 
-        Ok(())
+    /// Cancel a pending batch request
+    pub fn cancel_batch_request(ctx: Context<CancelBatchRequest>) -> Result<()> {
+        instructions::batch::cancel_batch_request(ctx)
     }
 
-    /// Cancel a pending request
-    pub fn cancel_request(ctx: Context<CancelRequest>) -> Result<()> {
-        let request = &mut ctx.accounts.request;
-        
-        require!(
-            request.status == RequestStatus::WaitingForAd,
-            FeePaymentError::InvalidStatus
-        );
+    /// Permissionless cleanup of an expired batch request
+    pub fn expire_batch_request(ctx: Context<ExpireBatchRequest>) -> Result<()> {
+        instructions::batch::expire_batch_request(ctx)
+    }
 
-        request.status = RequestStatus::Cancelled;
-        request.cancelled_at = Some(Clock::get()?.unix_timestamp);
+    /// Create a recurring payment. The user pre-funds every cycle into the
+    /// subscription PDA, plus the gas fees when paying with prepaid credits.
+    pub fn create_subscription(
+        ctx: Context<CreateSubscription>,
+        subscription_id: u64,
+        recipient: Pubkey,
+        amount: u64,
+        interval: i64,
+        payment_count: u32,
+        sponsorship: SubscriptionSponsorship,
+    ) -> Result<()> {
+        instructions::subscription::create_subscription(ctx, subscription_id, recipient, amount, interval, payment_count, sponsorship)
+    }
 
-        emit!(RequestCancelled {
-            user: request.user,
-        });
+    /// Start the ad view that sponsors the subscription payment now due.
+    /// Registered ads, passed as remaining accounts, bid for the placement.
+    pub fn begin_subscription_ad<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BeginSubscriptionAd<'info>>,
+    ) -> Result<()> {
+        instructions::subscription::begin_subscription_ad(ctx)
+    }
 
-        Ok(())
+    /// Confirm the cycle's ad was viewed so the crank may execute the payment
+    pub fn confirm_subscription_ad(
+        ctx: Context<ConfirmSubscriptionAd>,
+        view_duration: i64,
+    ) -> Result<()> {
+        instructions::subscription::confirm_subscription_ad(ctx, view_duration)
     }
 
-    /// Admin function to update base transaction fee
-    pub fn update_base_fee(ctx: Context<AdminAction>, new_base_fee: u64) -> Result<()> {
-        require!(new_base_fee > 0, FeePaymentError::InvalidAmount);
-        
-        let state = &mut ctx.accounts.state;
-        let old_fee = state.base_transaction_fee;
-        state.base_transaction_fee = new_base_fee;
-        emit!(BaseFeeUpdated {
-            old_fee,
-            new_fee: new_base_fee,
-            admin: ctx.accounts.admin.key(),
-        });
+    /// Permissionless crank: execute a due subscription payment. An executor
+    /// other than the subscriber is reimbursed up to `MAX_CRANK_FEE`.
+    pub fn execute_subscription_payment(ctx: Context<ExecuteSubscriptionPayment>) -> Result<()> {
+        instructions::subscription::execute_subscription_payment(ctx)
+    }
 
-        Ok(())
+    /// Cancel a subscription, refunding all unspent prepaid funds
+    pub fn cancel_subscription(ctx: Context<CancelSubscription>) -> Result<()> {
+        instructions::subscription::cancel_subscription(ctx)
     }
 
-    /// Admin functions
-    pub fn toggle_pause(ctx: Context<AdminAction>) -> Result<()> {
-        let state = &mut ctx.accounts.state;
-        state.is_paused = !state.is_paused;
+    /// Admin creates a named sponsor pool managed by a partner authority
+    pub fn create_sponsor_pool(
+        ctx: Context<CreateSponsorPool>,
+        pool_id: String,
+        authority: Pubkey,
+        rules: PoolRules,
+    ) -> Result<()> {
+        instructions::sponsor_pool::create_sponsor_pool(ctx, pool_id, authority, rules)
+    }
 
-        emit!(ProgramToggled {
-            is_paused: state.is_paused,
-            admin: ctx.accounts.admin.key(),
-        });
+    /// Pool authority updates the fee schedule and eligibility rules
+    pub fn update_pool_rules(ctx: Context<ManageSponsorPool>, rules: PoolRules) -> Result<()> {
+        instructions::sponsor_pool::update_pool_rules(ctx, rules)
+    }
 
-        Ok(())
+    /// Toggle sponsor pool status
+    pub fn toggle_sponsor_pool(ctx: Context<ManageSponsorPool>) -> Result<()> {
+        instructions::sponsor_pool::toggle_sponsor_pool(ctx)
     }
 
-    pub fn withdraw_funds(ctx: Context<WithdrawFunds>, amount: u64) -> Result<()> {
-        require!(amount > 0, FeePaymentError::InvalidAmount);
-        
-        let treasury_bump = ctx.accounts.state.treasury_bump;
-        
-        require!(ctx.accounts.state.total_funds >= amount, FeePaymentError::InsufficientProgramFunds);
-
-        let treasury_signer_seeds = &[b"treasury".as_ref(), &[treasury_bump]];
-        
-        transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.system_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.treasury.to_account_info(),
-                    to: ctx.accounts.admin.to_account_info(),
-                },
-                &[treasury_signer_seeds],
-            ),
-            amount,
-        )?;
-
-        let state = &mut ctx.accounts.state;
-        state.total_funds = state.total_funds
-            .checked_sub(amount)
-            .ok_or(FeePaymentError::MathUnderflow)?;
-
-        emit!(FundsWithdrawn {
-            amount,
-            admin: ctx.accounts.admin.key(),
-            remaining: state.total_funds,
-        });
-
-        Ok(())
+    /// Anyone can top up a sponsor pool
+    pub fn fund_sponsor_pool(ctx: Context<FundSponsorPool>, amount: u64) -> Result<()> {
+        instructions::sponsor_pool::fund_sponsor_pool(ctx, amount)
     }
-}
 
-/// Calculate gas fee for transaction
-fn calculate_gas_fee(amount: u64, state: &ProgramState) -> u64 {
-    let percentage_fee = amount / 1000; // 0.1% of amount
-    state.base_transaction_fee + percentage_fee
-}
+    /// Pool authority withdraws unreserved pool balance
+    pub fn withdraw_from_pool(ctx: Context<ManageSponsorPool>, amount: u64) -> Result<()> {
+        instructions::sponsor_pool::withdraw_from_pool(ctx, amount)
+    }
 
-// Account Structures
-#[account]
-pub struct ProgramState {
-    pub admin: Pubkey,                  // 32
-    pub total_funds: u64,               // 8
-    pub total_ads_viewed: u64,          // 8
-    pub total_transactions: u64,        // 8
-    pub fee_per_ad: u64,               // 8
-    pub base_transaction_fee: u64,      // 8
-    pub is_paused: bool,               // 1
-    pub bump: u8,                      // 1
-    pub treasury_bump: u8,             // 1 - Added treasury bump
-}                                      // Total: 75 bytes
-
-#[account]
-pub struct Advertisement {
-    pub id: String,                 // 4 + 32
-    pub url: String,                // 4 + 200
-    pub content: String,            // 4 + 500
-    pub reward_amount: u64,         // 8
-    pub display_duration: i64,      // 8
-    pub is_active: bool,           // 1
-    pub view_count: u64,           // 8
-    pub created_at: i64,           // 8
-    pub bump: u8,                  // 1
-}                                  // Total: 774 bytes
-
-#[account]
-pub struct TransactionRequest {
-    pub user: Pubkey,                    // 32
-    pub recipient: Pubkey,               // 32
-    pub amount: u64,                     // 8
-    pub calculated_fee: u64,             // 8
-    pub status: RequestStatus,           // 1 + 1
-    pub selected_ad_id: String,          // 4 + 32
-    pub created_at: i64,                 // 8
-    pub expires_at: i64,                 // 8
-    pub ad_display_started_at: Option<i64>, // 1 + 8
-    pub completed_at: Option<i64>,       // 1 + 8
-    pub cancelled_at: Option<i64>,       // 1 + 8
-    pub ad_view_duration: Option<i64>,   // 1 + 8
-    pub bump: u8,                        // 1
-}                                        // Total: 162 bytes
-
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
-pub enum RequestStatus {
-    WaitingForAd,
-    Completed,
-    Cancelled,
-}
+    /// A merchant registers to sponsor gas for payments made to its own wallet
+    pub fn register_merchant_sponsor(ctx: Context<RegisterMerchantSponsor>, skip_ad: bool) -> Result<()> {
+        instructions::merchant::register_merchant_sponsor(ctx, skip_ad)
+    }
 
-// Events (keeping same as original)
-#[event]
-pub struct ProgramInitialized {
-    pub admin: Pubkey,
-    pub timestamp: i64,
-}
+    /// Merchant updates whether the ad is waived and whether sponsorship is on
+    pub fn update_merchant_sponsor(
+        ctx: Context<ManageMerchantSponsor>,
+        skip_ad: bool,
+        is_active: bool,
+    ) -> Result<()> {
+        instructions::merchant::update_merchant_sponsor(ctx, skip_ad, is_active)
+    }
 
-#[event]
-pub struct FundsDeposited {
-    pub admin: Pubkey,
-    pub amount: u64,
-    pub total_funds: u64,
-}
+    /// Top up a merchant's sponsorship budget
+    pub fn fund_merchant_sponsor(ctx: Context<FundMerchantSponsor>, amount: u64) -> Result<()> {
+        instructions::merchant::fund_merchant_sponsor(ctx, amount)
+    }
 
-#[event]
-pub struct AdCreated {
-    pub ad_id: String,
-    pub reward_amount: u64,
-    pub display_duration: i64,
-    pub creator: Pubkey,
-    pub timestamp: i64,
-}
+    /// Merchant withdraws unspent sponsorship budget
+    pub fn withdraw_merchant_budget(ctx: Context<ManageMerchantSponsor>, amount: u64) -> Result<()> {
+        instructions::merchant::withdraw_merchant_budget(ctx, amount)
+    }
 
-#[event]
-pub struct AdToggled {
-    pub ad_id: String,
-    pub is_active: bool,
-}
+    /// Get a random active ad for popup display
+    pub fn get_random_ad(ctx: Context<GetRandomAd>) -> Result<()> {
+        instructions::ads::get_random_ad(ctx)
+    }
 
-#[event]
-pub struct TransactionInitiated {
-    pub user: Pubkey,
-    pub recipient: Pubkey,
-    pub amount: u64,
-    pub calculated_fee: u64,
-    pub ad_id: String,
-    pub ad_content: String,
-    pub ad_url: String,
-    pub display_duration: i64,
-    pub request_id: Pubkey,
-}
+    /// Cancel a pending request - any locked amount is returned with the rent
+    pub fn cancel_request(ctx: Context<CancelRequest>) -> Result<()> {
+        instructions::send::cancel_request(ctx)
+    }
 
-#[event]
-pub struct TransactionCompleted {
-    pub user: Pubkey,
-    pub recipient: Pubkey,
-    pub amount_sent: u64,
-    pub amount_received: u64,
-    pub gas_fee_sponsored: u64,
-    pub ad_id: String,
-    pub view_duration: i64,
-    pub timestamp: i64,
-}
+    /// Permissionless cleanup of an expired request. Rent, and any locked or
+    /// escrowed amount, goes back to whoever funded it.
+    pub fn expire_request(ctx: Context<ExpireRequest>) -> Result<()> {
+        instructions::send::expire_request(ctx)
+    }
 
-#[event]
-pub struct AdRetrieved {
-    pub ad_id: String,
-    pub ad_content: String,
-    pub ad_url: String,
-    pub display_duration: i64,
-    pub reward_amount: u64,
-}
+    /// Release a held escrow to the recipient - by the user or the arbiter
+    pub fn release_escrow(ctx: Context<ResolveEscrow>) -> Result<()> {
+        instructions::escrow::release_escrow(ctx)
+    }
 
-#[event]
-pub struct RequestCancelled {
-    pub user: Pubkey,
-}
+    /// Refund an escrow to the user - by the arbiter at any time, or by the
+    /// user once the refund deadline has passed
+    pub fn refund_escrow(ctx: Context<ResolveEscrow>) -> Result<()> {
+        instructions::escrow::refund_escrow(ctx)
+    }
 
-#[event]
-pub struct BaseFeeUpdated {
-    pub old_fee: u64,
-    pub new_fee: u64,
-    pub admin: Pubkey,
-}
+    /// Admin blocks a wallet from sending or receiving sponsored payments
+    pub fn block_address(ctx: Context<BlockAddress>, address: Pubkey, reason: u8) -> Result<()> {
+        instructions::access::block_address(ctx, address, reason)
+    }
 
-#[event]
-pub struct ProgramToggled {
-    pub is_paused: bool,
-    pub admin: Pubkey,
-}
+    /// Admin lifts a block, closing the BlockedAddress PDA
+    pub fn unblock_address(ctx: Context<UnblockAddress>) -> Result<()> {
+        instructions::access::unblock_address(ctx)
+    }
 
-#[event]
-pub struct FundsWithdrawn {
-    pub amount: u64,
-    pub admin: Pubkey,
-    pub remaining: u64,
-}
+    /// Admin adds a wallet to the allowlist used in allowlist mode
+    pub fn allow_address(ctx: Context<AllowAddress>, address: Pubkey) -> Result<()> {
+        instructions::access::allow_address(ctx, address)
+    }
 
-// Context Definitions
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(
-        init,
-        payer = deployer,
-        space = 8 + 75, // Updated space for new field
-        seeds = [b"state"],
-        bump
-    )]
-    pub state: Account<'info, ProgramState>,
-    /// CHECK: Treasury PDA for holding funds
-    #[account(
-        seeds = [b"treasury"],
-        bump
-    )]
-    pub treasury: AccountInfo<'info>,
-    #[account(mut)]
-    pub deployer: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+    /// Admin removes a wallet from the allowlist
+    pub fn disallow_address(ctx: Context<DisallowAddress>) -> Result<()> {
+        instructions::access::disallow_address(ctx)
+    }
 
-#[derive(Accounts)]
-pub struct DepositFunds<'info> {
-    #[account(
-        mut,
-        seeds = [b"state"],
-        bump = state.bump,
-        has_one = admin @ FeePaymentError::Unauthorized
-    )]
-    pub state: Account<'info, ProgramState>,
-    /// CHECK: Treasury PDA for holding funds
-    #[account(
-        mut,
-        seeds = [b"treasury"],
-        bump = state.treasury_bump
-    )]
-    pub treasury: AccountInfo<'info>,
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+    /// Admin switches allowlist mode, where only allowlisted wallets may transact
+    pub fn set_allowlist_mode(ctx: Context<AdminAction>, enabled: bool) -> Result<()> {
+        instructions::access::set_allowlist_mode(ctx, enabled)
+    }
 
-#[derive(Accounts)]
-#[instruction(ad_id: String)]
-pub struct CreateAd<'info> {
-    #[account(
-        seeds = [b"state"],
-        bump = state.bump,
-        has_one = admin @ FeePaymentError::Unauthorized
-    )]
-    pub state: Account<'info, ProgramState>,
-    #[account(
-        init,
-        payer = admin,
-        space = 8 + 774,
-        seeds = [b"ad", ad_id.as_bytes()],
-        bump
-    )]
-    pub ad: Account<'info, Advertisement>,
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+    /// Admin function to update base transaction fee
+    pub fn update_base_fee(ctx: Context<AdminAction>, new_base_fee: u64) -> Result<()> {
+        instructions::admin::update_base_fee(ctx, new_base_fee)
+    }
 
-#[derive(Accounts)]
-pub struct ToggleAd<'info> {
-    #[account(
-        seeds = [b"state"],
-        bump = state.bump,
-        has_one = admin @ FeePaymentError::Unauthorized
-    )]
-    pub state: Account<'info, ProgramState>,
-    #[account(mut)]
-    pub ad: Account<'info, Advertisement>,
-    pub admin: Signer<'info>,
-}
+    /// Admin function to update the per-request relayer reimbursement
+    pub fn update_relayer_fee(ctx: Context<AdminAction>, new_relayer_fee: u64) -> Result<()> {
+        instructions::admin::update_relayer_fee(ctx, new_relayer_fee)
+    }
 
-#[derive(Accounts)]
-pub struct InitiateSend<'info> {
-    #[account(
-        seeds = [b"state"],
-        bump = state.bump
-    )]
-    pub state: Account<'info, ProgramState>,
-    #[account(
-        init,
-        payer = user,
-        space = 8 + 162,
-        seeds = [b"request", user.key().as_ref()],
-        bump
-    )]
-    pub request: Account<'info, TransactionRequest>,
-    #[account(constraint = selected_ad.is_active @ FeePaymentError::AdNotActive)]
-    pub selected_ad: Account<'info, Advertisement>,
-    #[account(mut)]
-    pub user: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+    /// Admin registers a relayer allowed to pay rent and fees for users
+    pub fn register_relayer(ctx: Context<RegisterRelayer>, authority: Pubkey) -> Result<()> {
+        instructions::relayer::register_relayer(ctx, authority)
+    }
 
-#[derive(Accounts)]
-pub struct CompleteTransaction<'info> {
-    #[account(
-        mut,
-        seeds = [b"state"],
-        bump = state.bump
-    )]
-    pub state: Account<'info, ProgramState>,
-    /// CHECK: Treasury PDA for holding funds
-    #[account(
-        mut,
-        seeds = [b"treasury"],
-        bump = state.treasury_bump
-    )]
-    pub treasury: AccountInfo<'info>,
-    #[account(
-        mut,
-        constraint = ad.id == request.selected_ad_id @ FeePaymentError::AdMismatch
-    )]
-    pub ad: Account<'info, Advertisement>,
-    #[account(
-        mut,
-        has_one = user @ FeePaymentError::Unauthorized,
-        constraint = request.recipient == recipient.key() @ FeePaymentError::RecipientMismatch,
-        close = user
-    )]
-    pub request: Account<'info, TransactionRequest>,
-    #[account(mut)]
-    pub user: Signer<'info>,
-    /// CHECK: Recipient validation through constraint
-    #[account(mut)]
-    pub recipient: AccountInfo<'info>,
-    /// CHECK: Fee account to receive sponsored gas fees
-    #[account(mut)]
-    pub fee_account: AccountInfo<'info>,
-    pub system_program: Program<'info, System>,
-}
+    /// Toggle relayer status
+    pub fn toggle_relayer(ctx: Context<ToggleRelayer>) -> Result<()> {
+        instructions::relayer::toggle_relayer(ctx)
+    }
 
-#[derive(Accounts)]
-pub struct GetRandomAd<'info> {
-    #[account(constraint = ad.is_active @ FeePaymentError::AdNotActive)]
-    pub ad: Account<'info, Advertisement>,
-}
+    /// Admin registers a wallet or dApp that displays ads to its users
+    pub fn register_publisher(ctx: Context<RegisterPublisher>, authority: Pubkey) -> Result<()> {
+        instructions::publisher::register_publisher(ctx, authority)
+    }
 
-#[derive(Accounts)]
-pub struct CancelRequest<'info> {
-    #[account(
-        mut,
-        has_one = user @ FeePaymentError::Unauthorized,
-        close = user
-    )]
-    pub request: Account<'info, TransactionRequest>,
-    pub user: Signer<'info>,
-}
+    /// Toggle publisher status
+    pub fn toggle_publisher(ctx: Context<TogglePublisher>) -> Result<()> {
+        instructions::publisher::toggle_publisher(ctx)
+    }
 
-#[derive(Accounts)]
-pub struct AdminAction<'info> {
-    #[account(
-        mut,
-        seeds = [b"state"],
-        bump = state.bump,
-        has_one = admin @ FeePaymentError::Unauthorized
-    )]
-    pub state: Account<'info, ProgramState>,
-    pub admin: Signer<'info>,
-}
+    /// Publisher claims its accrued revenue share
+    pub fn claim_publisher_revenue(ctx: Context<ClaimPublisherRevenue>) -> Result<()> {
+        instructions::publisher::claim_publisher_revenue(ctx)
+    }
 
-#[derive(Accounts)]
-pub struct WithdrawFunds<'info> {
-    #[account(
-        mut,
-        seeds = [b"state"],
-        bump = state.bump,
-        has_one = admin @ FeePaymentError::Unauthorized
-    )]
-    pub state: Account<'info, ProgramState>,
-    /// CHECK: Treasury PDA for holding funds
-    #[account(
-        mut,
-        seeds = [b"treasury"],
-        bump = state.treasury_bump
-    )]
-    pub treasury: AccountInfo<'info>,
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    pub system_program: Program<'info, System>,
-}
+    /// Admin function to update the share of each ad charge paid to publishers
+    pub fn update_publisher_share(ctx: Context<AdminAction>, new_share_bps: u16) -> Result<()> {
+        instructions::admin::update_publisher_share(ctx, new_share_bps)
+    }
+
+    /// Admin function to update how many reports suspend an ad
+    pub fn update_report_threshold(ctx: Context<AdminAction>, new_threshold: u32) -> Result<()> {
+        instructions::admin::update_report_threshold(ctx, new_threshold)
+    }
+
+    /// Admin appoints the moderator who reviews submitted ads
+    pub fn set_moderator(ctx: Context<AdminAction>, moderator: Pubkey) -> Result<()> {
+        instructions::admin::set_moderator(ctx, moderator)
+    }
+
+    /// Admin appoints the guardian, who can pause operations but not unpause them
+    pub fn set_guardian(ctx: Context<AdminAction>, guardian: Pubkey) -> Result<()> {
+        instructions::admin::set_guardian(ctx, guardian)
+    }
+
+    /// Pause a set of operations (`PAUSE_*` bits) - admin or guardian
+    pub fn pause_operations(ctx: Context<GuardianAction>, operations: u8) -> Result<()> {
+        instructions::admin::pause_operations(ctx, operations)
+    }
+
+    /// Resume a set of operations - admin only, the guardian can't unpause
+    pub fn unpause_operations(ctx: Context<AdminAction>, operations: u8) -> Result<()> {
+        instructions::admin::unpause_operations(ctx, operations)
+    }
+
+    /// Admin functions
+    pub fn withdraw_funds(ctx: Context<WithdrawFunds>, amount: u64) -> Result<()> {
+        instructions::treasury::withdraw_funds(ctx, amount)
+    }
+
+    /// Admin function to update the treasury balance that must stay liquid
+    pub fn update_liquid_buffer(ctx: Context<AdminAction>, new_buffer: u64) -> Result<()> {
+        instructions::admin::update_liquid_buffer(ctx, new_buffer)
+    }
 
-// Custom Error Types
-#[error_code]
-pub enum FeePaymentError {
-    #[msg("Unauthorized access")]
-    Unauthorized,
-    #[msg("Invalid amount")]
-    InvalidAmount,
-    #[msg("Invalid recipient address")]
-    InvalidRecipient,
-    #[msg("Invalid fee amount")]
-    InvalidFee,
-    #[msg("Program has insufficient funds to sponsor gas fees")]
-    InsufficientProgramFunds,
-    #[msg("Invalid ad ID")]
-    InvalidAdId,
-    #[msg("Invalid ad URL - must be HTTPS")]
-    InvalidAdUrl,
-    #[msg("Invalid ad content")]
-    InvalidAdContent,
-    #[msg("Invalid display time")]
-    InvalidDisplayTime,
-    #[msg("Reward amount too low")]
-    RewardTooLow,
-    #[msg("Program is paused")]
-    ProgramPaused,
-    #[msg("Request has expired")]
-    RequestExpired,
-    #[msg("Invalid request status")]
-    InvalidStatus,
-    #[msg("Advertisement not active")]
-    AdNotActive,
-    #[msg("Recipient address mismatch")]
-    RecipientMismatch,
-    #[msg("Ad ID mismatch")]
-    AdMismatch,
-    #[msg("Ad display not started")]
-    AdNotStarted,
-    #[msg("Insufficient ad viewing time")]
-    InsufficientViewTime,
-    #[msg("Math overflow")]
-    MathOverflow,
-    #[msg("Math underflow")]
-    MathUnderflow,
+    /// Admin function to create the registry of ads bidding in placement auctions
+    pub fn create_ad_registry(ctx: Context<CreateAdRegistry>) -> Result<()> {
+        instructions::admin::create_ad_registry(ctx)
+    }
+
+    /// Configure the treasury outflow circuit breaker (threshold 0 disables it)
+    pub fn update_circuit_breaker(
+        ctx: Context<AdminAction>,
+        threshold: u64,
+        window_slots: u64,
+    ) -> Result<()> {
+        instructions::admin::update_circuit_breaker(ctx, threshold, window_slots)
+    }
+
+    /// Move idle treasury funds into a new treasury stake account. The treasury
+    /// PDA is both staker and withdrawer, and `min_liquid_buffer` stays liquid.
+    /// Each `stake_index` is its own stake account, so the treasury can spread
+    /// its stake across validators.
+    pub fn create_treasury_stake(
+        ctx: Context<CreateTreasuryStake>,
+        stake_index: u64,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::treasury::create_treasury_stake(ctx, stake_index, amount)
+    }
+
+    /// Delegate a treasury stake account to a validator
+    pub fn delegate_treasury_stake(ctx: Context<DelegateTreasuryStake>, stake_index: u64) -> Result<()> {
+        instructions::treasury::delegate_treasury_stake(ctx, stake_index)
+    }
+
+    /// Start cooling down a treasury stake account so it can be withdrawn
+    pub fn deactivate_treasury_stake(ctx: Context<DeactivateTreasuryStake>, stake_index: u64) -> Result<()> {
+        instructions::treasury::deactivate_treasury_stake(ctx, stake_index)
+    }
+
+    /// Withdraw inactive stake (principal and rewards) back into the treasury
+    pub fn withdraw_treasury_stake(
+        ctx: Context<WithdrawTreasuryStake>,
+        stake_index: u64,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::treasury::withdraw_treasury_stake(ctx, stake_index, amount)
+    }
+
+    /// Upgrade the program state from an older layout in place
+    pub fn migrate_state(ctx: Context<MigrateState>) -> Result<()> {
+        instructions::migration::migrate_state(ctx)
+    }
+
+    /// Upgrade an advertisement from an older layout in place
+    pub fn migrate_ad(ctx: Context<MigrateAd>) -> Result<()> {
+        instructions::migration::migrate_ad(ctx)
+    }
+
+    /// Close a request left pending in the pre-versioning layout, which no
+    /// longer deserializes, and return its rent to the user
+    pub fn close_legacy_request(ctx: Context<CloseLegacyRequest>) -> Result<()> {
+        instructions::migration::close_legacy_request(ctx)
+    }
 }
@@ -0,0 +1,379 @@
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct ProgramState {
+    pub admin: Pubkey,                  // 32
+    pub total_funds: u64,               // 8
+    pub total_ads_viewed: u64,          // 8
+    pub total_transactions: u64,        // 8
+    pub fee_per_ad: u64,               // 8
+    pub base_transaction_fee: u64,      // 8
+    pub paused_operations: u8,         // 1 - Bitflag of PAUSE_* operations
+    pub bump: u8,                      // 1
+    pub treasury_bump: u8,             // 1 - Added treasury bump
+    pub relayer_fee: u64,              // 8
+    pub reserved_funds: u64,           // 8 - Fees held for pending requests
+    pub staked_funds: u64,             // 8 - Treasury principal across its stake accounts
+    pub min_liquid_buffer: u64,        // 8 - Kept out of stake for sponsorship
+    pub allowlist_mode: bool,          // 1
+    pub guardian: Pubkey,              // 32 - May pause, but not unpause
+    pub outflow_threshold: u64,        // 8 - Circuit breaker limit, 0 = disabled
+    pub outflow_window_slots: u64,     // 8
+    pub outflow_window_start: u64,     // 8
+    pub outflow_in_window: u64,        // 8
+    pub version: u8,                   // 1 - Layout version, 0 = pre-versioning
+    pub publisher_share_bps: u16,      // 2 - Share of ad charges paid to publishers
+    pub moderator: Pubkey,             // 32 - Reviews submitted ads
+    pub report_threshold: u32,         // 4 - Reports beyond this suspend an ad
+    pub total_deposited: u64,          // 8 - Lifetime admin deposits and donations
+    pub sponsorship_index: u128,       // 16 - Sponsored payments per deposited lamport, scaled
+}                                      // Total: 235 bytes
+
+#[account]
+pub struct Advertisement {
+    pub id: String,                 // 4 + 32
+    pub url: String,                // 4 + 200
+    pub content: String,            // 4 + 500
+    pub reward_amount: u64,         // 8
+    pub display_duration: i64,      // 8
+    pub status: AdStatus,          // 1 - Replaced the `is_active` bool
+    pub view_count: u64,           // 8
+    pub created_at: i64,           // 8
+    pub bump: u8,                  // 1
+    pub version: u8,               // 1 - Layout version, 0 = pre-versioning
+    pub advertiser: Pubkey,        // 32 - Owns the budget and sets the bid
+    pub bid_per_view: u64,         // 8 - Sealed bid per billed view or click, 0 = not bidding
+    pub budget: u64,               // 8 - Held in this account's lamports
+    pub reserved_budget: u64,      // 8 - Clearing prices of pending requests
+    pub max_impressions_per_user: u16, // 2 - Per window, 0 = uncapped
+    pub frequency_window: i64,     // 8 - Seconds
+    pub categories: u32,           // 4 - Category bitmask, 0 = any
+    pub locales: Vec<[u8; 2]>,     // 4 + 4 * 2 - ISO 639-1 codes, empty = any
+    pub min_transfer_amount: u64,  // 8
+    pub recipient_programs: Vec<Pubkey>, // 4 + 4 * 32 - Recipients served, empty = any
+    pub creative_mode: CreativeMode, // 1
+    pub content_uri: String,       // 4 + 200 - Reference mode only
+    pub content_hash: [u8; 32],    // 32 - SHA-256 of the referenced creative
+    pub mime_type: String,         // 4 + 64
+    pub rejection_reason: u8,      // 1 - Moderator reason code when rejected
+    pub ends_at: i64,              // 8 - 0 = no end
+    pub report_count: u32,         // 4 - User reports since last review
+    pub click_count: u64,          // 8
+    pub billing_model: BillingModel, // 1
+    pub conversion_authority: Pubkey, // 32 - Reports conversions, default = none
+    pub attribution_window: i64,   // 8 - Seconds after a view
+    pub conversions: u64,          // 8
+    pub campaign: Option<Pubkey>,  // 1 + 32 - Campaign this ad is a variant of
+}                                  // Total: 1409 bytes
+
+#[account]
+pub struct TransactionRequest {
+    pub user: Pubkey,                    // 32
+    pub recipient: Pubkey,               // 32
+    pub amount: u64,                     // 8
+    pub calculated_fee: u64,             // 8
+    pub status: RequestStatus,           // 1 + 1
+    pub selected_ad_id: String,          // 4 + 32
+    pub created_at: i64,                 // 8
+    pub expires_at: i64,                 // 8
+    pub ad_display_started_at: Option<i64>, // 1 + 8
+    pub completed_at: Option<i64>,       // 1 + 8
+    pub cancelled_at: Option<i64>,       // 1 + 8
+    pub ad_view_duration: Option<i64>,   // 1 + 8
+    pub bump: u8,                        // 1
+    pub relayer: Option<Pubkey>,         // 1 + 32 - Relayer that paid rent, if any
+    pub escrow: Option<Pubkey>,          // 1 + 32 - Escrow holding the amount, if any
+    pub funds_locked: bool,              // 1 - Amount held in this account's lamports
+    pub relayer_fee: u64,                // 8 - Reimbursement reserved for the relayer
+    pub pool: Option<Pubkey>,            // 1 + 32 - Sponsor pool funding the fee, if any
+    pub version: u8,                     // 1 - Layout version
+    pub ad_price: u64,                   // 8 - Clearing price reserved on the ad
+    pub publisher: Option<Pubkey>,       // 1 + 32 - Publisher serving the ad, if any
+}                                        // Total: 321 bytes
+
+#[account]
+pub struct BatchRequest {
+    pub user: Pubkey,                    // 32
+    pub recipient_count: u8,             // 1
+    pub payouts: Vec<BatchPayout>,       // 4 + 10 * 40
+    pub total_amount: u64,               // 8
+    pub calculated_fee: u64,             // 8
+    pub status: RequestStatus,           // 1 + 1
+    pub selected_ad_id: String,          // 4 + 32
+    pub created_at: i64,                 // 8
+    pub expires_at: i64,                 // 8
+    pub ad_display_started_at: Option<i64>, // 1 + 8
+    pub bump: u8,                        // 1
+    pub ad_price: u64,                   // 8 - Clearing price reserved on the ad
+}                                        // Total: 525 bytes
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BatchPayout {
+    pub recipient: Pubkey,               // 32
+    pub amount: u64,                     // 8
+}
+
+#[account]
+pub struct Escrow {
+    pub user: Pubkey,                    // 32
+    pub recipient: Pubkey,               // 32
+    pub arbiter: Pubkey,                 // 32
+    pub escrow_id: u64,                  // 8
+    pub amount: u64,                     // 8
+    pub status: EscrowStatus,            // 1 + 1
+    pub refund_after: i64,               // 8
+    pub created_at: i64,                 // 8
+    pub bump: u8,                        // 1
+}                                        // Total: 131 bytes
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum EscrowStatus {
+    AwaitingAd,
+    Held,
+}
+
+#[account]
+pub struct SponsorPool {
+    pub pool_id: String,                 // 4 + 32
+    pub authority: Pubkey,               // 32
+    pub balance: u64,                    // 8
+    pub reserved: u64,                   // 8
+    pub base_fee: u64,                   // 8
+    pub fee_bps: u16,                    // 2
+    pub max_amount: u64,                 // 8
+    pub allowed_recipients: Vec<Pubkey>, // 4 + 5 * 32
+    pub allowed_ads: Vec<Pubkey>,        // 4 + 5 * 32
+    pub is_active: bool,                 // 1
+    pub total_sponsored: u64,            // 8
+    pub sponsored_count: u64,            // 8
+    pub created_at: i64,                 // 8
+    pub bump: u8,                        // 1
+}                                        // Total: 456 bytes
+
+/// Fee schedule and eligibility rules of a sponsor pool
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PoolRules {
+    pub base_fee: u64,
+    pub fee_bps: u16,
+    pub max_amount: u64,
+    pub allowed_recipients: Vec<Pubkey>,
+    pub allowed_ads: Vec<Pubkey>,
+}
+
+#[account]
+pub struct MerchantSponsor {
+    pub merchant: Pubkey,                // 32 - Recipient wallet whose inbound payments are sponsored
+    pub budget: u64,                     // 8
+    pub skip_ad: bool,                   // 1
+    pub is_active: bool,                 // 1
+    pub total_sponsored: u64,            // 8
+    pub sponsored_count: u64,            // 8
+    pub created_at: i64,                 // 8
+    pub bump: u8,                        // 1
+}                                        // Total: 67 bytes
+
+#[account]
+pub struct TreasuryStake {
+    pub stake_account: Pubkey,           // 32
+    pub principal: u64,                  // 8 - Treasury lamports not yet withdrawn back
+    pub bump: u8,                        // 1
+}                                        // Total: 41 bytes
+
+#[account]
+pub struct SponsorContribution {
+    pub donor: Pubkey,                   // 32
+    pub total_contributed: u64,          // 8
+    pub first_contributed_at: i64,       // 8
+    pub last_contributed_at: i64,        // 8
+    pub bump: u8,                        // 1
+    pub sponsored_count: u64,            // 8 - Treasury-sponsored payments attributed pro rata
+    pub sponsorship_units: u128,         // 16 - `sponsored_count` scaled, keeping the fraction
+    pub sponsorship_checkpoint: u128,    // 16 - `ProgramState::sponsorship_index` at last settle
+}                                        // Total: 97 bytes
+
+#[account]
+pub struct BlockedAddress {
+    pub address: Pubkey,                 // 32
+    pub reason: u8,                      // 1
+    pub blocked_at: i64,                 // 8
+    pub bump: u8,                        // 1
+}                                        // Total: 42 bytes
+
+#[account]
+pub struct AllowedAddress {
+    pub address: Pubkey,                 // 32
+    pub allowed_at: i64,                 // 8
+    pub bump: u8,                        // 1
+}                                        // Total: 41 bytes
+
+#[account]
+pub struct Relayer {
+    pub authority: Pubkey,               // 32
+    pub is_active: bool,                 // 1
+    pub total_relayed: u64,              // 8
+    pub total_reimbursed: u64,           // 8
+    pub registered_at: i64,              // 8
+    pub bump: u8,                        // 1
+}                                        // Total: 58 bytes
+
+#[account]
+pub struct Campaign {
+    pub campaign_id: String,             // 4 + 32
+    pub advertiser: Pubkey,              // 32
+    pub variants: Vec<CampaignVariant>,  // 4 + 5 * 50
+    pub is_active: bool,                 // 1
+    pub created_at: i64,                 // 8
+    pub bump: u8,                        // 1
+}                                        // Total: 332 bytes
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CampaignVariant {
+    pub ad: Pubkey,                      // 32
+    pub weight: u16,                     // 2 - Share of traffic, 0 = not served
+    pub views: u64,                      // 8
+    pub clicks: u64,                     // 8
+}
+
+#[account]
+pub struct ViewReceipt {
+    pub user: Pubkey,                    // 32
+    pub ad: Pubkey,                      // 32
+    pub viewed_at: i64,                  // 8 - Latest completed view
+    pub view_count: u32,                 // 4
+    pub clicked: bool,                   // 1 - Latest view clicked through
+    pub click_price: u64,                // 8 - Owed on click by cost-per-click ads
+    pub bump: u8,                        // 1
+    pub converted: bool,                 // 1 - Latest view attributed a conversion
+    pub publisher: Option<Pubkey>,       // 1 + 32 - Publisher that served the latest view
+}                                        // Total: 120 bytes
+
+#[account]
+pub struct AdReport {
+    pub ad: Pubkey,                      // 32
+    pub user: Pubkey,                    // 32
+    pub reason: u8,                      // 1
+    pub reported_at: i64,                // 8
+    pub bump: u8,                        // 1
+}                                        // Total: 74 bytes
+
+#[account]
+pub struct AdRegistry {
+    pub ads: Vec<Pubkey>,                // 4 + 8 * 32 - Ads bidding in placement auctions
+    pub bump: u8,                        // 1
+}                                        // Total: 261 bytes
+
+#[account]
+pub struct UserPreferences {
+    pub user: Pubkey,                    // 32
+    pub categories: u32,                 // 4 - Opted-in category bitmask
+    pub language: [u8; 2],               // 2 - ISO 639-1 code
+    pub updated_at: i64,                 // 8
+    pub bump: u8,                        // 1
+}                                        // Total: 47 bytes
+
+#[account]
+pub struct UserImpressions {
+    pub user: Pubkey,                    // 32
+    pub entries: Vec<ImpressionEntry>,   // 4 + 16 * 42
+    pub bump: u8,                        // 1
+}                                        // Total: 709 bytes
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ImpressionEntry {
+    pub ad: Pubkey,                      // 32
+    pub count: u16,                      // 2
+    pub window_start: i64,               // 8
+}
+
+#[account]
+pub struct Publisher {
+    pub authority: Pubkey,               // 32
+    pub is_active: bool,                 // 1
+    pub claimable: u64,                  // 8 - Held in this account's lamports until claimed
+    pub total_earned: u64,               // 8
+    pub views_served: u64,               // 8
+    pub registered_at: i64,              // 8
+    pub bump: u8,                        // 1
+}                                        // Total: 66 bytes
+
+#[account]
+pub struct UserProfile {
+    pub user: Pubkey,                    // 32
+    pub nonce: u64,                      // 8 - Next unused signed intent nonce
+    pub created_at: i64,                 // 8
+    pub bump: u8,                        // 1
+    pub balance: u64,                    // 8 - Held in this account's lamports for relayed sends
+}                                        // Total: 57 bytes
+
+/// Off-chain message a user signs to authorize a relayed send
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SendIntent {
+    pub program_id: Pubkey,
+    pub user: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub nonce: u64,
+}
+
+/// Ad lifecycle. Approved is second so a legacy `is_active = true` byte
+/// reads back as Approved.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AdStatus {
+    PendingReview,
+    Approved,
+    Rejected,
+    Paused,
+    Exhausted,
+    Expired,
+    Suspended,
+}
+
+/// What an ad's clearing price is charged for. Per view first so
+/// pre-existing ads migrate to it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BillingModel {
+    PerView,
+    PerClick,
+}
+
+/// Where an ad's creative lives. Inline is first so pre-existing ads
+/// migrate to it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CreativeMode {
+    Inline,    // Text in `url` and `content`
+    Reference, // Off-chain creative at `content_uri`
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum RequestStatus {
+    WaitingForAd,
+    Completed,
+    Cancelled,
+}
+
+#[account]
+pub struct Subscription {
+    pub user: Pubkey,                    // 32
+    pub recipient: Pubkey,               // 32
+    pub subscription_id: u64,            // 8
+    pub amount: u64,                     // 8
+    pub interval: i64,                   // 8
+    pub remaining_payments: u32,         // 4
+    pub next_payment_at: i64,            // 8
+    pub sponsorship: SubscriptionSponsorship, // 1 + 1
+    pub fee_per_payment: u64,            // 8
+    pub fee_credits: u64,                // 8
+    pub cycle_ad_id: String,             // 4 + 32
+    pub ad_display_started_at: Option<i64>, // 1 + 8
+    pub cycle_ad_viewed: bool,           // 1
+    pub cycle_ad_due_at: i64,            // 8 - Payment the cycle's ad view is bound to
+    pub created_at: i64,                 // 8
+    pub bump: u8,                        // 1
+    pub cycle_ad_price: u64,             // 8 - Clearing price reserved on the cycle's ad
+}                                        // Total: 190 bytes
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum SubscriptionSponsorship {
+    AdPerCycle,
+    PrepaidCredits,
+}
@@ -1,16 +1,3473 @@
-// import * as anchor from "@coral-xyz/anchor";
-// import { Program } from "@coral-xyz/anchor";
-// import { FeePaymentDapp } from "../target/types/fee_payment_dapp";
-
-// describe("sp", () => {
-//   // Configure the client to use the local cluster.
-//   anchor.setProvider(anchor.AnchorProvider.env());
-
-//   const program = anchor.workspace.sp as Program<Sp>;
-
-//   it("Is initialized!", async () => {
-//     // Add your test here.
-//     const tx = await program.methods.initialize().rpc();
-//     console.log("Your transaction signature", tx);
-//   });
-// });
+import * as anchor from "@coral-xyz/anchor";
+import { BN, Program } from "@coral-xyz/anchor";
+import {
+  AccountMeta,
+  Ed25519Program,
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  StakeProgram,
+  SystemProgram,
+  SYSVAR_CLOCK_PUBKEY,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
+  SYSVAR_RENT_PUBKEY,
+  SYSVAR_STAKE_HISTORY_PUBKEY,
+  Transaction,
+  TransactionInstruction,
+} from "@solana/web3.js";
+import { expect } from "chai";
+import { Sp } from "../target/types/sp";
+
+type AccountOverrides = { [name: string]: PublicKey | null };
+
+describe("sp", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.sp as Program<Sp>;
+  const connection = provider.connection;
+  const admin = provider.wallet.publicKey;
+
+  // Ads are shown for at least MIN_AD_VIEW_TIME seconds; leave slack for the
+  // validator clock
+  const VIEW_SECONDS = 5;
+  const VIEW_WAIT_MS = (VIEW_SECONDS + 2) * 1000;
+  const SEND_AMOUNT = 10_000_000; // 0.01 SOL
+
+  // Ids are suffixed per run so the suite can rerun against a live cluster
+  const run = Date.now().toString(36).slice(-5);
+
+  const pda = (...seeds: Buffer[]) =>
+    PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+  const u64 = (value: number | BN) =>
+    new BN(value).toArrayLike(Buffer, "le", 8);
+
+  const statePda = pda(Buffer.from("state"));
+  const treasuryPda = pda(Buffer.from("treasury"));
+  const registryPda = pda(Buffer.from("ad_registry"));
+  const adPda = (adId: string) => pda(Buffer.from("ad"), Buffer.from(adId));
+  const requestPda = (user: PublicKey) =>
+    pda(Buffer.from("request"), user.toBuffer());
+  const impressionsPda = (user: PublicKey) =>
+    pda(Buffer.from("impressions"), user.toBuffer());
+  const blockedPda = (address: PublicKey) =>
+    pda(Buffer.from("blocked"), address.toBuffer());
+  const allowedPda = (address: PublicKey) =>
+    pda(Buffer.from("allowed"), address.toBuffer());
+  const viewReceiptPda = (user: PublicKey, ad: PublicKey) =>
+    pda(Buffer.from("view_receipt"), user.toBuffer(), ad.toBuffer());
+  const relayerPda = (authority: PublicKey) =>
+    pda(Buffer.from("relayer"), authority.toBuffer());
+  const userProfilePda = (user: PublicKey) =>
+    pda(Buffer.from("user_profile"), user.toBuffer());
+  const publisherPda = (authority: PublicKey) =>
+    pda(Buffer.from("publisher"), authority.toBuffer());
+  const merchantPda = (owner: PublicKey) =>
+    pda(Buffer.from("merchant"), owner.toBuffer());
+
+  const feeAccount = Keypair.generate().publicKey;
+  let baseAd: PublicKey;
+
+  const sleep = (ms: number) =>
+    new Promise((resolve) => setTimeout(resolve, ms));
+
+  /** Fail unless `promise` rejects with the program error `code`, or logs it */
+  async function expectError(promise: Promise<unknown>, code: string) {
+    let error: any;
+    try {
+      await promise;
+    } catch (err) {
+      error = err;
+    }
+    expect(error, `expected ${code}`).to.not.equal(undefined);
+    // Errors from other programs only show up in the transaction logs
+    const actual =
+      error.error?.errorCode?.code ?? [String(error)].concat(error.logs ?? []).join("\n");
+    expect(actual).to.contain(code);
+  }
+
+  async function fund(to: PublicKey, lamports: number) {
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.transfer({ fromPubkey: admin, toPubkey: to, lamports })
+      )
+    );
+  }
+
+  async function fundedKeypair(sol = 1): Promise<Keypair> {
+    const keypair = Keypair.generate();
+    await fund(keypair.publicKey, sol * LAMPORTS_PER_SOL);
+    return keypair;
+  }
+
+  /** A fresh recipient, already rent-exempt so any amount can land */
+  async function newRecipient(): Promise<PublicKey> {
+    const recipient = Keypair.generate().publicKey;
+    await fund(recipient, 0.01 * LAMPORTS_PER_SOL);
+    return recipient;
+  }
+
+  const balance = (address: PublicKey) => connection.getBalance(address);
+
+  /** Gas fee the program sponsors for a send of `amount` */
+  async function gasFee(amount: number): Promise<number> {
+    const state = await program.account.programState.fetch(statePda);
+    return state.baseTransactionFee.toNumber() + Math.floor(amount / 1000);
+  }
+
+  async function createAd(adId: string, creator?: Keypair): Promise<PublicKey> {
+    const builder = program.methods
+      .createAd(
+        adId,
+        "https://example.com",
+        `Creative for ${adId}`,
+        new BN(1_000),
+        new BN(VIEW_SECONDS)
+      )
+      .accountsPartial({
+        state: statePda,
+        ad: adPda(adId),
+        creator: creator ? creator.publicKey : admin,
+        systemProgram: SystemProgram.programId,
+      });
+    await (creator ? builder.signers([creator]) : builder).rpc();
+    return adPda(adId);
+  }
+
+  /** Bid on `ad` as the admin, entering or leaving the auction registry */
+  async function setBid(ad: PublicKey, bidPerView: number) {
+    await program.methods
+      .setAdBid(new BN(bidPerView))
+      .accountsPartial({ state: statePda, ad, adRegistry: registryPda, advertiser: admin })
+      .rpc();
+  }
+
+  async function fundAd(ad: PublicKey, amount: number) {
+    await program.methods
+      .fundAdBudget(new BN(amount))
+      .accountsPartial({
+        state: statePda,
+        ad,
+        funder: admin,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+  }
+
+  /** Every registered ad, followed by its campaign, as the auction expects */
+  async function auctionAccounts(): Promise<AccountMeta[]> {
+    const registry = await program.account.adRegistry.fetch(registryPda);
+    const accounts: AccountMeta[] = [];
+    for (const key of registry.ads) {
+      accounts.push({ pubkey: key, isSigner: false, isWritable: true });
+      const ad = await program.account.advertisement.fetch(key);
+      if (ad.campaign) {
+        accounts.push({ pubkey: ad.campaign, isSigner: false, isWritable: false });
+      }
+    }
+    return accounts;
+  }
+
+  function sendAccounts(user: PublicKey, recipient: PublicKey) {
+    return {
+      state: statePda,
+      request: requestPda(user),
+      selectedAd: baseAd,
+      adRegistry: registryPda,
+      impressions: impressionsPda(user),
+      preferences: null,
+      campaign: null,
+      publisher: null,
+      pool: null,
+      user,
+      userBlocked: blockedPda(user),
+      userAllowed: null,
+      recipientBlocked: blockedPda(recipient),
+      recipientAllowed: null,
+      systemProgram: SystemProgram.programId,
+    };
+  }
+
+  function completeAccounts(user: PublicKey, recipient: PublicKey, ad = baseAd) {
+    return {
+      state: statePda,
+      treasury: treasuryPda,
+      ad,
+      request: requestPda(user),
+      user,
+      payer: user,
+      rentPayer: user,
+      relayer: null,
+      escrow: null,
+      pool: null,
+      recipient,
+      feeAccount,
+      merchantSponsor: merchantPda(recipient),
+      publisher: null,
+      viewReceipt: viewReceiptPda(user, ad),
+      campaign: null,
+      systemProgram: SystemProgram.programId,
+    };
+  }
+
+  async function initiateSend(
+    user: Keypair,
+    recipient: PublicKey,
+    amount = SEND_AMOUNT,
+    overrides: AccountOverrides = {}
+  ) {
+    return program.methods
+      .initiateSendTransaction(recipient, new BN(amount))
+      .accountsPartial({ ...sendAccounts(user.publicKey, recipient), ...overrides })
+      .remainingAccounts(await auctionAccounts())
+      .signers([user])
+      .rpc();
+  }
+
+  async function completeSend(
+    user: Keypair,
+    recipient: PublicKey,
+    overrides: AccountOverrides = {}
+  ) {
+    return program.methods
+      .completeTransactionAfterAd(new BN(VIEW_SECONDS))
+      .accountsPartial({
+        ...completeAccounts(user.publicKey, recipient, overrides.ad || baseAd),
+        ...overrides,
+      })
+      .signers([user])
+      .rpc();
+  }
+
+  async function cancelSend(user: Keypair, overrides: AccountOverrides = {}) {
+    return program.methods
+      .cancelRequest()
+      .accountsPartial({
+        state: statePda,
+        request: requestPda(user.publicKey),
+        escrow: null,
+        pool: null,
+        ad: null,
+        user: user.publicKey,
+        rentPayer: user.publicKey,
+        ...overrides,
+      })
+      .signers([user])
+      .rpc();
+  }
+
+  /** Borsh-encoded `SendIntent`, the message a user signs for a relayer */
+  function sendIntent(user: PublicKey, recipient: PublicKey, amount: number, nonce: number) {
+    return Buffer.concat([
+      program.programId.toBuffer(),
+      user.toBuffer(),
+      recipient.toBuffer(),
+      u64(amount),
+      u64(nonce),
+    ]);
+  }
+
+  function signIntent(signer: Keypair, message: Buffer) {
+    return Ed25519Program.createInstructionWithPrivateKey({
+      privateKey: signer.secretKey,
+      message,
+    });
+  }
+
+  async function initiateRelayedSend(
+    relayer: Keypair,
+    user: PublicKey,
+    recipient: PublicKey,
+    amount: number,
+    nonce: number,
+    preInstructions: TransactionInstruction[] = []
+  ) {
+    return program.methods
+      .initiateRelayedSend(recipient, new BN(amount), new BN(nonce))
+      .accountsPartial({
+        state: statePda,
+        relayer: relayerPda(relayer.publicKey),
+        request: requestPda(user),
+        userProfile: userProfilePda(user),
+        selectedAd: baseAd,
+        adRegistry: registryPda,
+        impressions: impressionsPda(user),
+        preferences: null,
+        campaign: null,
+        publisher: null,
+        user,
+        relayerAuthority: relayer.publicKey,
+        instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        userBlocked: blockedPda(user),
+        userAllowed: null,
+        recipientBlocked: blockedPda(recipient),
+        recipientAllowed: null,
+        systemProgram: SystemProgram.programId,
+      })
+      .preInstructions(preInstructions)
+      .remainingAccounts(await auctionAccounts())
+      .signers([relayer])
+      .rpc();
+  }
+
+  // Bits of `ProgramState::paused_operations`
+  const PAUSE_INITIATE = 1 << 0;
+  const PAUSE_COMPLETE = 1 << 1;
+  const PAUSE_DEPOSIT = 1 << 2;
+  const PAUSE_WITHDRAW = 1 << 3;
+  const PAUSE_AD_MANAGEMENT = 1 << 4;
+  const PAUSE_ALL =
+    PAUSE_INITIATE | PAUSE_COMPLETE | PAUSE_DEPOSIT | PAUSE_WITHDRAW | PAUSE_AD_MANAGEMENT;
+
+  async function pause(operations: number) {
+    await program.methods
+      .pauseOperations(operations)
+      .accountsPartial({ state: statePda, authority: admin })
+      .rpc();
+  }
+
+  async function unpause(operations: number) {
+    await program.methods
+      .unpauseOperations(operations)
+      .accountsPartial({ state: statePda, admin })
+      .rpc();
+  }
+
+  async function registerRelayer(authority: PublicKey) {
+    await program.methods
+      .registerRelayer(authority)
+      .accountsPartial({
+        state: statePda,
+        relayer: relayerPda(authority),
+        admin,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+  }
+
+  /** Create `user`'s profile, paid by `payer`, and prefund it for relayed sends */
+  async function createProfile(user: Keypair, payer: Keypair, deposit: number) {
+    await program.methods
+      .createUserProfile()
+      .accountsPartial({
+        userProfile: userProfilePda(user.publicKey),
+        user: user.publicKey,
+        payer: payer.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([payer])
+      .rpc();
+    await program.methods
+      .depositToProfile(new BN(deposit))
+      .accountsPartial({
+        state: statePda,
+        userProfile: userProfilePda(user.publicKey),
+        user: user.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  }
+
+  before(async () => {
+    if (!(await connection.getAccountInfo(statePda))) {
+      await program.methods
+        .initialize()
+        .accountsPartial({
+          state: statePda,
+          treasury: treasuryPda,
+          deployer: admin,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+    if (!(await connection.getAccountInfo(registryPda))) {
+      await program.methods
+        .createAdRegistry()
+        .accountsPartial({
+          state: statePda,
+          adRegistry: registryPda,
+          admin,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+
+    for (let i = 0; i < 2; i++) {
+      await program.methods
+        .depositFunds(new BN(10 * LAMPORTS_PER_SOL))
+        .accountsPartial({
+          state: statePda,
+          treasury: treasuryPda,
+          admin,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+
+    await fund(feeAccount, 0.01 * LAMPORTS_PER_SOL);
+    baseAd = await createAd(`base-${run}`);
+  });
+
+  describe("relayed sends (user-026)", () => {
+    let relayer: Keypair;
+    let user: Keypair;
+    let recipient: PublicKey;
+    const profileDeposit = 0.1 * LAMPORTS_PER_SOL;
+
+    before(async () => {
+      relayer = await fundedKeypair(2);
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+
+      await registerRelayer(relayer.publicKey);
+      await createProfile(user, relayer, profileDeposit);
+    });
+
+    it("only lets the admin register relayers", async () => {
+      const outsider = await fundedKeypair();
+      await expectError(
+        program.methods
+          .registerRelayer(outsider.publicKey)
+          .accountsPartial({
+            state: statePda,
+            relayer: relayerPda(outsider.publicKey),
+            admin: outsider.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([outsider])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("rejects a relayed send without the Ed25519 instruction", async () => {
+      await expectError(
+        initiateRelayedSend(relayer, user.publicKey, recipient, SEND_AMOUNT, 0),
+        "MissingSignatureInstruction"
+      );
+    });
+
+    it("rejects an intent signed by someone other than the user", async () => {
+      const message = sendIntent(user.publicKey, recipient, SEND_AMOUNT, 0);
+      await expectError(
+        initiateRelayedSend(relayer, user.publicKey, recipient, SEND_AMOUNT, 0, [
+          signIntent(Keypair.generate(), message),
+        ]),
+        "SignerMismatch"
+      );
+    });
+
+    it("rejects an intent that doesn't match the send", async () => {
+      const message = sendIntent(user.publicKey, recipient, SEND_AMOUNT * 2, 0);
+      await expectError(
+        initiateRelayedSend(relayer, user.publicKey, recipient, SEND_AMOUNT, 0, [
+          signIntent(user, message),
+        ]),
+        "IntentMismatch"
+      );
+    });
+
+    it("rejects a deactivated relayer", async () => {
+      const toggle = () =>
+        program.methods
+          .toggleRelayer()
+          .accountsPartial({
+            state: statePda,
+            relayer: relayerPda(relayer.publicKey),
+            admin,
+          })
+          .rpc();
+
+      await toggle();
+      const message = sendIntent(user.publicKey, recipient, SEND_AMOUNT, 0);
+      await expectError(
+        initiateRelayedSend(relayer, user.publicKey, recipient, SEND_AMOUNT, 0, [
+          signIntent(user, message),
+        ]),
+        "RelayerNotActive"
+      );
+      await toggle();
+    });
+
+    it("locks the amount from the profile and completes without the user's signature", async () => {
+      const message = sendIntent(user.publicKey, recipient, SEND_AMOUNT, 0);
+      await initiateRelayedSend(relayer, user.publicKey, recipient, SEND_AMOUNT, 0, [
+        signIntent(user, message),
+      ]);
+
+      const request = await program.account.transactionRequest.fetch(
+        requestPda(user.publicKey)
+      );
+      expect(request.relayer.toBase58()).to.equal(relayer.publicKey.toBase58());
+      expect(request.fundsLocked).to.equal(true);
+      const profile = await program.account.userProfile.fetch(
+        userProfilePda(user.publicKey)
+      );
+      expect(profile.balance.toNumber()).to.equal(profileDeposit - SEND_AMOUNT);
+
+      await sleep(VIEW_WAIT_MS);
+      const relayedAccounts = {
+        ...completeAccounts(user.publicKey, recipient),
+        payer: relayer.publicKey,
+        rentPayer: relayer.publicKey,
+      };
+
+      await expectError(
+        program.methods
+          .completeTransactionAfterAd(new BN(VIEW_SECONDS))
+          .accountsPartial(relayedAccounts)
+          .signers([relayer])
+          .rpc(),
+        "RelayerRequired"
+      );
+
+      const recipientBefore = await balance(recipient);
+      await program.methods
+        .completeTransactionAfterAd(new BN(VIEW_SECONDS))
+        .accountsPartial({
+          ...relayedAccounts,
+          relayer: relayerPda(relayer.publicKey),
+        })
+        .signers([relayer])
+        .rpc();
+
+      expect(await balance(recipient)).to.equal(recipientBefore + SEND_AMOUNT);
+      const relayerAccount = await program.account.relayer.fetch(
+        relayerPda(relayer.publicKey)
+      );
+      expect(relayerAccount.totalRelayed.toNumber()).to.equal(1);
+      expect(
+        await program.account.transactionRequest.fetchNullable(
+          requestPda(user.publicKey)
+        )
+      ).to.equal(null);
+    });
+
+    it("withdraws the unspent profile balance", async () => {
+      const withdraw = (amount: number) =>
+        program.methods
+          .withdrawFromProfile(new BN(amount))
+          .accountsPartial({
+            userProfile: userProfilePda(user.publicKey),
+            user: user.publicKey,
+          })
+          .signers([user])
+          .rpc();
+
+      const remaining = profileDeposit - SEND_AMOUNT;
+      await expectError(withdraw(remaining + 1), "InsufficientProfileBalance");
+      await withdraw(remaining);
+      const profile = await program.account.userProfile.fetch(
+        userProfilePda(user.publicKey)
+      );
+      expect(profile.balance.toNumber()).to.equal(0);
+    });
+
+    it("requires the user's signature for requests it didn't relay", async () => {
+      const sender = await fundedKeypair();
+      await initiateSend(sender, recipient);
+      await sleep(VIEW_WAIT_MS);
+
+      await expectError(
+        program.methods
+          .completeTransactionAfterAd(new BN(VIEW_SECONDS))
+          .accountsPartial({
+            ...completeAccounts(sender.publicKey, recipient),
+            payer: relayer.publicKey,
+          })
+          .signers([relayer])
+          .rpc(),
+        "Unauthorized"
+      );
+
+      // A relayer account on a request it didn't initiate is refused
+      await registerRelayer(sender.publicKey);
+      await expectError(
+        completeSend(sender, recipient, { relayer: relayerPda(sender.publicKey) }),
+        "UnexpectedRelayer"
+      );
+
+      await completeSend(sender, recipient);
+    });
+  });
+
+  describe("intent nonces (user-027)", () => {
+    let relayer: Keypair;
+    let user: Keypair;
+    let recipient: PublicKey;
+
+    const relay = (nonce: number, signedNonce = nonce) =>
+      initiateRelayedSend(relayer, user.publicKey, recipient, SEND_AMOUNT, nonce, [
+        signIntent(user, sendIntent(user.publicKey, recipient, SEND_AMOUNT, signedNonce)),
+      ]);
+    const profileNonce = async () =>
+      (
+        await program.account.userProfile.fetch(userProfilePda(user.publicKey))
+      ).nonce.toNumber();
+
+    before(async () => {
+      relayer = await fundedKeypair(2);
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+      await registerRelayer(relayer.publicKey);
+      await createProfile(user, relayer, 0.1 * LAMPORTS_PER_SOL);
+    });
+
+    it("rejects a nonce other than the profile's next one", async () => {
+      expect(await profileNonce()).to.equal(0);
+      await expectError(relay(1), "InvalidNonce");
+    });
+
+    it("rejects a nonce the user didn't sign", async () => {
+      await expectError(relay(0, 5), "IntentMismatch");
+    });
+
+    it("consumes nonces in order and refuses a replayed intent", async () => {
+      await relay(0);
+      expect(await profileNonce()).to.equal(1);
+      await cancelSend(user, { rentPayer: relayer.publicKey });
+
+      await expectError(relay(0), "InvalidNonce");
+
+      await relay(1);
+      expect(await profileNonce()).to.equal(2);
+      await cancelSend(user, { rentPayer: relayer.publicKey });
+    });
+  });
+
+  describe("batch sends (user-028)", () => {
+    let user: Keypair;
+    let recipients: PublicKey[];
+    const batchPda = (owner: PublicKey) =>
+      pda(Buffer.from("batch"), owner.toBuffer());
+
+    const payoutsTo = (targets: PublicKey[]) =>
+      targets.map((recipient, index) => ({
+        recipient,
+        amount: new BN(SEND_AMOUNT * (index + 1)),
+      }));
+    const meta = (pubkey: PublicKey, isWritable: boolean): AccountMeta => ({
+      pubkey,
+      isSigner: false,
+      isWritable,
+    });
+
+    const initiateBatch = async (
+      payouts: { recipient: PublicKey; amount: BN }[],
+      accessEntries = payouts.map((payout) => meta(blockedPda(payout.recipient), false))
+    ) =>
+      program.methods
+        .initiateBatchSend(payouts)
+        .accountsPartial({
+          state: statePda,
+          batch: batchPda(user.publicKey),
+          selectedAd: baseAd,
+          adRegistry: registryPda,
+          impressions: impressionsPda(user.publicKey),
+          preferences: null,
+          campaign: null,
+          user: user.publicKey,
+          userBlocked: blockedPda(user.publicKey),
+          userAllowed: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(accessEntries.concat(await auctionAccounts()))
+        .signers([user])
+        .rpc();
+    const completeBatch = (targets: PublicKey[]) =>
+      program.methods
+        .completeBatchAfterAd(new BN(VIEW_SECONDS))
+        .accountsPartial({
+          state: statePda,
+          treasury: treasuryPda,
+          ad: baseAd,
+          batch: batchPda(user.publicKey),
+          user: user.publicKey,
+          feeAccount,
+          viewReceipt: viewReceiptPda(user.publicKey, baseAd),
+          campaign: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(targets.map((recipient) => meta(recipient, true)))
+        .signers([user])
+        .rpc();
+
+    before(async () => {
+      user = await fundedKeypair(1);
+      recipients = [await newRecipient(), await newRecipient(), await newRecipient()];
+    });
+
+    it("rejects empty and oversized batches", async () => {
+      await expectError(initiateBatch([], []), "InvalidBatchSize");
+
+      const tooMany = Array.from({ length: 11 }, () => ({
+        recipient: Keypair.generate().publicKey,
+        amount: new BN(SEND_AMOUNT),
+      }));
+      await expectError(initiateBatch(tooMany, []), "InvalidBatchSize");
+    });
+
+    it("requires an access entry per recipient", async () => {
+      const payouts = payoutsTo(recipients);
+      await expectError(
+        initiateBatch(payouts, [meta(blockedPda(recipients[0]), false)]),
+        "InvalidAccessEntry"
+      );
+    });
+
+    it("pays every recipient its exact amount with one sponsored fee", async () => {
+      const payouts = payoutsTo(recipients);
+      await initiateBatch(payouts);
+      const balancesBefore = await Promise.all(recipients.map(balance));
+      const feeBefore = await balance(feeAccount);
+      const batch = await program.account.batchRequest.fetch(batchPda(user.publicKey));
+      expect(batch.recipientCount).to.equal(recipients.length);
+
+      await sleep(VIEW_WAIT_MS);
+      await expectError(
+        completeBatch([recipients[1], recipients[0], recipients[2]]),
+        "RecipientMismatch"
+      );
+      await completeBatch(recipients);
+
+      const after = await Promise.all(recipients.map(balance));
+      after.forEach((lamports, index) =>
+        expect(lamports).to.equal(balancesBefore[index] + payouts[index].amount.toNumber())
+      );
+      expect(await balance(feeAccount)).to.equal(
+        feeBefore + batch.calculatedFee.toNumber()
+      );
+      expect(
+        await program.account.batchRequest.fetchNullable(batchPda(user.publicKey))
+      ).to.equal(null);
+    });
+
+    it("releases the reserved fee when a batch is cancelled", async () => {
+      const reserved = async () =>
+        (await program.account.programState.fetch(statePda)).reservedFunds.toNumber();
+      const reservedBefore = await reserved();
+
+      await initiateBatch(payoutsTo(recipients));
+      expect(await reserved()).to.be.greaterThan(reservedBefore);
+
+      await program.methods
+        .cancelBatchRequest()
+        .accountsPartial({
+          state: statePda,
+          batch: batchPda(user.publicKey),
+          ad: null,
+          user: user.publicKey,
+        })
+        .signers([user])
+        .rpc();
+      expect(await reserved()).to.equal(reservedBefore);
+    });
+  });
+
+  describe("recurring payments (user-029)", () => {
+    const HOUR = 3_600;
+    let user: Keypair;
+    let executor: Keypair;
+    let recipient: PublicKey;
+    const subscriptionPda = (id: number) =>
+      pda(Buffer.from("subscription"), user.publicKey.toBuffer(), u64(id));
+
+    const createSubscription = (
+      id: number,
+      sponsorship: object,
+      interval = HOUR,
+      paymentCount = 2
+    ) =>
+      program.methods
+        .createSubscription(
+          new BN(id),
+          recipient,
+          new BN(SEND_AMOUNT),
+          new BN(interval),
+          paymentCount,
+          sponsorship as any
+        )
+        .accountsPartial({
+          state: statePda,
+          subscription: subscriptionPda(id),
+          user: user.publicKey,
+          userBlocked: blockedPda(user.publicKey),
+          userAllowed: null,
+          recipientBlocked: blockedPda(recipient),
+          recipientAllowed: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+    const beginAd = async (id: number) =>
+      program.methods
+        .beginSubscriptionAd()
+        .accountsPartial({
+          state: statePda,
+          subscription: subscriptionPda(id),
+          selectedAd: baseAd,
+          adRegistry: registryPda,
+          impressions: impressionsPda(user.publicKey),
+          preferences: null,
+          campaign: null,
+          user: user.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(await auctionAccounts())
+        .signers([user])
+        .rpc();
+    const confirmAd = (id: number) =>
+      program.methods
+        .confirmSubscriptionAd(new BN(VIEW_SECONDS))
+        .accountsPartial({
+          state: statePda,
+          treasury: treasuryPda,
+          subscription: subscriptionPda(id),
+          ad: baseAd,
+          viewReceipt: viewReceiptPda(user.publicKey, baseAd),
+          campaign: null,
+          user: user.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+    const execute = (id: number, signer: Keypair) =>
+      program.methods
+        .executeSubscriptionPayment()
+        .accountsPartial({
+          state: statePda,
+          treasury: treasuryPda,
+          subscription: subscriptionPda(id),
+          user: user.publicKey,
+          recipient,
+          executor: signer.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([signer])
+        .rpc();
+
+    before(async () => {
+      user = await fundedKeypair(1);
+      executor = await fundedKeypair();
+      recipient = await newRecipient();
+    });
+
+    it("rejects short intervals and empty schedules", async () => {
+      await expectError(
+        createSubscription(0, { adPerCycle: {} }, HOUR - 1),
+        "InvalidInterval"
+      );
+      await expectError(
+        createSubscription(0, { adPerCycle: {} }, HOUR, 0),
+        "InvalidPaymentCount"
+      );
+    });
+
+    it("pays an ad-sponsored cycle once its view is confirmed", async () => {
+      await createSubscription(1, { adPerCycle: {} });
+      await expectError(execute(1, executor), "AdNotViewed");
+
+      await pause(PAUSE_INITIATE);
+      await expectError(beginAd(1), "ProgramPaused");
+      await unpause(PAUSE_INITIATE);
+
+      await beginAd(1);
+      await expectError(confirmAd(1), "InsufficientViewTime");
+      await sleep(VIEW_WAIT_MS);
+      await confirmAd(1);
+
+      const recipientBefore = await balance(recipient);
+      const executorBefore = await balance(executor.publicKey);
+      await execute(1, executor);
+
+      // The crank fee is capped at MAX_CRANK_FEE however large the send's fee
+      expect(await balance(recipient)).to.equal(recipientBefore + SEND_AMOUNT);
+      expect(await balance(executor.publicKey)).to.equal(
+        executorBefore + Math.min(await gasFee(SEND_AMOUNT), 10_000)
+      );
+      const subscription = await program.account.subscription.fetch(subscriptionPda(1));
+      expect(subscription.remainingPayments).to.equal(1);
+      expect(subscription.cycleAdViewed).to.equal(false);
+    });
+
+    it("doesn't let a view sponsor a cycle that isn't due yet", async () => {
+      await expectError(beginAd(1), "PaymentNotDue");
+      await expectError(execute(1, executor), "PaymentNotDue");
+    });
+
+    it("pays no crank fee to subscribers executing their own payments", async () => {
+      await createSubscription(2, { prepaidCredits: {} });
+      await expectError(beginAd(2), "InvalidSponsorship");
+
+      const credits = (await program.account.subscription.fetch(subscriptionPda(2)))
+        .feeCredits;
+      await execute(2, user);
+      const subscription = await program.account.subscription.fetch(subscriptionPda(2));
+      expect(subscription.feeCredits.toString()).to.equal(credits.toString());
+    });
+
+    it("returns the remaining deposit when cancelled", async () => {
+      for (const id of [1, 2]) {
+        await program.methods
+          .cancelSubscription()
+          .accountsPartial({ subscription: subscriptionPda(id), ad: null, user: user.publicKey })
+          .signers([user])
+          .rpc();
+        expect(
+          await program.account.subscription.fetchNullable(subscriptionPda(id))
+        ).to.equal(null);
+      }
+    });
+  });
+
+  describe("escrowed sends (user-030)", () => {
+    let user: Keypair;
+    let arbiter: Keypair;
+    let recipient: PublicKey;
+    const escrowPda = (id: number) =>
+      pda(Buffer.from("escrow"), user.publicKey.toBuffer(), u64(id));
+
+    const initiateEscrow = async (id: number, refundAfter?: number) =>
+      program.methods
+        .initiateEscrowSend(
+          new BN(id),
+          recipient,
+          new BN(SEND_AMOUNT),
+          arbiter.publicKey,
+          new BN(refundAfter ?? Math.floor(Date.now() / 1000) + 3_600)
+        )
+        .accountsPartial({
+          ...sendAccounts(user.publicKey, recipient),
+          escrow: escrowPda(id),
+        })
+        .remainingAccounts(await auctionAccounts())
+        .signers([user])
+        .rpc();
+    const resolve = (id: number, action: "release" | "refund", authority: Keypair) =>
+      (action === "release"
+        ? program.methods.releaseEscrow()
+        : program.methods.refundEscrow()
+      )
+        .accountsPartial({
+          state: statePda,
+          escrow: escrowPda(id),
+          user: user.publicKey,
+          recipient,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+    const escrowStatus = async (id: number) =>
+      Object.keys((await program.account.escrow.fetch(escrowPda(id))).status)[0];
+
+    before(async () => {
+      user = await fundedKeypair(1);
+      arbiter = await fundedKeypair();
+      recipient = await newRecipient();
+    });
+
+    it("rejects a refund deadline inside the request timeout", async () => {
+      await expectError(
+        initiateEscrow(0, Math.floor(Date.now() / 1000) + 60),
+        "InvalidRefundDeadline"
+      );
+    });
+
+    it("holds the amount after the view and releases it to the recipient", async () => {
+      await initiateEscrow(1);
+      expect(await escrowStatus(1)).to.equal("awaitingAd");
+      await expectError(resolve(1, "release", user), "InvalidStatus");
+      await expectError(resolve(1, "refund", arbiter), "InvalidStatus");
+
+      await sleep(VIEW_WAIT_MS);
+      const recipientBefore = await balance(recipient);
+      await completeSend(user, recipient, { escrow: escrowPda(1) });
+      expect(await escrowStatus(1)).to.equal("held");
+      expect(await balance(recipient)).to.equal(recipientBefore);
+
+      await expectError(resolve(1, "release", await fundedKeypair()), "Unauthorized");
+      await expectError(resolve(1, "refund", user), "Unauthorized");
+
+      await resolve(1, "release", arbiter);
+      expect(await balance(recipient)).to.equal(recipientBefore + SEND_AMOUNT);
+      expect(await program.account.escrow.fetchNullable(escrowPda(1))).to.equal(null);
+    });
+
+    it("lets the arbiter refund a held escrow", async () => {
+      await initiateEscrow(2);
+      await sleep(VIEW_WAIT_MS);
+      await completeSend(user, recipient, { escrow: escrowPda(2) });
+
+      const userBefore = await balance(user.publicKey);
+      const recipientBefore = await balance(recipient);
+      await resolve(2, "refund", arbiter);
+      expect(await balance(user.publicKey)).to.be.greaterThan(userBefore + SEND_AMOUNT);
+      expect(await balance(recipient)).to.equal(recipientBefore);
+    });
+
+    it("returns the escrowed amount when the request is cancelled", async () => {
+      await initiateEscrow(3);
+      const userBefore = await balance(user.publicKey);
+      await cancelSend(user, { escrow: escrowPda(3) });
+      expect(await balance(user.publicKey)).to.be.greaterThan(userBefore + SEND_AMOUNT);
+      expect(await program.account.escrow.fetchNullable(escrowPda(3))).to.equal(null);
+    });
+  });
+
+  describe("locked sends (user-031)", () => {
+    let user: Keypair;
+    let recipient: PublicKey;
+
+    const initiateLocked = async (sender: Keypair, amount = SEND_AMOUNT) =>
+      program.methods
+        .initiateLockedSend(recipient, new BN(amount))
+        .accountsPartial(sendAccounts(sender.publicKey, recipient))
+        .remainingAccounts(await auctionAccounts())
+        .signers([sender])
+        .rpc();
+
+    before(async () => {
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+    });
+
+    it("leaves the amount with the user for a plain send", async () => {
+      await initiateSend(user, recipient);
+      const request = await program.account.transactionRequest.fetch(
+        requestPda(user.publicKey)
+      );
+      expect(request.fundsLocked).to.equal(false);
+      await cancelSend(user);
+    });
+
+    it("refuses to lock more than the user holds", async () => {
+      const poor = await fundedKeypair(0.05);
+      await expectError(initiateLocked(poor, LAMPORTS_PER_SOL), "insufficient lamports");
+    });
+
+    it("moves the amount into the request and pays it out on completion", async () => {
+      await initiateLocked(user);
+      const requestInfo = await connection.getAccountInfo(requestPda(user.publicKey));
+      const rent = await connection.getMinimumBalanceForRentExemption(
+        requestInfo.data.length
+      );
+      expect(requestInfo.lamports).to.equal(rent + SEND_AMOUNT);
+
+      await sleep(VIEW_WAIT_MS);
+      const recipientBefore = await balance(recipient);
+      await completeSend(user, recipient);
+      expect(await balance(recipient)).to.equal(recipientBefore + SEND_AMOUNT);
+    });
+
+    it("returns the locked amount on cancel", async () => {
+      await initiateLocked(user);
+      const userBefore = await balance(user.publicKey);
+      await cancelSend(user);
+      expect(await balance(user.publicKey)).to.be.greaterThan(userBefore + SEND_AMOUNT);
+    });
+  });
+
+  describe("treasury reservations (user-032)", () => {
+    let user: Keypair;
+    let recipient: PublicKey;
+    const fetchState = () => program.account.programState.fetch(statePda);
+    const withdraw = (amount: BN) =>
+      program.methods
+        .withdrawFunds(amount)
+        .accountsPartial({
+          state: statePda,
+          treasury: treasuryPda,
+          admin,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+    before(async () => {
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+    });
+
+    it("reserves the fee at initiation and releases it on cancel", async () => {
+      const reservedBefore = (await fetchState()).reservedFunds;
+      await initiateSend(user, recipient);
+      const request = await program.account.transactionRequest.fetch(
+        requestPda(user.publicKey)
+      );
+      expect((await fetchState()).reservedFunds.toString()).to.equal(
+        reservedBefore.add(request.calculatedFee).toString()
+      );
+
+      await cancelSend(user);
+      expect((await fetchState()).reservedFunds.toString()).to.equal(
+        reservedBefore.toString()
+      );
+    });
+
+    it("keeps reserved funds out of admin withdrawals", async () => {
+      await initiateSend(user, recipient);
+      const { calculatedFee } = await program.account.transactionRequest.fetch(
+        requestPda(user.publicKey)
+      );
+      const state = await fetchState();
+      const available = state.totalFunds.sub(state.reservedFunds);
+      await expectError(withdraw(available.addn(1)), "InsufficientProgramFunds");
+      await withdraw(new BN(1_000));
+
+      await sleep(VIEW_WAIT_MS);
+      await completeSend(user, recipient);
+      expect((await fetchState()).reservedFunds.toString()).to.equal(
+        state.reservedFunds.sub(calculatedFee).toString()
+      );
+    });
+
+    it("refuses a send whose fee the unreserved funds can't cover", async () => {
+      const state = await fetchState();
+      const available = state.totalFunds.sub(state.reservedFunds);
+      await expectError(
+        initiateSend(user, recipient, available.addn(1).muln(1000).toNumber()),
+        "InsufficientProgramFunds"
+      );
+    });
+  });
+
+  describe("treasury stake (user-033)", () => {
+    const STAKE_CONFIG = new PublicKey("StakeConfig11111111111111111111111111111111");
+    // Each run stakes at fresh indices
+    const undelegated = Date.now();
+    const delegated = undelegated + 1;
+    const prefunded = undelegated + 2;
+    const stakePda = (index: number) =>
+      pda(Buffer.from("treasury_stake"), u64(index));
+    const stakeRecordPda = (index: number) =>
+      pda(Buffer.from("stake_record"), u64(index));
+    const fetchState = () => program.account.programState.fetch(statePda);
+
+    const createStake = (index: number, amount: BN | number, signer?: Keypair) => {
+      const builder = program.methods
+        .createTreasuryStake(new BN(index), new BN(amount))
+        .accountsPartial({
+          state: statePda,
+          treasury: treasuryPda,
+          stakeAccount: stakePda(index),
+          stakeRecord: stakeRecordPda(index),
+          admin: signer ? signer.publicKey : admin,
+          rent: SYSVAR_RENT_PUBKEY,
+          stakeProgram: StakeProgram.programId,
+          systemProgram: SystemProgram.programId,
+        });
+      return (signer ? builder.signers([signer]) : builder).rpc();
+    };
+    const withdrawStake = (index: number, amount: number) =>
+      program.methods
+        .withdrawTreasuryStake(new BN(index), new BN(amount))
+        .accountsPartial({
+          state: statePda,
+          treasury: treasuryPda,
+          stakeAccount: stakePda(index),
+          stakeRecord: stakeRecordPda(index),
+          clock: SYSVAR_CLOCK_PUBKEY,
+          stakeHistory: SYSVAR_STAKE_HISTORY_PUBKEY,
+          admin,
+          stakeProgram: StakeProgram.programId,
+        })
+        .rpc();
+    const stakeState = async (index: number) => {
+      const info = await connection.getParsedAccountInfo(stakePda(index));
+      return (info.value.data as any).parsed;
+    };
+
+    it("only stakes above rent and above the liquid buffer", async () => {
+      await expectError(createStake(undelegated, 1_000), "InvalidAmount");
+
+      const state = await fetchState();
+      const tooMuch = state.totalFunds
+        .sub(state.reservedFunds)
+        .sub(state.minLiquidBuffer)
+        .addn(1);
+      await expectError(createStake(undelegated, tooMuch), "LiquidBufferTooLow");
+
+      await expectError(
+        createStake(undelegated, LAMPORTS_PER_SOL, await fundedKeypair()),
+        "Unauthorized"
+      );
+    });
+
+    it("creates a stake account owned by the stake program", async () => {
+      const stateBefore = await fetchState();
+      await createStake(undelegated, LAMPORTS_PER_SOL);
+
+      const info = await connection.getAccountInfo(stakePda(undelegated));
+      expect(info.owner.toBase58()).to.equal(StakeProgram.programId.toBase58());
+      expect(info.lamports).to.equal(LAMPORTS_PER_SOL);
+      expect((await stakeState(undelegated)).type).to.equal("initialized");
+
+      const state = await fetchState();
+      expect(state.stakedFunds.toString()).to.equal(
+        stateBefore.stakedFunds.addn(LAMPORTS_PER_SOL).toString()
+      );
+      expect(state.totalFunds.toString()).to.equal(
+        stateBefore.totalFunds.subn(LAMPORTS_PER_SOL).toString()
+      );
+    });
+
+    it("creates a stake account someone already sent lamports to", async () => {
+      // Above any minimum delegation once rent is set aside
+      const amount = 2 * LAMPORTS_PER_SOL;
+      const griefed = 0.01 * LAMPORTS_PER_SOL;
+      await fund(stakePda(delegated), griefed);
+      await createStake(delegated, amount);
+
+      const info = await connection.getAccountInfo(stakePda(delegated));
+      expect(info.lamports).to.equal(amount + griefed);
+    });
+
+    it("withdraws undelegated stake back into the treasury", async () => {
+      const amount = LAMPORTS_PER_SOL / 2;
+      const stateBefore = await fetchState();
+      const treasuryBefore = await balance(treasuryPda);
+
+      await withdrawStake(undelegated, amount);
+
+      expect(await balance(treasuryPda)).to.equal(treasuryBefore + amount);
+      const state = await fetchState();
+      expect(state.stakedFunds.toString()).to.equal(
+        stateBefore.stakedFunds.subn(amount).toString()
+      );
+      const record = await program.account.treasuryStake.fetch(stakeRecordPda(undelegated));
+      expect(record.principal.toNumber()).to.equal(LAMPORTS_PER_SOL - amount);
+    });
+
+    it("counts only lamports beyond an account's own principal as rewards", async () => {
+      // Other stake accounts still hold principal, so the treasury-wide total
+      // would hide these extra lamports
+      const amount = LAMPORTS_PER_SOL;
+      const extra = 0.01 * LAMPORTS_PER_SOL;
+      await fund(stakePda(prefunded), extra);
+      await createStake(prefunded, amount);
+
+      const stateBefore = await fetchState();
+      await withdrawStake(prefunded, amount + extra);
+
+      const state = await fetchState();
+      expect(state.stakedFunds.toString()).to.equal(
+        stateBefore.stakedFunds.subn(amount).toString()
+      );
+      expect(state.totalFunds.toString()).to.equal(
+        stateBefore.totalFunds.addn(amount + extra).toString()
+      );
+      const record = await program.account.treasuryStake.fetch(stakeRecordPda(prefunded));
+      expect(record.principal.toNumber()).to.equal(0);
+    });
+
+    it("delegates to a validator and deactivates", async () => {
+      const { current } = await connection.getVoteAccounts();
+      const voteAccount = new PublicKey(current[0].votePubkey);
+
+      await program.methods
+        .delegateTreasuryStake(new BN(delegated))
+        .accountsPartial({
+          state: statePda,
+          treasury: treasuryPda,
+          stakeAccount: stakePda(delegated),
+          voteAccount,
+          clock: SYSVAR_CLOCK_PUBKEY,
+          stakeHistory: SYSVAR_STAKE_HISTORY_PUBKEY,
+          stakeConfig: STAKE_CONFIG,
+          admin,
+          stakeProgram: StakeProgram.programId,
+        })
+        .rpc();
+      const delegation = (await stakeState(delegated)).info.stake.delegation;
+      expect(delegation.voter).to.equal(voteAccount.toBase58());
+
+      await program.methods
+        .deactivateTreasuryStake(new BN(delegated))
+        .accountsPartial({
+          state: statePda,
+          treasury: treasuryPda,
+          stakeAccount: stakePda(delegated),
+          clock: SYSVAR_CLOCK_PUBKEY,
+          admin,
+          stakeProgram: StakeProgram.programId,
+        })
+        .rpc();
+      const deactivated = (await stakeState(delegated)).info.stake.delegation;
+      expect(deactivated.deactivationEpoch).to.not.equal("18446744073709551615");
+    });
+  });
+
+  describe("public donations (user-034)", () => {
+    let donor: Keypair;
+    const contributionPda = (owner: PublicKey) =>
+      pda(Buffer.from("contribution"), owner.toBuffer());
+    const donate = (amount: number) =>
+      program.methods
+        .donate(new BN(amount))
+        .accountsPartial({
+          state: statePda,
+          treasury: treasuryPda,
+          contribution: contributionPda(donor.publicKey),
+          donor: donor.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([donor])
+        .rpc();
+
+    before(async () => {
+      donor = await fundedKeypair(1);
+    });
+
+    it("rejects empty and oversized donations", async () => {
+      await expectError(donate(0), "InvalidAmount");
+      await expectError(donate(10 * LAMPORTS_PER_SOL + 1), "InvalidAmount");
+    });
+
+    it("adds donations to the treasury and the donor's running total", async () => {
+      const amount = 0.1 * LAMPORTS_PER_SOL;
+      const stateBefore = await program.account.programState.fetch(statePda);
+      const treasuryBefore = await balance(treasuryPda);
+
+      await donate(amount);
+      const first = await program.account.sponsorContribution.fetch(
+        contributionPda(donor.publicKey)
+      );
+      expect(first.donor.toBase58()).to.equal(donor.publicKey.toBase58());
+      expect(first.totalContributed.toNumber()).to.equal(amount);
+
+      await donate(amount);
+      const second = await program.account.sponsorContribution.fetch(
+        contributionPda(donor.publicKey)
+      );
+      expect(second.totalContributed.toNumber()).to.equal(2 * amount);
+      expect(second.firstContributedAt.toString()).to.equal(
+        first.firstContributedAt.toString()
+      );
+
+      expect(await balance(treasuryPda)).to.equal(treasuryBefore + 2 * amount);
+      const state = await program.account.programState.fetch(statePda);
+      expect(state.totalFunds.toString()).to.equal(
+        stateBefore.totalFunds.addn(2 * amount).toString()
+      );
+    });
+
+    it("attributes treasury-sponsored sends pro rata to donors", async () => {
+      const contribution = contributionPda(donor.publicKey);
+      const settle = () =>
+        program.methods
+          .settleContribution()
+          .accountsPartial({ state: statePda, contribution })
+          .rpc();
+      await settle();
+      const before = await program.account.sponsorContribution.fetch(contribution);
+      const { totalDeposited } = await program.account.programState.fetch(statePda);
+
+      const user = await fundedKeypair(1);
+      const recipient = await newRecipient();
+      await initiateSend(user, recipient);
+      await sleep(VIEW_WAIT_MS);
+      await completeSend(user, recipient);
+
+      // Settling is permissionless and credits the donor's share of the send
+      await settle();
+      const after = await program.account.sponsorContribution.fetch(contribution);
+      const share = before.totalContributed.mul(
+        new BN("1000000000000000000").div(totalDeposited)
+      );
+      expect(after.sponsorshipUnits.sub(before.sponsorshipUnits).toString()).to.equal(
+        share.toString()
+      );
+      expect(after.sponsoredCount.toString()).to.equal(
+        after.sponsorshipUnits.div(new BN("1000000000000000000")).toString()
+      );
+    });
+
+    it("stops donations while deposits are paused", async () => {
+      await pause(PAUSE_DEPOSIT);
+      await expectError(donate(LAMPORTS_PER_SOL / 100), "ProgramPaused");
+      await unpause(PAUSE_DEPOSIT);
+    });
+  });
+
+  describe("sponsor pools (user-035)", () => {
+    const poolId = `pool-${run}`;
+    const poolPda = (id: string) => pda(Buffer.from("pool"), Buffer.from(id));
+    const pool = poolPda(poolId);
+    let authority: Keypair;
+    let user: Keypair;
+    let recipient: PublicKey;
+
+    const rules = (overrides: { feeBps?: number; allowedRecipients?: PublicKey[] } = {}) => ({
+      baseFee: new BN(2_000),
+      feeBps: 10,
+      maxAmount: new BN(2 * SEND_AMOUNT),
+      allowedRecipients: [recipient],
+      allowedAds: [] as PublicKey[],
+      ...overrides,
+    });
+    const createPool = (id: string, poolRules: ReturnType<typeof rules>) =>
+      program.methods
+        .createSponsorPool(id, authority.publicKey, poolRules)
+        .accountsPartial({
+          state: statePda,
+          pool: poolPda(id),
+          admin,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    const manage = (signer: Keypair) => ({
+      state: statePda,
+      pool,
+      authority: signer.publicKey,
+    });
+    const toggle = () =>
+      program.methods
+        .toggleSponsorPool()
+        .accountsPartial(manage(authority))
+        .signers([authority])
+        .rpc();
+    const fetchPool = () => program.account.sponsorPool.fetch(pool);
+
+    before(async () => {
+      authority = await fundedKeypair(1);
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+    });
+
+    it("validates the pool id and rules", async () => {
+      await expectError(createPool("", rules()), "InvalidPoolId");
+      await expectError(createPool(`${poolId}-a`, rules({ feeBps: 10_001 })), "InvalidFee");
+      const sixRecipients = Array.from({ length: 6 }, () => Keypair.generate().publicKey);
+      await expectError(
+        createPool(`${poolId}-b`, rules({ allowedRecipients: sixRecipients })),
+        "TooManyPoolRules"
+      );
+    });
+
+    it("is funded by anyone and managed by its authority", async () => {
+      await createPool(poolId, rules());
+      await program.methods
+        .fundSponsorPool(new BN(0.1 * LAMPORTS_PER_SOL))
+        .accountsPartial({
+          state: statePda,
+          pool,
+          funder: user.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+      expect((await fetchPool()).balance.toNumber()).to.equal(0.1 * LAMPORTS_PER_SOL);
+
+      await expectError(
+        program.methods
+          .toggleSponsorPool()
+          .accountsPartial(manage(user))
+          .signers([user])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("only sponsors eligible sends", async () => {
+      await expectError(
+        initiateSend(user, await newRecipient(), SEND_AMOUNT, { pool }),
+        "NotEligibleForPool"
+      );
+      await expectError(
+        initiateSend(user, recipient, 3 * SEND_AMOUNT, { pool }),
+        "NotEligibleForPool"
+      );
+
+      await toggle();
+      await expectError(initiateSend(user, recipient, SEND_AMOUNT, { pool }), "PoolNotActive");
+      await toggle();
+    });
+
+    it("pays the fee from the pool under its own schedule", async () => {
+      const poolFee = 2_000 + (SEND_AMOUNT * 10) / 10_000;
+      const stateBefore = await program.account.programState.fetch(statePda);
+
+      await initiateSend(user, recipient, SEND_AMOUNT, { pool });
+      const request = await program.account.transactionRequest.fetch(
+        requestPda(user.publicKey)
+      );
+      expect(request.pool.toBase58()).to.equal(pool.toBase58());
+      expect(request.calculatedFee.toNumber()).to.equal(poolFee);
+      expect((await fetchPool()).reserved.toNumber()).to.equal(poolFee);
+      const state = await program.account.programState.fetch(statePda);
+      expect(state.reservedFunds.toString()).to.equal(stateBefore.reservedFunds.toString());
+
+      await expectError(
+        program.methods
+          .withdrawFromPool(new BN(0.1 * LAMPORTS_PER_SOL))
+          .accountsPartial(manage(authority))
+          .signers([authority])
+          .rpc(),
+        "InsufficientPoolFunds"
+      );
+
+      await sleep(VIEW_WAIT_MS);
+      await expectError(completeSend(user, recipient), "PoolRequired");
+      const feeBefore = await balance(feeAccount);
+      await completeSend(user, recipient, { pool });
+
+      expect(await balance(feeAccount)).to.equal(feeBefore + poolFee);
+      const funded = await fetchPool();
+      expect(funded.balance.toNumber()).to.equal(0.1 * LAMPORTS_PER_SOL - poolFee);
+      expect(funded.reserved.toNumber()).to.equal(0);
+      expect(funded.sponsoredCount.toNumber()).to.equal(1);
+    });
+
+    it("lets the authority withdraw the unreserved balance", async () => {
+      const { balance: poolBalance } = await fetchPool();
+      await program.methods
+        .withdrawFromPool(poolBalance)
+        .accountsPartial(manage(authority))
+        .signers([authority])
+        .rpc();
+      expect((await fetchPool()).balance.toNumber()).to.equal(0);
+    });
+  });
+
+  describe("merchant sponsors (user-036)", () => {
+    let merchant: Keypair;
+    let user: Keypair;
+    const update = (skipAd: boolean, isActive: boolean) =>
+      program.methods
+        .updateMerchantSponsor(skipAd, isActive)
+        .accountsPartial({
+          state: statePda,
+          merchantSponsor: merchantPda(merchant.publicKey),
+          merchant: merchant.publicKey,
+        })
+        .signers([merchant])
+        .rpc();
+    const fetchMerchant = () =>
+      program.account.merchantSponsor.fetch(merchantPda(merchant.publicKey));
+    const viewCount = async () =>
+      (await program.account.advertisement.fetch(baseAd)).viewCount.toNumber();
+
+    before(async () => {
+      merchant = await fundedKeypair(1);
+      user = await fundedKeypair(1);
+
+      await program.methods
+        .registerMerchantSponsor(false)
+        .accountsPartial({
+          merchantSponsor: merchantPda(merchant.publicKey),
+          merchant: merchant.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([merchant])
+        .rpc();
+      await program.methods
+        .fundMerchantSponsor(new BN(0.05 * LAMPORTS_PER_SOL))
+        .accountsPartial({
+          state: statePda,
+          merchantSponsor: merchantPda(merchant.publicKey),
+          funder: user.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+    });
+
+    it("pays the fee for payments to the merchant instead of the treasury", async () => {
+      await initiateSend(user, merchant.publicKey);
+      const { calculatedFee } = await program.account.transactionRequest.fetch(
+        requestPda(user.publicKey)
+      );
+      const stateBefore = await program.account.programState.fetch(statePda);
+      const feeBefore = await balance(feeAccount);
+
+      await sleep(VIEW_WAIT_MS);
+      await completeSend(user, merchant.publicKey);
+
+      const sponsor = await fetchMerchant();
+      expect(sponsor.budget.toNumber()).to.equal(
+        0.05 * LAMPORTS_PER_SOL - calculatedFee.toNumber()
+      );
+      expect(sponsor.sponsoredCount.toNumber()).to.equal(1);
+      expect(await balance(feeAccount)).to.equal(feeBefore + calculatedFee.toNumber());
+      const state = await program.account.programState.fetch(statePda);
+      expect(state.totalFunds.toString()).to.equal(stateBefore.totalFunds.toString());
+    });
+
+    it("waives the ad only when the merchant pays", async () => {
+      await update(true, true);
+      const viewsBefore = await viewCount();
+
+      await initiateSend(user, merchant.publicKey);
+      // The sponsor can't be swapped out to make the payment look unsponsored
+      await expectError(
+        completeSend(user, merchant.publicKey, { merchantSponsor: merchantPda(user.publicKey) }),
+        "ConstraintSeeds"
+      );
+      await completeSend(user, merchant.publicKey);
+      expect(await viewCount()).to.equal(viewsBefore);
+    });
+
+    it("falls back to the treasury once deactivated", async () => {
+      await update(true, false);
+      const { budget } = await fetchMerchant();
+
+      await initiateSend(user, merchant.publicKey);
+      await expectError(completeSend(user, merchant.publicKey), "InsufficientViewTime");
+      await sleep(VIEW_WAIT_MS);
+      await completeSend(user, merchant.publicKey);
+      expect((await fetchMerchant()).budget.toString()).to.equal(budget.toString());
+    });
+
+    it("lets the merchant withdraw its remaining budget", async () => {
+      const withdraw = (amount: BN) =>
+        program.methods
+          .withdrawMerchantBudget(amount)
+          .accountsPartial({
+            state: statePda,
+            merchantSponsor: merchantPda(merchant.publicKey),
+            merchant: merchant.publicKey,
+          })
+          .signers([merchant])
+          .rpc();
+
+      const { budget } = await fetchMerchant();
+      await expectError(withdraw(budget.addn(1)), "InsufficientMerchantBudget");
+      await withdraw(budget);
+      expect((await fetchMerchant()).budget.toNumber()).to.equal(0);
+    });
+  });
+
+  describe("access lists (user-037)", () => {
+    let user: Keypair;
+    let recipient: PublicKey;
+
+    const block = (address: PublicKey) =>
+      program.methods
+        .blockAddress(address, 1)
+        .accountsPartial({
+          state: statePda,
+          blocked: blockedPda(address),
+          admin,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    const unblock = (address: PublicKey) =>
+      program.methods
+        .unblockAddress()
+        .accountsPartial({ state: statePda, blocked: blockedPda(address), admin })
+        .rpc();
+    const allow = (address: PublicKey) =>
+      program.methods
+        .allowAddress(address)
+        .accountsPartial({
+          state: statePda,
+          allowed: allowedPda(address),
+          admin,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    const setAllowlistMode = (enabled: boolean) =>
+      program.methods
+        .setAllowlistMode(enabled)
+        .accountsPartial({ state: statePda, admin })
+        .rpc();
+
+    before(async () => {
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+    });
+
+    after(async () => {
+      await setAllowlistMode(false);
+    });
+
+    it("only lets the admin block addresses", async () => {
+      const outsider = await fundedKeypair();
+      await expectError(
+        program.methods
+          .blockAddress(user.publicKey, 1)
+          .accountsPartial({
+            state: statePda,
+            blocked: blockedPda(user.publicKey),
+            admin: outsider.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([outsider])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("rejects blocked users and recipients until unblocked", async () => {
+      await block(user.publicKey);
+      await expectError(initiateSend(user, recipient), "AddressBlocked");
+      await unblock(user.publicKey);
+
+      await block(recipient);
+      await expectError(initiateSend(user, recipient), "AddressBlocked");
+      await unblock(recipient);
+
+      await initiateSend(user, recipient);
+      await cancelSend(user);
+    });
+
+    it("rejects an access entry for another address", async () => {
+      await expectError(
+        initiateSend(user, recipient, SEND_AMOUNT, {
+          recipientBlocked: blockedPda(user.publicKey),
+        }),
+        "InvalidAccessEntry"
+      );
+    });
+
+    it("only admits allowlisted addresses in allowlist mode", async () => {
+      await setAllowlistMode(true);
+      await expectError(initiateSend(user, recipient), "AddressNotAllowed");
+
+      await allow(user.publicKey);
+      const allowed = {
+        userAllowed: allowedPda(user.publicKey),
+        recipientAllowed: allowedPda(recipient),
+      };
+      await expectError(
+        initiateSend(user, recipient, SEND_AMOUNT, allowed),
+        "AddressNotAllowed"
+      );
+
+      await allow(recipient);
+      await initiateSend(user, recipient, SEND_AMOUNT, allowed);
+      await cancelSend(user);
+      await setAllowlistMode(false);
+    });
+  });
+
+  describe("pause flags (user-038)", () => {
+    let guardian: Keypair;
+    let user: Keypair;
+    let recipient: PublicKey;
+
+    const pauseAs = (authority: Keypair, operations: number) =>
+      program.methods
+        .pauseOperations(operations)
+        .accountsPartial({ state: statePda, authority: authority.publicKey })
+        .signers([authority])
+        .rpc();
+
+    before(async () => {
+      guardian = await fundedKeypair();
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+      await program.methods
+        .setGuardian(guardian.publicKey)
+        .accountsPartial({ state: statePda, admin })
+        .rpc();
+    });
+
+    after(async () => {
+      await unpause(PAUSE_ALL);
+    });
+
+    it("rejects empty and unknown flags", async () => {
+      await expectError(pause(0), "InvalidPauseFlags");
+      await expectError(pause(1 << 5), "InvalidPauseFlags");
+    });
+
+    it("lets the guardian pause but only the admin unpause", async () => {
+      await expectError(pauseAs(await fundedKeypair(), PAUSE_INITIATE), "Unauthorized");
+
+      await pauseAs(guardian, PAUSE_INITIATE);
+      await expectError(initiateSend(user, recipient), "ProgramPaused");
+      await expectError(
+        program.methods
+          .unpauseOperations(PAUSE_INITIATE)
+          .accountsPartial({ state: statePda, admin: guardian.publicKey })
+          .signers([guardian])
+          .rpc(),
+        "Unauthorized"
+      );
+      await unpause(PAUSE_INITIATE);
+    });
+
+    it("pauses completions without stopping initiations", async () => {
+      await pause(PAUSE_COMPLETE);
+      await initiateSend(user, recipient);
+      await sleep(VIEW_WAIT_MS);
+      await expectError(completeSend(user, recipient), "ProgramPaused");
+
+      await unpause(PAUSE_COMPLETE);
+      await completeSend(user, recipient);
+    });
+
+    it("stops treasury withdrawals without holding up user exits", async () => {
+      const deposit = LAMPORTS_PER_SOL / 100;
+      await createProfile(user, user, deposit);
+
+      await pause(PAUSE_WITHDRAW);
+      await expectError(
+        program.methods
+          .withdrawFunds(new BN(1))
+          .accountsPartial({
+            state: statePda,
+            treasury: treasuryPda,
+            admin,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc(),
+        "ProgramPaused"
+      );
+
+      // Users still get their own funds back
+      await program.methods
+        .withdrawFromProfile(new BN(deposit))
+        .accountsPartial({
+          userProfile: userProfilePda(user.publicKey),
+          user: user.publicKey,
+        })
+        .signers([user])
+        .rpc();
+      const profile = await program.account.userProfile.fetch(userProfilePda(user.publicKey));
+      expect(profile.balance.toNumber()).to.equal(0);
+      await unpause(PAUSE_WITHDRAW);
+    });
+
+    it("gates ad management and merchant funding", async () => {
+      await pause(PAUSE_AD_MANAGEMENT);
+      await expectError(createAd(`paused-${run}`), "ProgramPaused");
+      await unpause(PAUSE_AD_MANAGEMENT);
+
+      const merchant = await fundedKeypair();
+      const merchantSponsor = merchantPda(merchant.publicKey);
+      await program.methods
+        .registerMerchantSponsor(false)
+        .accountsPartial({
+          merchantSponsor,
+          merchant: merchant.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([merchant])
+        .rpc();
+
+      await pause(PAUSE_DEPOSIT);
+      await expectError(
+        program.methods
+          .fundMerchantSponsor(new BN(LAMPORTS_PER_SOL / 100))
+          .accountsPartial({
+            state: statePda,
+            merchantSponsor,
+            funder: merchant.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([merchant])
+          .rpc(),
+        "ProgramPaused"
+      );
+      await unpause(PAUSE_DEPOSIT);
+    });
+  });
+
+  describe("circuit breaker (user-039)", () => {
+    let first: Keypair;
+    let second: Keypair;
+    let recipient: PublicKey;
+
+    const setBreaker = (threshold: number, windowSlots: number) =>
+      program.methods
+        .updateCircuitBreaker(new BN(threshold), new BN(windowSlots))
+        .accountsPartial({ state: statePda, admin })
+        .rpc();
+
+    before(async () => {
+      first = await fundedKeypair(1);
+      second = await fundedKeypair(1);
+      recipient = await newRecipient();
+    });
+
+    after(async () => {
+      await setBreaker(0, 9_000);
+      await unpause(PAUSE_COMPLETE);
+    });
+
+    it("rejects an empty window", async () => {
+      await expectError(setBreaker(1, 0), "InvalidOutflowWindow");
+    });
+
+    it("refuses the payout that trips it and pauses completions", async () => {
+      const fee = await gasFee(SEND_AMOUNT);
+      await setBreaker(fee + fee / 2, 1_000_000);
+
+      await initiateSend(first, recipient);
+      await initiateSend(second, recipient);
+      await sleep(VIEW_WAIT_MS);
+      await completeSend(first, recipient);
+
+      const recipientBefore = await balance(recipient);
+      await completeSend(second, recipient);
+      expect(await balance(recipient)).to.equal(recipientBefore);
+      const request = await program.account.transactionRequest.fetch(
+        requestPda(second.publicKey)
+      );
+      expect(request.status).to.have.property("waitingForAd");
+      const state = await program.account.programState.fetch(statePda);
+      expect(state.pausedOperations & PAUSE_COMPLETE).to.equal(PAUSE_COMPLETE);
+
+      await expectError(completeSend(second, recipient), "ProgramPaused");
+    });
+
+    it("pays the refused request once the admin unpauses", async () => {
+      await unpause(PAUSE_COMPLETE);
+      const recipientBefore = await balance(recipient);
+      await completeSend(second, recipient);
+      expect(await balance(recipient)).to.equal(recipientBefore + SEND_AMOUNT);
+    });
+  });
+
+  describe("migrations (user-040)", () => {
+    // Layout-0 ad loaded by the local validator from tests/fixtures, created
+    // inactive before `status` replaced `is_active`
+    const legacyAd = new PublicKey("5qkWURtHC3H4s3QHexrXeZ1gtYPVtQCWLY5rvt1ppuEu");
+
+    const migrateAd = (ad: PublicKey, signer?: Keypair) => {
+      const builder = program.methods.migrateAd().accountsPartial({
+        state: statePda,
+        ad,
+        admin: signer ? signer.publicKey : admin,
+        systemProgram: SystemProgram.programId,
+      });
+      return (signer ? builder.signers([signer]) : builder).rpc();
+    };
+
+    it("leaves current state alone and checks the admin", async () => {
+      const migrateState = (signer?: Keypair) => {
+        const builder = program.methods.migrateState().accountsPartial({
+          state: statePda,
+          admin: signer ? signer.publicKey : admin,
+          systemProgram: SystemProgram.programId,
+        });
+        return (signer ? builder.signers([signer]) : builder).rpc();
+      };
+
+      await expectError(migrateState(await fundedKeypair()), "Unauthorized");
+      await expectError(migrateState(), "AlreadyMigrated");
+    });
+
+    it("refuses to migrate a current ad", async () => {
+      await expectError(migrateAd(baseAd, await fundedKeypair()), "Unauthorized");
+      await expectError(migrateAd(baseAd), "AlreadyMigrated");
+    });
+
+    it("upgrades a pre-versioning ad in place", async function () {
+      const legacy = await connection.getAccountInfo(legacyAd);
+      if (!legacy) {
+        // Fixtures are only loaded on the local validator
+        this.skip();
+      }
+
+      await migrateAd(legacyAd);
+      const migrated = await connection.getAccountInfo(legacyAd);
+      expect(migrated.data.length).to.be.greaterThan(legacy.data.length);
+
+      const ad = await program.account.advertisement.fetch(legacyAd);
+      expect(ad.version).to.equal(1);
+      expect(ad.id).to.equal("legacy-v0");
+      expect(ad.viewCount.toNumber()).to.equal(7);
+      expect(ad.advertiser.toBase58()).to.equal(admin.toBase58());
+      expect(ad.status).to.have.property("paused");
+      expect(ad.campaign).to.equal(null);
+
+      await expectError(migrateAd(legacyAd), "AlreadyMigrated");
+    });
+
+    it("closes a request left pending in the legacy layout", async function () {
+      // Layout-0 request loaded from tests/fixtures, at this user's request PDA
+      const legacyUser = Keypair.fromSeed(new Uint8Array(32).fill(40));
+      const request = requestPda(legacyUser.publicKey);
+      const legacy = await connection.getAccountInfo(request);
+      if (!legacy) {
+        this.skip();
+      }
+      await fund(legacyUser.publicKey, 0.1 * LAMPORTS_PER_SOL);
+
+      const close = (user: Keypair) =>
+        program.methods
+          .closeLegacyRequest()
+          .accountsPartial({ request, user: user.publicKey })
+          .signers([user])
+          .rpc();
+      await expectError(close(await fundedKeypair()), "ConstraintSeeds");
+
+      const userBefore = await balance(legacyUser.publicKey);
+      await close(legacyUser);
+      expect(await connection.getAccountInfo(request)).to.equal(null);
+      // The user pays the transaction fee out of the returned rent
+      expect(await balance(legacyUser.publicKey)).to.equal(
+        userBefore + legacy.lamports - 5_000
+      );
+
+      // The request PDA is free for new sends
+      const recipient = await newRecipient();
+      await initiateSend(legacyUser, recipient);
+      await cancelSend(legacyUser);
+      await expectError(close(legacyUser), "ConstraintOwner");
+    });
+
+    it("leaves current requests to the regular paths", async () => {
+      const user = await fundedKeypair(1);
+      await initiateSend(user, await newRecipient());
+      await expectError(
+        program.methods
+          .closeLegacyRequest()
+          .accountsPartial({ request: requestPda(user.publicKey), user: user.publicKey })
+          .signers([user])
+          .rpc(),
+        "AlreadyMigrated"
+      );
+      await cancelSend(user);
+    });
+  });
+
+  describe("publisher revenue share (user-041)", () => {
+    let publisher: Keypair;
+    let user: Keypair;
+    let recipient: PublicKey;
+    let ad: PublicKey;
+    let publisherAccount: PublicKey;
+    let feePerAd: number;
+
+    const registerPublisher = (authority: PublicKey, signer?: Keypair) => {
+      const builder = program.methods.registerPublisher(authority).accountsPartial({
+        state: statePda,
+        publisher: publisherPda(authority),
+        admin: signer ? signer.publicKey : admin,
+        systemProgram: SystemProgram.programId,
+      });
+      return (signer ? builder.signers([signer]) : builder).rpc();
+    };
+
+    const togglePublisher = (account: PublicKey) =>
+      program.methods
+        .togglePublisher()
+        .accountsPartial({ state: statePda, publisher: account, admin })
+        .rpc();
+
+    before(async () => {
+      publisher = await fundedKeypair();
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+      publisherAccount = publisherPda(publisher.publicKey);
+      feePerAd = (await program.account.programState.fetch(statePda)).feePerAd.toNumber();
+
+      // A bidding ad, so its views are charged and the publisher takes a share
+      ad = await createAd(`pub-${run}`);
+      await fundAd(ad, LAMPORTS_PER_SOL / 10);
+      await setBid(ad, feePerAd);
+      await registerPublisher(publisher.publicKey);
+    });
+
+    after(async () => {
+      await setBid(ad, 0);
+    });
+
+    it("only lets the admin register publishers", async () => {
+      const outsider = await fundedKeypair();
+      await expectError(registerPublisher(outsider.publicKey, outsider), "Unauthorized");
+    });
+
+    it("rejects an inactive publisher at initiation", async () => {
+      await togglePublisher(publisherAccount);
+      await expectError(
+        initiateSend(user, recipient, SEND_AMOUNT, {
+          selectedAd: ad,
+          publisher: publisherAccount,
+        }),
+        "PublisherNotActive"
+      );
+      await togglePublisher(publisherAccount);
+    });
+
+    it("pays the bound publisher its share of the ad charge", async () => {
+      await initiateSend(user, recipient, SEND_AMOUNT, {
+        selectedAd: ad,
+        publisher: publisherAccount,
+      });
+      const request = await program.account.transactionRequest.fetch(
+        requestPda(user.publicKey)
+      );
+      expect(request.adPrice.toNumber()).to.equal(feePerAd);
+      expect(request.publisher.toBase58()).to.equal(publisherAccount.toBase58());
+
+      await sleep(VIEW_WAIT_MS);
+      await expectError(completeSend(user, recipient, { ad }), "PublisherRequired");
+
+      const other = await fundedKeypair();
+      await registerPublisher(other.publicKey);
+      await expectError(
+        completeSend(user, recipient, { ad, publisher: publisherPda(other.publicKey) }),
+        "PublisherMismatch"
+      );
+
+      const { budget } = await program.account.advertisement.fetch(ad);
+      await completeSend(user, recipient, { ad, publisher: publisherAccount });
+
+      const state = await program.account.programState.fetch(statePda);
+      const share = Math.floor((feePerAd * state.publisherShareBps) / 10_000);
+      const account = await program.account.publisher.fetch(publisherAccount);
+      expect(account.claimable.toNumber()).to.equal(share);
+      expect(account.totalEarned.toNumber()).to.equal(share);
+      expect(account.viewsServed.toNumber()).to.equal(1);
+
+      const charged = await program.account.advertisement.fetch(ad);
+      expect(budget.sub(charged.budget).toNumber()).to.equal(feePerAd);
+      expect(charged.reservedBudget.toNumber()).to.equal(0);
+    });
+
+    it("keeps the publisher share, paid from the ad, out of the circuit breaker", async () => {
+      const setBreaker = (threshold: number) =>
+        program.methods
+          .updateCircuitBreaker(new BN(threshold), new BN(1_000_000))
+          .accountsPartial({ state: statePda, admin })
+          .rpc();
+
+      // Room for exactly the sponsored fee, not the fee plus the share
+      await setBreaker(await gasFee(SEND_AMOUNT));
+      await initiateSend(user, recipient, SEND_AMOUNT, {
+        selectedAd: ad,
+        publisher: publisherAccount,
+      });
+      await sleep(VIEW_WAIT_MS);
+      const recipientBefore = await balance(recipient);
+      await completeSend(user, recipient, { ad, publisher: publisherAccount });
+      await setBreaker(0);
+
+      expect(await balance(recipient)).to.equal(recipientBefore + SEND_AMOUNT);
+      const state = await program.account.programState.fetch(statePda);
+      expect(state.pausedOperations & PAUSE_COMPLETE).to.equal(0);
+    });
+
+    it("rejects a publisher the request was not bound to", async () => {
+      await initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: ad });
+      await sleep(VIEW_WAIT_MS);
+      await expectError(
+        completeSend(user, recipient, { ad, publisher: publisherAccount }),
+        "PublisherMismatch"
+      );
+      await completeSend(user, recipient, { ad });
+    });
+
+    it("lets the publisher claim its revenue once", async () => {
+      const claim = (authority: Keypair) =>
+        program.methods
+          .claimPublisherRevenue()
+          .accountsPartial({
+            publisher: publisherPda(authority.publicKey),
+            authority: authority.publicKey,
+          })
+          .signers([authority])
+          .rpc();
+
+      const { claimable } = await program.account.publisher.fetch(publisherAccount);
+      const held = await balance(publisherAccount);
+      await claim(publisher);
+      expect(held - (await balance(publisherAccount))).to.equal(claimable.toNumber());
+      const account = await program.account.publisher.fetch(publisherAccount);
+      expect(account.claimable.toNumber()).to.equal(0);
+
+      await expectError(claim(publisher), "InvalidAmount");
+    });
+  });
+
+  describe("ad auction (user-042)", () => {
+    let user: Keypair;
+    let recipient: PublicKey;
+    let low: PublicKey;
+    let high: PublicKey;
+    let feePerAd: number;
+
+    before(async () => {
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+      feePerAd = (await program.account.programState.fetch(statePda)).feePerAd.toNumber();
+
+      low = await createAd(`low-${run}`);
+      high = await createAd(`high-${run}`);
+      await fundAd(low, LAMPORTS_PER_SOL / 10);
+      await fundAd(high, LAMPORTS_PER_SOL / 10);
+    });
+
+    after(async () => {
+      await setBid(low, 0);
+      await setBid(high, 0);
+    });
+
+    it("only registers approved ads, and only on their advertiser's bid", async () => {
+      const advertiser = await fundedKeypair();
+      const pending = await createAd(`pending-bid-${run}`, advertiser);
+      await expectError(
+        program.methods
+          .setAdBid(new BN(feePerAd))
+          .accountsPartial({
+            state: statePda,
+            ad: pending,
+            adRegistry: registryPda,
+            advertiser: advertiser.publicKey,
+          })
+          .signers([advertiser])
+          .rpc(),
+        "AdNotActive"
+      );
+
+      await expectError(
+        program.methods
+          .setAdBid(new BN(feePerAd))
+          .accountsPartial({
+            state: statePda,
+            ad: low,
+            adRegistry: registryPda,
+            advertiser: advertiser.publicKey,
+          })
+          .signers([advertiser])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("registers bids at the reserve price and above", async () => {
+      await setBid(low, feePerAd - 1);
+      let registry = await program.account.adRegistry.fetch(registryPda);
+      expect(registry.ads.map((key) => key.toBase58())).to.not.contain(low.toBase58());
+
+      await setBid(low, feePerAd + 1_000);
+      await setBid(high, feePerAd + 3_000);
+      registry = await program.account.adRegistry.fetch(registryPda);
+      const keys = registry.ads.map((key) => key.toBase58());
+      expect(keys).to.contain(low.toBase58());
+      expect(keys).to.contain(high.toBase58());
+    });
+
+    it("rejects a placement that leaves a registered ad out", async () => {
+      const accounts = await auctionAccounts();
+      await expectError(
+        program.methods
+          .initiateSendTransaction(recipient, new BN(SEND_AMOUNT))
+          .accountsPartial(sendAccounts(user.publicKey, recipient))
+          .remainingAccounts(accounts.slice(1))
+          .signers([user])
+          .rpc(),
+        "AuctionCandidatesMismatch"
+      );
+    });
+
+    it("serves the highest bidder at the runner-up's price", async () => {
+      await initiateSend(user, recipient);
+      const request = await program.account.transactionRequest.fetch(
+        requestPda(user.publicKey)
+      );
+      expect(request.selectedAdId).to.equal(`high-${run}`);
+      expect(request.adPrice.toNumber()).to.equal(feePerAd + 1_000);
+      let winner = await program.account.advertisement.fetch(high);
+      expect(winner.reservedBudget.toNumber()).to.equal(feePerAd + 1_000);
+
+      await sleep(VIEW_WAIT_MS);
+      await expectError(completeSend(user, recipient), "AdMismatch");
+      await completeSend(user, recipient, { ad: high });
+
+      winner = await program.account.advertisement.fetch(high);
+      expect(winner.reservedBudget.toNumber()).to.equal(0);
+      expect(winner.budget.toNumber()).to.equal(LAMPORTS_PER_SOL / 10 - (feePerAd + 1_000));
+    });
+
+    it("releases the winner's reservation on cancel", async () => {
+      await initiateSend(user, recipient);
+      await expectError(cancelSend(user), "AdRequired");
+      await cancelSend(user, { ad: high });
+
+      const winner = await program.account.advertisement.fetch(high);
+      expect(winner.reservedBudget.toNumber()).to.equal(0);
+    });
+
+    it("leaves ads a sponsor pool doesn't admit out of its sends", async () => {
+      const poolId = `auction-${run}`;
+      const pool = pda(Buffer.from("pool"), Buffer.from(poolId));
+      await program.methods
+        .createSponsorPool(poolId, admin, {
+          baseFee: new BN(2_000),
+          feeBps: 10,
+          maxAmount: new BN(0),
+          allowedRecipients: [],
+          allowedAds: [baseAd, low],
+        })
+        .accountsPartial({ state: statePda, pool, admin, systemProgram: SystemProgram.programId })
+        .rpc();
+      await program.methods
+        .fundSponsorPool(new BN(LAMPORTS_PER_SOL / 100))
+        .accountsPartial({
+          state: statePda,
+          pool,
+          funder: admin,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      await initiateSend(user, recipient, SEND_AMOUNT, { pool });
+      const request = await program.account.transactionRequest.fetch(
+        requestPda(user.publicKey)
+      );
+      expect(request.selectedAdId).to.equal(`low-${run}`);
+      expect(request.adPrice.toNumber()).to.equal(feePerAd);
+
+      await cancelSend(user, { ad: low, pool });
+    });
+
+    it("places batch sends and subscription views through the auction", async () => {
+      const batch = pda(Buffer.from("batch"), user.publicKey.toBuffer());
+      await program.methods
+        .initiateBatchSend([{ recipient, amount: new BN(SEND_AMOUNT) }])
+        .accountsPartial({
+          state: statePda,
+          batch,
+          selectedAd: baseAd,
+          adRegistry: registryPda,
+          impressions: impressionsPda(user.publicKey),
+          preferences: null,
+          campaign: null,
+          user: user.publicKey,
+          userBlocked: blockedPda(user.publicKey),
+          userAllowed: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: blockedPda(recipient), isSigner: false, isWritable: false },
+          ...(await auctionAccounts()),
+        ])
+        .signers([user])
+        .rpc();
+      const batchRequest = await program.account.batchRequest.fetch(batch);
+      expect(batchRequest.selectedAdId).to.equal(`high-${run}`);
+      expect(batchRequest.adPrice.toNumber()).to.equal(feePerAd + 1_000);
+
+      await program.methods
+        .cancelBatchRequest()
+        .accountsPartial({ state: statePda, batch, ad: high, user: user.publicKey })
+        .signers([user])
+        .rpc();
+      expect(
+        (await program.account.advertisement.fetch(high)).reservedBudget.toNumber()
+      ).to.equal(0);
+
+      const subscription = pda(
+        Buffer.from("subscription"),
+        user.publicKey.toBuffer(),
+        u64(0)
+      );
+      await program.methods
+        .createSubscription(
+          new BN(0),
+          recipient,
+          new BN(SEND_AMOUNT),
+          new BN(3_600),
+          1,
+          { adPerCycle: {} } as any
+        )
+        .accountsPartial({
+          state: statePda,
+          subscription,
+          user: user.publicKey,
+          userBlocked: blockedPda(user.publicKey),
+          userAllowed: null,
+          recipientBlocked: blockedPda(recipient),
+          recipientAllowed: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+      await program.methods
+        .beginSubscriptionAd()
+        .accountsPartial({
+          state: statePda,
+          subscription,
+          selectedAd: baseAd,
+          adRegistry: registryPda,
+          impressions: impressionsPda(user.publicKey),
+          preferences: null,
+          campaign: null,
+          user: user.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(await auctionAccounts())
+        .signers([user])
+        .rpc();
+      const cycle = await program.account.subscription.fetch(subscription);
+      expect(cycle.cycleAdId).to.equal(`high-${run}`);
+      expect(cycle.cycleAdPrice.toNumber()).to.equal(feePerAd + 1_000);
+
+      await program.methods
+        .cancelSubscription()
+        .accountsPartial({ subscription, ad: high, user: user.publicKey })
+        .signers([user])
+        .rpc();
+      expect(
+        (await program.account.advertisement.fetch(high)).reservedBudget.toNumber()
+      ).to.equal(0);
+    });
+
+    it("prunes an ad only once it can no longer win", async () => {
+      const prune = (ad: PublicKey) =>
+        program.methods
+          .pruneAdRegistry()
+          .accountsPartial({ state: statePda, ad, adRegistry: registryPda })
+          .rpc();
+
+      await expectError(prune(high), "AdStillEligible");
+
+      const { budget } = await program.account.advertisement.fetch(high);
+      await program.methods
+        .withdrawAdBudget(budget)
+        .accountsPartial({ state: statePda, ad: high, advertiser: admin })
+        .rpc();
+      await prune(high);
+
+      const registry = await program.account.adRegistry.fetch(registryPda);
+      expect(registry.ads.map((key) => key.toBase58())).to.not.contain(high.toBase58());
+      await expectError(prune(high), "AdNotRegistered");
+    });
+  });
+
+  describe("frequency caps (user-043)", () => {
+    let user: Keypair;
+    let recipient: PublicKey;
+    let ad: PublicKey;
+
+    const setCap = (maxImpressions: number, windowSeconds: number, advertiser?: Keypair) => {
+      const builder = program.methods
+        .setAdFrequencyCap(maxImpressions, new BN(windowSeconds))
+        .accountsPartial({
+          state: statePda,
+          ad,
+          advertiser: advertiser ? advertiser.publicKey : admin,
+        });
+      return (advertiser ? builder.signers([advertiser]) : builder).rpc();
+    };
+
+    before(async () => {
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+      ad = await createAd(`capped-${run}`);
+    });
+
+    it("rejects a cap without a window and a stranger's cap", async () => {
+      await expectError(setCap(1, 0), "InvalidFrequencyCap");
+      await expectError(setCap(1, 3_600, await fundedKeypair()), "Unauthorized");
+    });
+
+    it("stops serving a user who reached the cap", async () => {
+      await setCap(1, 3_600);
+      await initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: ad });
+      await cancelSend(user);
+
+      const impressions = await program.account.userImpressions.fetch(
+        impressionsPda(user.publicKey)
+      );
+      const entry = impressions.entries.find((entry) => entry.ad.equals(ad));
+      expect(entry.count).to.equal(1);
+
+      await expectError(
+        initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: ad }),
+        "FrequencyCapReached"
+      );
+
+      // The cap is per user
+      const other = await fundedKeypair(1);
+      await initiateSend(other, recipient, SEND_AMOUNT, { selectedAd: ad });
+      await cancelSend(other);
+    });
+
+    it("serves a bidding rival in place of a capped ad", async () => {
+      const { feePerAd } = await program.account.programState.fetch(statePda);
+      const rival = await createAd(`cap-rival-${run}`);
+      await fundAd(rival, LAMPORTS_PER_SOL / 100);
+      await setBid(rival, feePerAd.toNumber());
+
+      await initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: ad });
+      const request = await program.account.transactionRequest.fetch(
+        requestPda(user.publicKey)
+      );
+      expect(request.selectedAdId).to.equal(`cap-rival-${run}`);
+      await cancelSend(user, { ad: rival });
+
+      await setBid(rival, 0);
+    });
+
+    it("serves the user again once the window passes", async () => {
+      await setCap(1, 2);
+      await sleep(3_000);
+      await initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: ad });
+      await cancelSend(user);
+    });
+
+    it("counts batch sends and subscription views against the cap", async () => {
+      const viewer = await fundedKeypair(1);
+      await setCap(1, 3_600);
+
+      const batch = pda(Buffer.from("batch"), viewer.publicKey.toBuffer());
+      await program.methods
+        .initiateBatchSend([{ recipient, amount: new BN(SEND_AMOUNT) }])
+        .accountsPartial({
+          state: statePda,
+          batch,
+          selectedAd: ad,
+          adRegistry: registryPda,
+          impressions: impressionsPda(viewer.publicKey),
+          preferences: null,
+          campaign: null,
+          user: viewer.publicKey,
+          userBlocked: blockedPda(viewer.publicKey),
+          userAllowed: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: blockedPda(recipient), isSigner: false, isWritable: false },
+          ...(await auctionAccounts()),
+        ])
+        .signers([viewer])
+        .rpc();
+      const impressions = await program.account.userImpressions.fetch(
+        impressionsPda(viewer.publicKey)
+      );
+      expect(impressions.entries.find((entry) => entry.ad.equals(ad)).count).to.equal(1);
+      await program.methods
+        .cancelBatchRequest()
+        .accountsPartial({ state: statePda, batch, ad: null, user: viewer.publicKey })
+        .signers([viewer])
+        .rpc();
+
+      const subscription = pda(
+        Buffer.from("subscription"),
+        viewer.publicKey.toBuffer(),
+        u64(0)
+      );
+      await program.methods
+        .createSubscription(
+          new BN(0),
+          recipient,
+          new BN(SEND_AMOUNT),
+          new BN(3_600),
+          1,
+          { adPerCycle: {} } as any
+        )
+        .accountsPartial({
+          state: statePda,
+          subscription,
+          user: viewer.publicKey,
+          userBlocked: blockedPda(viewer.publicKey),
+          userAllowed: null,
+          recipientBlocked: blockedPda(recipient),
+          recipientAllowed: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([viewer])
+        .rpc();
+      await expectError(
+        program.methods
+          .beginSubscriptionAd()
+          .accountsPartial({
+            state: statePda,
+            subscription,
+            selectedAd: ad,
+            adRegistry: registryPda,
+            impressions: impressionsPda(viewer.publicKey),
+            preferences: null,
+            campaign: null,
+            user: viewer.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .remainingAccounts(await auctionAccounts())
+          .signers([viewer])
+          .rpc(),
+        "FrequencyCapReached"
+      );
+    });
+
+    it("lifts the cap when set to zero", async () => {
+      await setCap(0, 0);
+      for (let i = 0; i < 2; i++) {
+        await initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: ad });
+        await cancelSend(user);
+      }
+    });
+  });
+
+  describe("ad targeting (user-044)", () => {
+    let user: Keypair;
+    let recipient: PublicKey;
+    let ad: PublicKey;
+    let adId: string;
+
+    const CATEGORY = 1 << 1;
+    const MIN_AMOUNT = SEND_AMOUNT;
+    const language = (code: string) => Array.from(Buffer.from(code));
+    const preferencesPda = (user: PublicKey) =>
+      pda(Buffer.from("preferences"), user.toBuffer());
+
+    const setTargeting = (
+      locales: string[],
+      recipientPrograms: PublicKey[] = [],
+      advertiser?: Keypair
+    ) => {
+      const builder = program.methods
+        .setAdTargeting(CATEGORY, locales.map(language), new BN(MIN_AMOUNT), recipientPrograms)
+        .accountsPartial({
+          state: statePda,
+          ad,
+          advertiser: advertiser ? advertiser.publicKey : admin,
+        });
+      return (advertiser ? builder.signers([advertiser]) : builder).rpc();
+    };
+
+    const setPreferences = (categories: number, code: string) =>
+      program.methods
+        .setUserPreferences(categories, language(code))
+        .accountsPartial({
+          preferences: preferencesPda(user.publicKey),
+          user: user.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+
+    const initiateTargeted = (amount = SEND_AMOUNT, to = recipient) =>
+      initiateSend(user, to, amount, {
+        selectedAd: ad,
+        preferences: preferencesPda(user.publicKey),
+      });
+
+    before(async () => {
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+      adId = `targeted-${run}`;
+      ad = await createAd(adId);
+    });
+
+    after(async () => {
+      await setBid(ad, 0);
+    });
+
+    it("rejects too many locales and a stranger's targeting", async () => {
+      await expectError(setTargeting(["en", "fr", "de", "es", "it"]), "InvalidTargeting");
+      await expectError(setTargeting(["en"], [], await fundedKeypair()), "Unauthorized");
+    });
+
+    it("only serves users whose preferences match", async () => {
+      await setTargeting(["en"]);
+      await expectError(
+        initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: ad }),
+        "AdNotTargeted"
+      );
+
+      await setPreferences(CATEGORY, "fr");
+      await expectError(initiateTargeted(), "AdNotTargeted");
+
+      await setPreferences(1 << 2, "en");
+      await expectError(initiateTargeted(), "AdNotTargeted");
+
+      await setPreferences(CATEGORY | (1 << 2), "en");
+      const preferences = await program.account.userPreferences.fetch(
+        preferencesPda(user.publicKey)
+      );
+      expect(preferences.categories).to.equal(CATEGORY | (1 << 2));
+
+      await initiateTargeted();
+      await cancelSend(user);
+    });
+
+    it("only serves transfers above the minimum amount", async () => {
+      await expectError(initiateTargeted(MIN_AMOUNT - 1), "AdNotTargeted");
+    });
+
+    it("only serves the targeted recipients", async () => {
+      await setTargeting(["en"], [recipient]);
+      await expectError(initiateTargeted(SEND_AMOUNT, await newRecipient()), "AdNotTargeted");
+      await initiateTargeted();
+      await cancelSend(user);
+      await setTargeting(["en"]);
+    });
+
+    it("only lets a targeted ad outbid for matching users", async () => {
+      const feePerAd = (await program.account.programState.fetch(statePda)).feePerAd;
+      await fundAd(ad, LAMPORTS_PER_SOL / 10);
+      await setBid(ad, feePerAd.toNumber());
+
+      await initiateSend(user, recipient, SEND_AMOUNT, {
+        preferences: preferencesPda(user.publicKey),
+      });
+      let request = await program.account.transactionRequest.fetch(
+        requestPda(user.publicKey)
+      );
+      expect(request.selectedAdId).to.equal(adId);
+      await cancelSend(user, { ad });
+
+      const outsider = await fundedKeypair(1);
+      await initiateSend(outsider, recipient);
+      request = await program.account.transactionRequest.fetch(
+        requestPda(outsider.publicKey)
+      );
+      expect(request.selectedAdId).to.equal(`base-${run}`);
+      expect(request.adPrice.toNumber()).to.equal(0);
+      await cancelSend(outsider);
+    });
+
+    it("applies targeting to batch sends and subscription views", async () => {
+      const batch = pda(Buffer.from("batch"), user.publicKey.toBuffer());
+      const initiateBatch = async (preferences: PublicKey | null) =>
+        program.methods
+          .initiateBatchSend([{ recipient, amount: new BN(SEND_AMOUNT) }])
+          .accountsPartial({
+            state: statePda,
+            batch,
+            selectedAd: ad,
+            adRegistry: registryPda,
+            impressions: impressionsPda(user.publicKey),
+            preferences,
+            campaign: null,
+            user: user.publicKey,
+            userBlocked: blockedPda(user.publicKey),
+            userAllowed: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .remainingAccounts([
+            { pubkey: blockedPda(recipient), isSigner: false, isWritable: false },
+            ...(await auctionAccounts()),
+          ])
+          .signers([user])
+          .rpc();
+
+      await expectError(initiateBatch(null), "AdNotTargeted");
+      await initiateBatch(preferencesPda(user.publicKey));
+      await program.methods
+        .cancelBatchRequest()
+        .accountsPartial({ state: statePda, batch, ad, user: user.publicKey })
+        .signers([user])
+        .rpc();
+
+      const subscription = pda(
+        Buffer.from("subscription"),
+        user.publicKey.toBuffer(),
+        u64(0)
+      );
+      await program.methods
+        .createSubscription(
+          new BN(0),
+          recipient,
+          new BN(MIN_AMOUNT - 1),
+          new BN(3_600),
+          1,
+          { adPerCycle: {} } as any
+        )
+        .accountsPartial({
+          state: statePda,
+          subscription,
+          user: user.publicKey,
+          userBlocked: blockedPda(user.publicKey),
+          userAllowed: null,
+          recipientBlocked: blockedPda(recipient),
+          recipientAllowed: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+      await expectError(
+        program.methods
+          .beginSubscriptionAd()
+          .accountsPartial({
+            state: statePda,
+            subscription,
+            selectedAd: ad,
+            adRegistry: registryPda,
+            impressions: impressionsPda(user.publicKey),
+            preferences: preferencesPda(user.publicKey),
+            campaign: null,
+            user: user.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .remainingAccounts(await auctionAccounts())
+          .signers([user])
+          .rpc(),
+        "AdNotTargeted"
+      );
+    });
+  });
+
+  describe("reference ads (user-045)", () => {
+    let user: Keypair;
+    let recipient: PublicKey;
+
+    const contentHash = Array.from(Buffer.alloc(32, 7));
+
+    const createReferenceAd = (
+      adId: string,
+      contentUri = "ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi",
+      hash = contentHash,
+      mimeType = "image/png"
+    ) =>
+      program.methods
+        .createReferenceAd(adId, contentUri, hash, mimeType, new BN(1_000), new BN(VIEW_SECONDS))
+        .accountsPartial({
+          state: statePda,
+          ad: adPda(adId),
+          creator: admin,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+    before(async () => {
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+    });
+
+    it("rejects unsupported URIs and incomplete creatives", async () => {
+      await expectError(
+        createReferenceAd(`ref-bad-${run}`, "ftp://example.com/banner.png"),
+        "InvalidContentUri"
+      );
+      await expectError(
+        createReferenceAd(`ref-bad-${run}`, "ar://" + "a".repeat(200)),
+        "InvalidContentUri"
+      );
+      await expectError(
+        createReferenceAd(`ref-bad-${run}`, undefined, Array.from(Buffer.alloc(32))),
+        "InvalidCreative"
+      );
+      await expectError(
+        createReferenceAd(`ref-bad-${run}`, undefined, contentHash, ""),
+        "InvalidCreative"
+      );
+    });
+
+    it("stores the reference without allocating inline text", async () => {
+      const adId = `ref-${run}`;
+      await createReferenceAd(adId);
+      const ad = await program.account.advertisement.fetch(adPda(adId));
+      expect(ad.creativeMode).to.have.property("reference");
+      expect(ad.contentUri).to.match(/^ipfs:\/\//);
+      expect(ad.contentHash).to.deep.equal(contentHash);
+      expect(ad.mimeType).to.equal("image/png");
+      expect(ad.url).to.equal("");
+      expect(ad.content).to.equal("");
+
+      const info = await connection.getAccountInfo(adPda(adId));
+      const inline = await connection.getAccountInfo(baseAd);
+      expect(inline.data.length - info.data.length).to.equal(200 + 500);
+    });
+
+    it("serves a reference ad like an inline one", async () => {
+      const ad = adPda(`ref-${run}`);
+      await initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: ad });
+      await sleep(VIEW_WAIT_MS);
+      const recipientBefore = await balance(recipient);
+      await completeSend(user, recipient, { ad });
+      expect(await balance(recipient)).to.equal(recipientBefore + SEND_AMOUNT);
+    });
+  });
+
+  describe("ad moderation (user-046)", () => {
+    let moderator: Keypair;
+    let advertiser: Keypair;
+    let user: Keypair;
+    let recipient: PublicKey;
+
+    const setModerator = (key: PublicKey) =>
+      program.methods.setModerator(key).accountsPartial({ state: statePda, admin }).rpc();
+
+    const approve = (ad: PublicKey, signer: Keypair) =>
+      program.methods
+        .approveAd()
+        .accountsPartial({ state: statePda, ad, moderator: signer.publicKey })
+        .signers([signer])
+        .rpc();
+
+    const reject = (ad: PublicKey, reasonCode: number, signer: Keypair) =>
+      program.methods
+        .rejectAd(reasonCode)
+        .accountsPartial({ state: statePda, ad, moderator: signer.publicKey })
+        .signers([signer])
+        .rpc();
+
+    before(async () => {
+      moderator = await fundedKeypair();
+      advertiser = await fundedKeypair();
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+      await setModerator(moderator.publicKey);
+    });
+
+    after(async () => {
+      await setModerator(admin);
+    });
+
+    it("holds ads from advertisers for review", async () => {
+      const ad = await createAd(`review-${run}`, advertiser);
+      const pending = await program.account.advertisement.fetch(ad);
+      expect(pending.status).to.have.property("pendingReview");
+      expect(pending.advertiser.toBase58()).to.equal(advertiser.publicKey.toBase58());
+
+      await expectError(
+        initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: ad }),
+        "AdNotActive"
+      );
+    });
+
+    it("only lets the moderator review", async () => {
+      const ad = adPda(`review-${run}`);
+      await expectError(approve(ad, advertiser), "Unauthorized");
+      // The admin no longer moderates once a moderator is set
+      await expectError(
+        program.methods
+          .approveAd()
+          .accountsPartial({ state: statePda, ad, moderator: admin })
+          .rpc(),
+        "Unauthorized"
+      );
+      await expectError(reject(ad, 1, advertiser), "Unauthorized");
+    });
+
+    it("serves an ad once approved", async () => {
+      const ad = adPda(`review-${run}`);
+      await approve(ad, moderator);
+      expect((await program.account.advertisement.fetch(ad)).status).to.have.property(
+        "approved"
+      );
+      await expectError(approve(ad, moderator), "InvalidAdStatus");
+
+      await initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: ad });
+      await cancelSend(user);
+    });
+
+    it("takes a rejected ad out of rotation for good", async () => {
+      const ad = adPda(`review-${run}`);
+      await reject(ad, 3, moderator);
+      const rejected = await program.account.advertisement.fetch(ad);
+      expect(rejected.status).to.have.property("rejected");
+      expect(rejected.rejectionReason).to.equal(3);
+
+      await expectError(
+        initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: ad }),
+        "AdNotActive"
+      );
+      await expectError(approve(ad, moderator), "InvalidAdStatus");
+      await expectError(reject(ad, 3, moderator), "InvalidAdStatus");
+      await expectError(
+        program.methods
+          .toggleAd()
+          .accountsPartial({ state: statePda, ad, admin })
+          .rpc(),
+        "InvalidAdStatus"
+      );
+    });
+  });
+
+  describe("ad reports (user-047)", () => {
+    let ad: PublicKey;
+    let recipient: PublicKey;
+    let viewers: Keypair[];
+    let threshold: number;
+
+    const setThreshold = (value: number, signer?: Keypair) => {
+      const builder = program.methods.updateReportThreshold(value).accountsPartial({
+        state: statePda,
+        admin: signer ? signer.publicKey : admin,
+      });
+      return (signer ? builder.signers([signer]) : builder).rpc();
+    };
+
+    const report = (user: Keypair, reason = 1) =>
+      program.methods
+        .reportAd(reason)
+        .accountsPartial({
+          state: statePda,
+          ad,
+          viewReceipt: viewReceiptPda(user.publicKey, ad),
+          report: pda(Buffer.from("report"), ad.toBuffer(), user.publicKey.toBuffer()),
+          user: user.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+
+    before(async () => {
+      threshold = (await program.account.programState.fetch(statePda)).reportThreshold;
+      recipient = await newRecipient();
+      ad = await createAd(`reported-${run}`);
+
+      viewers = [];
+      for (let i = 0; i < 2; i++) {
+        viewers.push(await fundedKeypair(1));
+        await initiateSend(viewers[i], recipient, SEND_AMOUNT, { selectedAd: ad });
+      }
+      await sleep(VIEW_WAIT_MS);
+      for (const viewer of viewers) {
+        await completeSend(viewer, recipient, { ad });
+      }
+    });
+
+    after(async () => {
+      await setThreshold(threshold);
+    });
+
+    it("only lets the admin set a positive threshold", async () => {
+      await expectError(setThreshold(0), "InvalidReportThreshold");
+      await expectError(setThreshold(1, await fundedKeypair()), "Unauthorized");
+    });
+
+    it("only takes reports from users who viewed the ad", async () => {
+      await expectError(report(await fundedKeypair()), "AccountNotInitialized");
+
+      // A merchant-waived ad leaves a receipt without a view
+      const merchant = await fundedKeypair(1);
+      const merchantSponsor = merchantPda(merchant.publicKey);
+      await program.methods
+        .registerMerchantSponsor(true)
+        .accountsPartial({
+          merchantSponsor,
+          merchant: merchant.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([merchant])
+        .rpc();
+      await program.methods
+        .fundMerchantSponsor(new BN(LAMPORTS_PER_SOL / 100))
+        .accountsPartial({
+          state: statePda,
+          merchantSponsor,
+          funder: merchant.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([merchant])
+        .rpc();
+
+      const skipper = await fundedKeypair(1);
+      await initiateSend(skipper, merchant.publicKey, SEND_AMOUNT, { selectedAd: ad });
+      await completeSend(skipper, merchant.publicKey, { ad });
+      await expectError(report(skipper), "NoViewReceipt");
+    });
+
+    it("takes one report per viewer", async () => {
+      await report(viewers[0], 2);
+      const stored = await program.account.adReport.fetch(
+        pda(Buffer.from("report"), ad.toBuffer(), viewers[0].publicKey.toBuffer())
+      );
+      expect(stored.reason).to.equal(2);
+      expect((await program.account.advertisement.fetch(ad)).reportCount).to.equal(1);
+
+      await expectError(report(viewers[0]), "already in use");
+    });
+
+    it("suspends an ad reported beyond the threshold until approved", async () => {
+      await setThreshold(1);
+      await report(viewers[1]);
+      const suspended = await program.account.advertisement.fetch(ad);
+      expect(suspended.reportCount).to.equal(2);
+      expect(suspended.status).to.have.property("suspended");
+
+      await expectError(
+        initiateSend(viewers[0], recipient, SEND_AMOUNT, { selectedAd: ad }),
+        "AdNotActive"
+      );
+
+      await program.methods
+        .approveAd()
+        .accountsPartial({ state: statePda, ad, moderator: admin })
+        .rpc();
+      const reinstated = await program.account.advertisement.fetch(ad);
+      expect(reinstated.status).to.have.property("approved");
+      expect(reinstated.reportCount).to.equal(0);
+    });
+  });
+
+  describe("cost-per-click billing (user-048)", () => {
+    let user: Keypair;
+    let recipient: PublicKey;
+    let ad: PublicKey;
+    let feePerAd: number;
+
+    const click = (viewer: Keypair, clicked = ad) =>
+      program.methods
+        .recordClick()
+        .accountsPartial({
+          state: statePda,
+          treasury: treasuryPda,
+          ad: clicked,
+          viewReceipt: viewReceiptPda(viewer.publicKey, clicked),
+          campaign: null,
+          publisher: null,
+          user: viewer.publicKey,
+        })
+        .signers([viewer])
+        .rpc();
+
+    before(async () => {
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+      feePerAd = (await program.account.programState.fetch(statePda)).feePerAd.toNumber();
+
+      ad = await createAd(`cpc-${run}`);
+      await fundAd(ad, LAMPORTS_PER_SOL / 10);
+    });
+
+    after(async () => {
+      await setBid(ad, 0);
+    });
+
+    it("only lets the advertiser change the billing model", async () => {
+      const outsider = await fundedKeypair();
+      await expectError(
+        program.methods
+          .setAdBillingModel({ perClick: {} })
+          .accountsPartial({ state: statePda, ad, advertiser: outsider.publicKey })
+          .signers([outsider])
+          .rpc(),
+        "Unauthorized"
+      );
+
+      await program.methods
+        .setAdBillingModel({ perClick: {} })
+        .accountsPartial({ state: statePda, ad, advertiser: admin })
+        .rpc();
+      await setBid(ad, feePerAd);
+      const updated = await program.account.advertisement.fetch(ad);
+      expect(updated.billingModel).to.have.property("perClick");
+    });
+
+    it("charges nothing for the view itself", async () => {
+      await initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: ad });
+      await sleep(VIEW_WAIT_MS);
+      await completeSend(user, recipient, { ad });
+
+      const viewed = await program.account.advertisement.fetch(ad);
+      expect(viewed.budget.toNumber()).to.equal(LAMPORTS_PER_SOL / 10);
+      expect(viewed.reservedBudget.toNumber()).to.equal(0);
+      const receipt = await program.account.viewReceipt.fetch(
+        viewReceiptPda(user.publicKey, ad)
+      );
+      expect(receipt.clickPrice.toNumber()).to.equal(feePerAd);
+      expect(receipt.clicked).to.equal(false);
+    });
+
+    it("charges the clearing price on the first click only", async () => {
+      const stateBefore = await program.account.programState.fetch(statePda);
+      await click(user);
+
+      const clicked = await program.account.advertisement.fetch(ad);
+      expect(clicked.clickCount.toNumber()).to.equal(1);
+      expect(clicked.budget.toNumber()).to.equal(LAMPORTS_PER_SOL / 10 - feePerAd);
+      const state = await program.account.programState.fetch(statePda);
+      expect(state.totalFunds.sub(stateBefore.totalFunds).toNumber()).to.equal(feePerAd);
+
+      await expectError(click(user), "AlreadyClicked");
+    });
+
+    it("only counts clicks from users who viewed the ad", async () => {
+      await expectError(click(await fundedKeypair()), "AccountNotInitialized");
+    });
+
+    it("counts clicks on per-view ads without charging", async () => {
+      await initiateSend(user, recipient);
+      await sleep(VIEW_WAIT_MS);
+      await completeSend(user, recipient);
+
+      const { budget, clickCount } = await program.account.advertisement.fetch(baseAd);
+      await click(user, baseAd);
+      const clicked = await program.account.advertisement.fetch(baseAd);
+      expect(clicked.clickCount.toNumber()).to.equal(clickCount.toNumber() + 1);
+      expect(clicked.budget.toString()).to.equal(budget.toString());
+    });
+  });
+
+  describe("conversion attribution (user-049)", () => {
+    let authority: Keypair;
+    let viewers: Keypair[];
+    let recipient: PublicKey;
+    let ad: PublicKey;
+    let adId: string;
+
+    const setAuthority = (key: PublicKey, windowSeconds: number, advertiser?: Keypair) => {
+      const builder = program.methods
+        .setConversionAuthority(key, new BN(windowSeconds))
+        .accountsPartial({
+          state: statePda,
+          ad,
+          advertiser: advertiser ? advertiser.publicKey : admin,
+        });
+      return (advertiser ? builder.signers([advertiser]) : builder).rpc();
+    };
+
+    const convert = (user: PublicKey, signer = authority) =>
+      program.methods
+        .recordConversion(user, adId)
+        .accountsPartial({
+          state: statePda,
+          ad,
+          viewReceipt: viewReceiptPda(user, ad),
+          conversionAuthority: signer.publicKey,
+        })
+        .signers([signer])
+        .rpc();
+
+    before(async () => {
+      authority = await fundedKeypair();
+      recipient = await newRecipient();
+      adId = `conv-${run}`;
+      ad = await createAd(adId);
+
+      viewers = [];
+      for (let i = 0; i < 2; i++) {
+        viewers.push(await fundedKeypair(1));
+        await initiateSend(viewers[i], recipient, SEND_AMOUNT, { selectedAd: ad });
+      }
+      await sleep(VIEW_WAIT_MS);
+      for (const viewer of viewers) {
+        await completeSend(viewer, recipient, { ad });
+      }
+    });
+
+    it("requires a window with an authority, set by the advertiser", async () => {
+      await expectError(setAuthority(authority.publicKey, 0), "InvalidAttributionWindow");
+      await expectError(
+        setAuthority(authority.publicKey, 3_600, await fundedKeypair()),
+        "Unauthorized"
+      );
+    });
+
+    it("only takes conversions from the ad's authority", async () => {
+      // No authority is set yet
+      await expectError(convert(viewers[0].publicKey), "Unauthorized");
+
+      await setAuthority(authority.publicKey, 3_600);
+      await expectError(convert(viewers[0].publicKey, await fundedKeypair()), "Unauthorized");
+    });
+
+    it("attributes one conversion per view", async () => {
+      await convert(viewers[0].publicKey);
+      const receipt = await program.account.viewReceipt.fetch(
+        viewReceiptPda(viewers[0].publicKey, ad)
+      );
+      expect(receipt.converted).to.equal(true);
+      expect((await program.account.advertisement.fetch(ad)).conversions.toNumber()).to.equal(1);
+
+      await expectError(convert(viewers[0].publicKey), "AlreadyConverted");
+    });
+
+    it("rejects users who never viewed the ad", async () => {
+      await expectError(convert(Keypair.generate().publicKey), "AccountNotInitialized");
+    });
+
+    it("rejects conversions after the attribution window", async () => {
+      await setAuthority(authority.publicKey, 1);
+      await sleep(2_000);
+      await expectError(convert(viewers[1].publicKey), "AttributionWindowClosed");
+    });
+  });
+
+  describe("campaigns (user-050)", () => {
+    let user: Keypair;
+    let recipient: PublicKey;
+    let variantA: PublicKey;
+    let variantB: PublicKey;
+    let campaign: PublicKey;
+    const campaignId = `camp-${run}`;
+
+    const campaignPda = (id: string) => pda(Buffer.from("campaign"), Buffer.from(id));
+
+    const createCampaign = (id: string) =>
+      program.methods
+        .createCampaign(id)
+        .accountsPartial({
+          campaign: campaignPda(id),
+          advertiser: admin,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+    const addVariant = (ad: PublicKey, weight: number, advertiser?: Keypair) => {
+      const builder = program.methods.addCampaignVariant(weight).accountsPartial({
+        campaign,
+        ad,
+        advertiser: advertiser ? advertiser.publicKey : admin,
+      });
+      return (advertiser ? builder.signers([advertiser]) : builder).rpc();
+    };
+
+    const setWeight = (ad: PublicKey, weight: number) =>
+      program.methods
+        .setVariantWeight(weight)
+        .accountsPartial({ campaign, ad, advertiser: admin })
+        .rpc();
+
+    const toggleCampaign = (advertiser?: Keypair) => {
+      const builder = program.methods.toggleCampaign().accountsPartial({
+        campaign,
+        advertiser: advertiser ? advertiser.publicKey : admin,
+      });
+      return (advertiser ? builder.signers([advertiser]) : builder).rpc();
+    };
+
+    before(async () => {
+      user = await fundedKeypair(1);
+      recipient = await newRecipient();
+      variantA = await createAd(`variant-a-${run}`);
+      variantB = await createAd(`variant-b-${run}`);
+      campaign = campaignPda(campaignId);
+    });
+
+    it("rejects empty and overlong campaign ids", async () => {
+      await expectError(createCampaign(""), "InvalidCampaignId");
+      await expectError(createCampaign("c".repeat(33)), "InvalidCampaignId");
+    });
+
+    it("only lets the advertiser add its own ads, once", async () => {
+      await createCampaign(campaignId);
+      await expectError(addVariant(variantA, 1, await fundedKeypair()), "Unauthorized");
+
+      // All traffic goes to A while B has no weight
+      await addVariant(variantA, 1);
+      await addVariant(variantB, 0);
+      await expectError(addVariant(variantA, 1), "InvalidCampaignVariant");
+
+      const stored = await program.account.campaign.fetch(campaign);
+      expect(stored.variants.map((variant) => variant.weight)).to.deep.equal([1, 0]);
+      const ad = await program.account.advertisement.fetch(variantA);
+      expect(ad.campaign.toBase58()).to.equal(campaign.toBase58());
+    });
+
+    it("requires the campaign with its ads", async () => {
+      await expectError(
+        initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: variantA }),
+        "CampaignRequired"
+      );
+      await expectError(
+        initiateSend(user, recipient, SEND_AMOUNT, { campaign }),
+        "InvalidCampaignVariant"
+      );
+    });
+
+    it("only serves users the variant they are assigned", async () => {
+      await expectError(
+        initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: variantB, campaign }),
+        "VariantMismatch"
+      );
+
+      await initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: variantA, campaign });
+      await sleep(VIEW_WAIT_MS);
+      await expectError(completeSend(user, recipient, { ad: variantA }), "CampaignRequired");
+      await completeSend(user, recipient, { ad: variantA, campaign });
+
+      const stored = await program.account.campaign.fetch(campaign);
+      expect(stored.variants[0].views.toNumber()).to.equal(1);
+      expect(stored.variants[1].views.toNumber()).to.equal(0);
+    });
+
+    it("moves traffic when the weights change", async () => {
+      await setWeight(variantA, 0);
+      await setWeight(variantB, 1);
+      await expectError(
+        initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: variantA, campaign }),
+        "VariantMismatch"
+      );
+      await initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: variantB, campaign });
+      await cancelSend(user);
+    });
+
+    it("stops serving a paused campaign", async () => {
+      await expectError(toggleCampaign(await fundedKeypair()), "Unauthorized");
+      await toggleCampaign();
+      expect((await program.account.campaign.fetch(campaign)).isActive).to.equal(false);
+      await expectError(
+        initiateSend(user, recipient, SEND_AMOUNT, { selectedAd: variantB, campaign }),
+        "CampaignNotActive"
+      );
+      await toggleCampaign();
+    });
+
+    it("holds batch sends and subscription views to the split too", async () => {
+      const batch = pda(Buffer.from("batch"), user.publicKey.toBuffer());
+      const initiateBatch = async (selectedAd: PublicKey, campaignAccount: PublicKey | null) =>
+        program.methods
+          .initiateBatchSend([{ recipient, amount: new BN(SEND_AMOUNT) }])
+          .accountsPartial({
+            state: statePda,
+            batch,
+            selectedAd,
+            adRegistry: registryPda,
+            impressions: impressionsPda(user.publicKey),
+            preferences: null,
+            campaign: campaignAccount,
+            user: user.publicKey,
+            userBlocked: blockedPda(user.publicKey),
+            userAllowed: null,
+            systemProgram: SystemProgram.programId,
+          })
+          .remainingAccounts([
+            { pubkey: blockedPda(recipient), isSigner: false, isWritable: false },
+            ...(await auctionAccounts()),
+          ])
+          .signers([user])
+          .rpc();
+
+      await expectError(initiateBatch(variantB, null), "CampaignRequired");
+      await expectError(initiateBatch(variantA, campaign), "VariantMismatch");
+
+      const subscription = pda(
+        Buffer.from("subscription"),
+        user.publicKey.toBuffer(),
+        u64(0)
+      );
+      await program.methods
+        .createSubscription(
+          new BN(0),
+          recipient,
+          new BN(SEND_AMOUNT),
+          new BN(3_600),
+          1,
+          { adPerCycle: {} } as any
+        )
+        .accountsPartial({
+          state: statePda,
+          subscription,
+          user: user.publicKey,
+          userBlocked: blockedPda(user.publicKey),
+          userAllowed: null,
+          recipientBlocked: blockedPda(recipient),
+          recipientAllowed: null,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+      await expectError(
+        program.methods
+          .beginSubscriptionAd()
+          .accountsPartial({
+            state: statePda,
+            subscription,
+            selectedAd: variantB,
+            adRegistry: registryPda,
+            impressions: impressionsPda(user.publicKey),
+            preferences: null,
+            campaign: null,
+            user: user.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .remainingAccounts(await auctionAccounts())
+          .signers([user])
+          .rpc(),
+        "CampaignRequired"
+      );
+    });
+
+    it("caps the number of variants", async () => {
+      for (let i = 0; i < 3; i++) {
+        await addVariant(await createAd(`variant-${i}-${run}`), 1);
+      }
+      await expectError(
+        addVariant(await createAd(`variant-extra-${run}`), 1),
+        "TooManyVariants"
+      );
+    });
+  });
+});